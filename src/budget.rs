@@ -0,0 +1,161 @@
+//! Size-capped text export for seeding an agent's context.
+
+use crate::model::IdPrefix;
+use crate::ticket::{format_export_text_with, TicketExport};
+use crate::workflow::Workflow;
+
+/// Rough conversion used for `--budget N` given in tokens.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    Tokens(usize),
+    Chars(usize),
+}
+
+impl Budget {
+    pub fn chars(self) -> usize {
+        match self {
+            Budget::Tokens(n) => n.saturating_mul(CHARS_PER_TOKEN),
+            Budget::Chars(n) => n,
+        }
+    }
+}
+
+/// Relevance tier, most relevant first: claimed by `me`, other in-progress
+/// work, ready, blocked, then everything else.
+fn tier(t: &TicketExport, me: Option<&str>) -> u8 {
+    if me.is_some() && t.claimed_by.as_deref() == me {
+        0
+    } else if t.status == "in-progress" {
+        1
+    } else if t.ready {
+        2
+    } else if t.status == "blocked" {
+        3
+    } else {
+        4
+    }
+}
+
+/// Characters of description kept per tier; the rest get none.
+fn description_cap(tier: u8) -> usize {
+    match tier {
+        0 | 1 => 300,
+        2 => 160,
+        3 => 100,
+        _ => 0,
+    }
+}
+
+/// Shorten a description to at most `cap` characters. Whitespace is
+/// collapsed; the cut prefers a sentence end in the second half of the
+/// allowance, then a word boundary, and marks the cut with an ellipsis.
+pub fn truncate_description(desc: &str, cap: usize) -> String {
+    let flat = desc.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= cap {
+        return flat;
+    }
+    if cap == 0 {
+        return String::new();
+    }
+    let head: String = flat.chars().take(cap).collect();
+    let sentence_end = head
+        .char_indices()
+        .filter(|&(i, c)| matches!(c, '.' | '!' | '?') && head[i + c.len_utf8()..].starts_with(' '))
+        .map(|(i, c)| i + c.len_utf8())
+        .next_back();
+    if let Some(end) = sentence_end.filter(|&end| head[..end].chars().count() >= cap / 2) {
+        return head[..end].to_string();
+    }
+    let head: String = flat.chars().take(cap - 1).collect();
+    let cut = head.rfind(' ').filter(|&i| i > 0).unwrap_or(head.len());
+    format!("{}…", head[..cut].trim_end())
+}
+
+/// One-line tally of left-out tickets, e.g. `+37 more todo, 12 done`.
+fn summary(counts: &[(&str, usize)]) -> Option<String> {
+    let parts: Vec<String> = counts
+        .iter()
+        .filter(|(_, n)| *n > 0)
+        .enumerate()
+        .map(|(i, (status, n))| {
+            if i == 0 {
+                format!("+{} more {}", n, status)
+            } else {
+                format!("{} {}", n, status)
+            }
+        })
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(", "))
+    }
+}
+
+/// Counts per status: the workflow's in workflow order, then any others in
+/// the order they appear.
+fn tally<'a>(tickets: &[&'a TicketExport], workflow: &'a Workflow) -> Vec<(&'a str, usize)> {
+    let mut counts: Vec<(&str, usize)> = workflow
+        .statuses()
+        .iter()
+        .map(|d| (d.status.as_str(), 0))
+        .collect();
+    for t in tickets {
+        match counts.iter_mut().find(|(s, _)| t.status == *s) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((t.status.as_str(), 1)),
+        }
+    }
+    counts
+}
+
+/// Render tickets most relevant first until `max_chars` would be exceeded,
+/// then a summary of what was left out. Tickets in higher tiers carry a
+/// truncated description on an indented second line; when that does not fit
+/// the bare ticket line is tried before giving up. The summary counts
+/// statuses in `workflow` order.
+pub fn render_budgeted(
+    exports: &[TicketExport],
+    me: Option<&str>,
+    max_chars: usize,
+    workflow: &Workflow,
+    ids: &IdPrefix,
+) -> String {
+    let mut ranked: Vec<&TicketExport> = exports.iter().collect();
+    ranked.sort_by_key(|t| (tier(t, me), t.priority, t.id));
+
+    let mut out = String::new();
+    let mut used = 0;
+    let mut included = 0;
+    for (i, t) in ranked.iter().enumerate() {
+        let line = format!("{}\n", format_export_text_with(t, ids));
+        let desc = truncate_description(&t.description, description_cap(tier(t, me)));
+        let full = if desc.is_empty() {
+            line.clone()
+        } else {
+            format!("{}  {}\n", line, desc)
+        };
+        // Leave room for the summary of whatever would still be left out.
+        let reserve = summary(&tally(&ranked[i + 1..], workflow))
+            .map(|s| s.chars().count() + 1)
+            .unwrap_or(0);
+        let entry = [full, line]
+            .into_iter()
+            .find(|e| used + e.chars().count() + reserve <= max_chars);
+        match entry {
+            Some(e) => {
+                used += e.chars().count();
+                out.push_str(&e);
+                included += 1;
+            }
+            None => break,
+        }
+    }
+    if let Some(s) = summary(&tally(&ranked[included..], workflow)) {
+        out.push_str(&s);
+        out.push('\n');
+    }
+    out
+}
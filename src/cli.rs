@@ -1,10 +1,46 @@
-use clap::{Args, Parser, Subcommand};
+use std::any::TypeId;
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+
+use crate::model::{IdPrefix, Status, TicketId};
 
 #[derive(Parser)]
-#[command(name = "rtik", about = "Ticket tracker for agents", version)]
+#[command(
+    name = "rtik",
+    about = "Ticket tracker for agents",
+    version,
+    // Lets flags after an expanded `@view` override the view's own values.
+    args_override_self = true
+)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Print one JSON document instead of text (also RTIK_OUTPUT=json or
+    /// output = "json" in .rtik.toml)
+    #[arg(long, global = true)]
+    pub json: bool,
+}
+
+/// The `rtik` command with ticket ids and `--query` read in the project's
+/// `id_prefix`. Parse with `Cli::from_arg_matches` on its matches.
+pub fn command(ids: &IdPrefix) -> clap::Command {
+    with_ids(Cli::command(), ids)
+}
+
+fn with_ids(cmd: clap::Command, ids: &IdPrefix) -> clap::Command {
+    let is = |arg: &clap::Arg, ty: TypeId| arg.get_value_parser().type_id() == ty;
+    let cmd = cmd.mut_args(|arg| {
+        if is(&arg, TypeId::of::<TicketId>()) {
+            let ids = ids.clone();
+            arg.value_parser(move |raw: &str| parse_id_with(raw, &ids))
+        } else if is(&arg, TypeId::of::<crate::query::Expr>()) {
+            let ids = ids.clone();
+            arg.value_parser(move |raw: &str| parse_query_with(raw, &ids))
+        } else {
+            arg
+        }
+    });
+    cmd.mut_subcommands(|sub| with_ids(sub, ids))
 }
 
 #[derive(Subcommand)]
@@ -13,33 +49,62 @@ pub enum Commands {
     #[command(alias = "new")]
     Create(CreateArgs),
     /// Show ticket details
-    #[command(alias = "get")]
-    Get { id: i64 },
+    Get(GetArgs),
     /// Update ticket fields (at least one required)
     #[command(alias = "up")]
     Update(UpdateArgs),
     /// Delete a ticket
     #[command(alias = "rm")]
-    Delete { id: i64 },
+    Delete(DeleteArgs),
     /// List all tickets
     #[command(alias = "ls")]
     List(ListArgs),
     /// Claim a ticket for this agent
     Claim(ClaimArgs),
+    /// Claim the next ready ticket (todo, unclaimed, all deps done)
+    Next(NextArgs),
+    /// Extend the lease on a ticket you have claimed
+    #[command(alias = "hb")]
+    Heartbeat(HeartbeatArgs),
+    /// Release claims whose lease has expired
+    Reap,
     /// Release a claimed ticket
     #[command(alias = "rel")]
     Release(ReleaseArgs),
     /// Block a ticket with a reason
     Block(BlockArgs),
+    /// List labels in use with ticket counts
+    Labels,
+    /// Full-text search over names, descriptions and notes
+    Search(SearchArgs),
+    /// Append a work note to a ticket
+    Note(NoteArgs),
     /// Manage ticket dependencies (add/remove)
-    #[command(alias = "dep")]
     Dep(DepArgs),
     /// Show dependency tree for a ticket
-    #[command(alias = "deps")]
     Deps(DepsArgs),
+    /// Show a ticket's subtask hierarchy with done rollups
+    Tree {
+        #[arg(value_parser = parse_id)]
+        id: TicketId,
+    },
+    /// Order open tickets into waves that can run in parallel
+    Plan(PlanArgs),
+    /// Show ticket history (one ticket, or the global feed)
+    Log(LogArgs),
     /// Export tickets as text or JSON
     #[command(alias = "dump")]
     Export(ExportArgs),
+    /// Import tickets and dependencies from an export file
+    Import(ImportArgs),
+    /// Manage saved views (use with `list @name` / `export @name`)
+    View(ViewArgs),
+    /// Run a Model Context Protocol server on stdin/stdout
+    Mcp,
+    /// Serve a JSON HTTP API on a loopback address or unix socket
+    Serve(ServeArgs),
+    /// Read and change project settings in .rtik.toml
+    Config(ConfigArgs),
 }
 
 #[derive(Args)]
@@ -48,30 +113,76 @@ pub struct CreateArgs {
     pub name: String,
     #[arg(short = 'd', long, help = "Ticket description")]
     pub desc: Option<String>,
+    #[arg(short = 'p', long, value_parser = parse_priority, help = "Priority P0 (urgent) to P4 (default P2)")]
+    pub priority: Option<i64>,
+    #[arg(long = "label", help = "Add a label (repeatable)")]
+    pub labels: Vec<String>,
+    #[arg(long, value_parser = parse_id, help = "Make this a subtask of another ticket")]
+    pub parent: Option<TicketId>,
+}
+
+#[derive(Args)]
+pub struct DeleteArgs {
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "refuse",
+        help = "What to do with subtasks: refuse, cascade (delete them) or orphan (detach them)"
+    )]
+    pub children: ChildPolicyArg,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ChildPolicyArg {
+    Refuse,
+    Cascade,
+    Orphan,
+}
+
+impl From<ChildPolicyArg> for crate::ticket::ChildPolicy {
+    fn from(arg: ChildPolicyArg) -> Self {
+        match arg {
+            ChildPolicyArg::Refuse => crate::ticket::ChildPolicy::Refuse,
+            ChildPolicyArg::Cascade => crate::ticket::ChildPolicy::Cascade,
+            ChildPolicyArg::Orphan => crate::ticket::ChildPolicy::Orphan,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct GetArgs {
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
+    #[arg(long, default_value_t = 3, help = "Number of recent notes to show")]
+    pub notes: usize,
 }
 
 #[derive(Args)]
 pub struct UpdateArgs {
-    pub id: i64,
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
     #[arg(short = 'n', long)]
     pub name: Option<String>,
     #[arg(short = 'd', long)]
     pub desc: Option<String>,
     #[arg(long, value_parser = parse_status)]
-    pub status: Option<String>,
+    pub status: Option<Status>,
+    #[arg(short = 'p', long, value_parser = parse_priority)]
+    pub priority: Option<i64>,
+    #[arg(long = "label", help = "Add a label (repeatable)")]
+    pub labels: Vec<String>,
+    #[arg(long = "unlabel", help = "Remove a label (repeatable)")]
+    pub unlabels: Vec<String>,
 }
 
+/// Filters and ordering shared by `list` and `export`.
 #[derive(Args)]
-pub struct ListArgs {
-    /// Show created/updated timestamps
-    #[arg(long)]
-    pub timestamps: bool,
-    #[arg(
-        long,
-        help = "Filter by status (passes through to DB; returns empty if invalid)"
-    )]
-    pub status: Option<String>,
-    #[arg(long, help = "Show only claimed tickets")]
+pub struct FilterArgs {
+    #[arg(long, value_parser = parse_status, help = "Filter by status")]
+    pub status: Option<Status>,
+    #[arg(long, conflicts_with = "unclaimed", help = "Show only claimed tickets")]
     pub claimed: bool,
     #[arg(long, help = "Show only unclaimed tickets")]
     pub unclaimed: bool,
@@ -82,47 +193,342 @@ pub struct ListArgs {
         help = "Substring search in name+description (repeatable, all terms must match)"
     )]
     pub search: Vec<String>,
+    #[arg(
+        long = "label",
+        help = "Require a label; comma-separate for any-of (a,b), repeat for all-of"
+    )]
+    pub labels: Vec<String>,
+    #[arg(
+        long = "no-label",
+        value_delimiter = ',',
+        help = "Exclude tickets with this label (repeatable)"
+    )]
+    pub no_labels: Vec<String>,
+    #[arg(long, value_parser = parse_id, help = "Only direct subtasks of this ticket")]
+    pub parent: Option<TicketId>,
+    #[arg(
+        long,
+        conflicts_with = "parent",
+        help = "Only tickets without a parent"
+    )]
+    pub top_level: bool,
+    #[arg(long, help = "Only todo, unclaimed tickets whose deps are all done")]
+    pub ready: bool,
+    #[arg(long, help = "Only tickets with unmet dependencies")]
+    pub waiting: bool,
+    #[arg(long, help = "Only open tickets that other open tickets depend on")]
+    pub blocking: bool,
+    #[arg(
+        short = 'q',
+        long,
+        value_parser = parse_query,
+        help = "Filter expression, e.g. 'status:(todo,blocked) AND claimer:bot-* AND updated>2026-10-01'"
+    )]
+    pub query: Option<crate::query::Expr>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_sort_key,
+        help = "Sort keys: priority, created, updated, id; suffix :desc to reverse (e.g. priority,created:desc)"
+    )]
+    pub sort: Vec<crate::ticket::SortKey>,
+    #[arg(long, help = "Reverse the sort order")]
+    pub reverse: bool,
+    #[arg(long, help = "Return at most N tickets")]
+    pub limit: Option<usize>,
+    #[arg(long, default_value_t = 0, help = "Skip the first N matching tickets")]
+    pub offset: usize,
+    #[arg(
+        long,
+        conflicts_with = "offset",
+        help = "Continue after this ticket id (the next_cursor of a previous page)"
+    )]
+    pub after: Option<i64>,
+    #[arg(
+        long,
+        help = "With JSON output, print {\"tickets\", \"next_cursor\"} instead of a bare array"
+    )]
+    pub paged: bool,
+}
+
+impl FilterArgs {
+    /// Whether any flag narrows which tickets match; sorting and paging
+    /// do not count.
+    pub fn has_filters(&self) -> bool {
+        self.status.is_some()
+            || self.claimed
+            || self.unclaimed
+            || self.claimer.is_some()
+            || !self.search.is_empty()
+            || !self.labels.is_empty()
+            || !self.no_labels.is_empty()
+            || self.parent.is_some()
+            || self.top_level
+            || self.ready
+            || self.waiting
+            || self.blocking
+            || self.query.is_some()
+    }
+}
+
+/// Ticket fields selectable with `--columns` (list tables and CSV export).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Column {
+    Id,
+    Name,
+    Description,
+    Status,
+    #[value(alias = "pri")]
+    Priority,
+    #[value(alias = "parent_id")]
+    Parent,
+    #[value(alias = "claimed_by")]
+    Claimer,
+    #[value(alias = "claimed_at")]
+    ClaimedAt,
+    #[value(alias = "lease", alias = "lease_expires_at")]
+    LeaseExpiresAt,
+    #[value(alias = "block_reason")]
+    BlockReason,
+    #[value(alias = "deps")]
+    Dependencies,
+    Labels,
+    Ready,
+    Waiting,
+    Blocking,
+    #[value(alias = "created_at")]
+    Created,
+    #[value(alias = "updated_at")]
+    Updated,
+    #[value(alias = "external_key", alias = "key")]
+    ExternalKey,
+}
+
+/// Columns of `list` when neither `--columns` nor `list.columns` is set.
+pub const DEFAULT_COLUMNS: [Column; 4] =
+    [Column::Id, Column::Priority, Column::Status, Column::Name];
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    #[value(alias = "md")]
+    Markdown,
+    /// One compact JSON object per line, streamed
+    Jsonl,
+    Csv,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum TemplateArg {
+    Status,
+    Claimer,
+    Label,
+}
+
+impl From<TemplateArg> for crate::markdown::Grouping {
+    fn from(arg: TemplateArg) -> Self {
+        match arg {
+            TemplateArg::Status => crate::markdown::Grouping::Status,
+            TemplateArg::Claimer => crate::markdown::Grouping::Claimer,
+            TemplateArg::Label => crate::markdown::Grouping::Label,
+        }
+    }
+}
+
+/// Presentation options shared by `list` and `export`.
+#[derive(Args)]
+pub struct OutputArgs {
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Columns for list tables and CSV, e.g. id,priority,status,name,claimer,labels,created (see README)"
+    )]
+    pub columns: Vec<Column>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Output format: text (default), json or markdown"
+    )]
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Show created/updated timestamps
+    #[arg(long)]
+    pub timestamps: bool,
+    /// Ignore list.filter from .rtik.toml
+    #[arg(long)]
+    pub all: bool,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
 }
 
 #[derive(Args)]
 pub struct ExportArgs {
-    #[arg(long, help = "Filter by status")]
-    pub status: Option<String>,
-    #[arg(long, help = "Show only claimed tickets")]
-    pub claimed: bool,
-    #[arg(long, help = "Show only unclaimed tickets")]
-    pub unclaimed: bool,
-    #[arg(long, help = "Filter by claimer name")]
-    pub claimer: Option<String>,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[arg(long, help = "Include only the most recent note per ticket in JSON")]
+    pub latest_note: bool,
     #[arg(
-        long = "search",
-        help = "Substring search in name+description (repeatable)"
+        long,
+        value_parser = parse_budget,
+        help = "Cap text output, most relevant first: 2000 (tokens), 2000t or 8000c (chars)"
     )]
-    pub search: Vec<String>,
-    #[arg(long, help = "Output as JSON array instead of plain text")]
-    pub json: bool,
+    pub budget: Option<crate::budget::Budget>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Markdown sections: status (default), claimer or label"
+    )]
+    pub template: Option<TemplateArg>,
 }
 
 #[derive(Args)]
 pub struct ClaimArgs {
-    pub id: i64,
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
     #[arg(long, help = "Override another agent's claim")]
     pub force: bool,
+    #[arg(long, value_parser = parse_duration, help = "Lease duration, e.g. 90s, 30m, 2h (default lease.claim, else 1h)")]
+    pub lease: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct NextArgs {
+    #[arg(long, value_parser = parse_duration, help = "Lease duration, e.g. 90s, 30m, 2h (default lease.claim, else 1h)")]
+    pub lease: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct HeartbeatArgs {
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
+    #[arg(long, value_parser = parse_duration, help = "New lease duration from now (default lease.heartbeat, else 1h)")]
+    pub lease: Option<u64>,
 }
 
 #[derive(Args)]
 pub struct ReleaseArgs {
-    pub id: i64,
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
     #[arg(long, help = "Release regardless of ownership")]
     pub force: bool,
 }
 
 #[derive(Args)]
 pub struct BlockArgs {
-    pub id: i64,
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
     pub reason: String,
 }
 
+#[derive(Args)]
+pub struct SearchArgs {
+    /// FTS5 query: words, "phrases", prefix*, NOT, OR, column:term
+    pub query: String,
+    #[arg(long, default_value_t = 20, help = "Maximum number of results")]
+    pub limit: usize,
+}
+
+#[derive(Args)]
+pub struct NoteArgs {
+    #[arg(value_parser = parse_id)]
+    pub id: TicketId,
+    pub text: String,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// File to read, or - for stdin
+    pub file: String,
+    #[arg(
+        long,
+        value_enum,
+        help = "Input format (default: markdown for .md files, otherwise JSON or JSON Lines)"
+    )]
+    pub format: Option<ImportFormatArg>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "parent",
+        help = "Markdown: nested items become subtasks (parent) or dependencies of the outer item (deps)"
+    )]
+    pub nesting: NestingArg,
+    #[arg(long, help = "Show what would be imported without writing anything")]
+    pub dry_run: bool,
+    #[arg(long, help = "Update tickets whose external_key already exists")]
+    pub upsert: bool,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ImportFormatArg {
+    Json,
+    Jsonl,
+    #[value(alias = "md")]
+    Markdown,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum NestingArg {
+    Parent,
+    Deps,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Loopback host:port (port 0 picks a free one) or a unix socket path
+    #[arg(long, default_value = "127.0.0.1:7777")]
+    pub listen: String,
+}
+
+#[derive(Args)]
+pub struct ViewArgs {
+    #[command(subcommand)]
+    pub action: ViewAction,
+}
+
+#[derive(Subcommand)]
+pub enum ViewAction {
+    /// Save list/export flags under a name, replacing any existing view
+    Save {
+        name: String,
+        /// Flags as given to `list` or `export`, e.g. --status todo --sort priority
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// List saved views
+    #[command(alias = "ls")]
+    List,
+    /// Delete a saved view
+    Rm { name: String },
+}
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub action: ConfigAction,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print a setting's effective value
+    Get { key: String },
+    /// Write a setting to .rtik.toml
+    Set { key: String, value: String },
+    /// Remove a setting from .rtik.toml
+    Unset { key: String },
+    /// Show every setting, its value and where it comes from
+    #[command(alias = "ls")]
+    List,
+}
+
 #[derive(Args)]
 pub struct DepArgs {
     #[command(subcommand)]
@@ -132,23 +538,199 @@ pub struct DepArgs {
 #[derive(Subcommand)]
 pub enum DepAction {
     /// Add a dependency: ticket_id depends on dep_id
-    Add { ticket_id: i64, dep_id: i64 },
+    Add {
+        #[arg(value_parser = parse_id)]
+        ticket_id: TicketId,
+        #[arg(value_parser = parse_id)]
+        dep_id: TicketId,
+    },
     /// Remove a dependency
-    Remove { ticket_id: i64, dep_id: i64 },
+    Remove {
+        #[arg(value_parser = parse_id)]
+        ticket_id: TicketId,
+        #[arg(value_parser = parse_id)]
+        dep_id: TicketId,
+    },
+}
+
+#[derive(Args)]
+pub struct LogArgs {
+    /// Ticket to show; omit for the feed across all tickets
+    #[arg(value_parser = parse_id)]
+    pub id: Option<TicketId>,
+    #[arg(long, value_parser = parse_since, help = "Only events since a timestamp (2026-10-01, 2026-10-01T12:00:00Z) or age (30m, 2h, 1d)")]
+    pub since: Option<String>,
+}
+
+#[derive(Args)]
+pub struct PlanArgs {
+    #[arg(
+        long,
+        value_parser = parse_id,
+        help = "Only plan this ticket and its transitive dependencies"
+    )]
+    pub to: Option<TicketId>,
 }
 
 #[derive(Args)]
 pub struct DepsArgs {
-    pub id: i64,
+    #[arg(required_unless_present = "all", value_parser = parse_id)]
+    pub id: Option<TicketId>,
+    #[arg(long, help = "Show the full transitive tree in both directions")]
+    pub tree: bool,
+    #[arg(long, conflicts_with_all = ["id", "tree"], help = "Emit the whole dependency graph")]
+    pub all: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "dot",
+        help = "Graph format for --all"
+    )]
+    pub format: GraphFormatArg,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum GraphFormatArg {
+    Dot,
+    Mermaid,
+}
+
+pub fn parse_status(raw: &str) -> Result<Status, String> {
+    raw.parse()
+        .map_err(|e: crate::ticket::AppError| e.to_string())
+}
+
+/// Accept `12` or `#12`; `command` swaps in `parse_id_with` for projects
+/// with their own `id_prefix`.
+pub fn parse_id(raw: &str) -> Result<TicketId, String> {
+    parse_id_with(raw, &IdPrefix::default())
+}
+
+pub fn parse_id_with(raw: &str, ids: &IdPrefix) -> Result<TicketId, String> {
+    ids.parse(raw.trim())
+        .map_err(|_| format!("invalid ticket id '{}'", raw))
+}
+
+/// Parse a duration like `90`, `90s`, `30m`, `2h` or `1d` into seconds.
+pub fn parse_duration(raw: &str) -> Result<u64, String> {
+    let trimmed = raw.trim();
+    let (digits, unit) = match trimmed.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => trimmed.split_at(pos),
+        None => (trimmed, "s"),
+    };
+    let n: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration '{}': expected e.g. 90s, 30m, 2h", raw))?;
+    let mult = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => {
+            return Err(format!(
+                "invalid duration '{}': unit must be one of s, m, h, d",
+                raw
+            ))
+        }
+    };
+    match n.checked_mul(mult) {
+        Some(secs) if secs <= crate::ticket::MAX_DURATION_SECS => Ok(secs),
+        _ => Err(format!(
+            "invalid duration '{}': must be at most 36500d",
+            raw
+        )),
+    }
+}
+
+/// Accept either an age with a unit (`2h`, `1d`) or an ISO 8601 timestamp or
+/// date prefix, and return a timestamp that sorts against stored values.
+pub fn parse_since(raw: &str) -> Result<String, String> {
+    let trimmed = raw.trim();
+    if trimmed.ends_with(|c: char| c.is_ascii_alphabetic()) && !trimmed.ends_with('Z') {
+        let secs = parse_duration(trimmed)?;
+        return Ok(crate::ticket::utc_now_minus(secs));
+    }
+    if trimmed
+        .get(..4)
+        .is_some_and(|year| year.chars().all(|c| c.is_ascii_digit()))
+    {
+        Ok(trimmed.to_string())
+    } else {
+        Err(format!(
+            "invalid time '{}': expected e.g. 2026-10-01, 2026-10-01T12:00:00Z or 2h",
+            raw
+        ))
+    }
+}
+
+/// Accept `0`-`4` or `P0`-`P4` (case-insensitive); P0 is the most urgent.
+pub fn parse_priority(raw: &str) -> Result<i64, String> {
+    let digits = raw.strip_prefix(['p', 'P']).unwrap_or(raw);
+    match digits.parse::<i64>() {
+        Ok(n) if (0..=4).contains(&n) => Ok(n),
+        _ => Err(format!(
+            "invalid priority '{}': must be P0-P4 (P0 most urgent)",
+            raw
+        )),
+    }
 }
 
-pub fn parse_status(raw: &str) -> Result<String, String> {
-    let normalized = raw.to_lowercase();
-    match normalized.as_str() {
-        "todo" | "in-progress" | "blocked" | "done" => Ok(normalized),
+/// Accept `N`, `Nt`/`Ntokens` or `Nc`/`Nchars`; a bare number is tokens.
+pub fn parse_budget(raw: &str) -> Result<crate::budget::Budget, String> {
+    use crate::budget::Budget;
+    let trimmed = raw.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (digits, unit) = trimmed.split_at(split);
+    let n: usize = digits.parse().map_err(|_| {
+        format!(
+            "invalid budget '{}': expected e.g. 2000, 2000t or 8000c",
+            raw
+        )
+    })?;
+    match unit.trim() {
+        "" | "t" | "tok" | "tokens" => Ok(Budget::Tokens(n)),
+        "c" | "chars" => Ok(Budget::Chars(n)),
         _ => Err(format!(
-            "invalid status '{}': must be one of todo, in-progress, blocked, done",
+            "invalid budget '{}': unit must be tokens (t) or chars (c)",
             raw
         )),
     }
 }
+
+pub fn parse_query(raw: &str) -> Result<crate::query::Expr, String> {
+    parse_query_with(raw, &IdPrefix::default())
+}
+
+pub fn parse_query_with(raw: &str, ids: &IdPrefix) -> Result<crate::query::Expr, String> {
+    crate::query::parse_with(raw, ids).map_err(|e| e.render(raw))
+}
+
+pub fn parse_sort_key(raw: &str) -> Result<crate::ticket::SortKey, String> {
+    use crate::ticket::{SortField, SortKey};
+    let (name, descending) = match raw.split_once(':') {
+        Some((name, "asc")) => (name, false),
+        Some((name, "desc")) => (name, true),
+        Some(_) => {
+            return Err(format!(
+                "invalid sort '{}': direction must be asc or desc",
+                raw
+            ))
+        }
+        None => (raw, false),
+    };
+    let field = match name {
+        "priority" | "pri" => SortField::Priority,
+        "created" => SortField::Created,
+        "updated" => SortField::Updated,
+        "id" => SortField::Id,
+        _ => {
+            return Err(format!(
+                "invalid sort key '{}': must be one of priority, created, updated, id",
+                name
+            ))
+        }
+    };
+    Ok(SortKey { field, descending })
+}
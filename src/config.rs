@@ -0,0 +1,415 @@
+//! `.rtik.toml`: project settings kept next to the database.
+//!
+//! ```toml
+//! agent = "planner"
+//! output = "json"
+//! id_prefix = "T-"
+//! busy_timeout = "10s"
+//!
+//! [lease]
+//! claim = "2h"
+//! heartbeat = "30m"
+//!
+//! [list]
+//! columns = ["id", "priority", "status", "name", "claimer"]
+//! name_width = 60
+//! filter = "status:(todo,in-progress)"
+//! ```
+//!
+//! A setting is taken from the first of: a command-line flag, an environment
+//! variable (`RTIK_AGENT`, `RTIK_OUTPUT`), this file, the built-in default.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cli::{self, Column};
+use crate::model::{IdPrefix, Status};
+use crate::query::{self, Expr};
+use crate::ticket::{AppError, DEFAULT_LEASE_SECS};
+use crate::workflow::{Workflow, WorkflowConfig};
+
+pub const CONFIG_FILE: &str = ".rtik.toml";
+
+/// Default `busy_timeout`: how long a write waits on another writer.
+pub const DEFAULT_BUSY_TIMEOUT_SECS: u64 = 5;
+
+/// Default `list.name_width`, in characters.
+pub const DEFAULT_NAME_WIDTH: usize = 40;
+
+/// Every key `rtik config` reads and writes, in `config list` order.
+pub const KEYS: [&str; 11] = [
+    "agent",
+    "output",
+    "id_prefix",
+    "busy_timeout",
+    "lease.claim",
+    "lease.heartbeat",
+    "list.columns",
+    "list.name_width",
+    "list.filter",
+    "workflow.claim",
+    "workflow.release",
+];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub agent: Option<String>,
+    pub output: Option<OutputMode>,
+    pub id_prefix: Option<String>,
+    #[serde(default, deserialize_with = "duration")]
+    pub busy_timeout: Option<u64>,
+    #[serde(default)]
+    pub lease: LeaseConfig,
+    #[serde(default)]
+    pub list: ListConfig,
+    #[serde(default)]
+    pub workflow: WorkflowConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    Text,
+    Json,
+}
+
+/// `[lease]`: default lease lengths, in seconds once parsed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LeaseConfig {
+    #[serde(default, deserialize_with = "duration")]
+    pub claim: Option<u64>,
+    #[serde(default, deserialize_with = "duration")]
+    pub heartbeat: Option<u64>,
+}
+
+/// `[list]`: how `rtik list` looks when no flags say otherwise.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListConfig {
+    #[serde(default, deserialize_with = "columns")]
+    pub columns: Option<Vec<Column>>,
+    pub name_width: Option<usize>,
+    /// Parsed from `filter` once `id_prefix` is known.
+    #[serde(skip)]
+    pub filter: Option<Filter>,
+    #[serde(rename = "filter")]
+    filter_text: Option<String>,
+}
+
+/// A query expression with the text it was written as.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub text: String,
+    pub expr: Expr,
+}
+
+fn duration<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    let raw = String::deserialize(d)?;
+    cli::parse_duration(&raw)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn columns<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<Column>>, D::Error> {
+    Vec::<String>::deserialize(d)?
+        .iter()
+        .map(|name| {
+            Column::from_str(name, true)
+                .map_err(|_| serde::de::Error::custom(format!("unknown column '{}'", name)))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Env,
+    Config,
+    Default,
+}
+
+/// One key as `rtik config get` and `config list` report it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Setting {
+    pub key: &'static str,
+    pub value: Option<String>,
+    pub source: Source,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, AppError> {
+        let mut config: Config =
+            toml::from_str(text).map_err(|e| AppError::InvalidConfig(e.message().to_string()))?;
+        config.validate()?;
+        if let Some(text) = config.list.filter_text.take() {
+            let expr = query::parse_with(&text, &config.ids())
+                .map_err(|e| AppError::InvalidConfig(e.render(&text)))?;
+            config.list.filter = Some(Filter { text, expr });
+        }
+        Ok(config)
+    }
+
+    /// Read the config at `path`; a missing file is an empty config.
+    pub fn load(path: &Path) -> Result<Config, AppError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|e| match e {
+                AppError::InvalidConfig(msg) => {
+                    AppError::InvalidConfig(format!("{}: {}", path.display(), msg))
+                }
+                other => other,
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(AppError::InvalidConfig(format!(
+                "cannot read {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    fn validate(&self) -> Result<(), AppError> {
+        if self.agent.as_deref().is_some_and(|a| a.trim().is_empty()) {
+            return Err(AppError::InvalidConfig("agent must not be empty".into()));
+        }
+        if let Some(prefix) = &self.id_prefix {
+            if prefix.is_empty()
+                || prefix.contains(char::is_whitespace)
+                || prefix.ends_with(|c: char| c.is_ascii_digit())
+            {
+                return Err(AppError::InvalidConfig(format!(
+                    "id_prefix '{}' must be non-empty, without spaces, and not end in a digit",
+                    prefix
+                )));
+            }
+        }
+        if self.list.name_width.is_some_and(|w| w < 10) {
+            return Err(AppError::InvalidConfig(
+                "list.name_width must be at least 10".into(),
+            ));
+        }
+        Workflow::from_config(&self.workflow)?;
+        Ok(())
+    }
+
+    /// `RTIK_AGENT`, else `agent`.
+    pub fn agent(&self) -> Option<String> {
+        std::env::var("RTIK_AGENT")
+            .ok()
+            .or_else(|| self.agent.clone())
+    }
+
+    /// Whether commands print JSON without `--json`: `RTIK_OUTPUT` when it
+    /// is set, else `output`.
+    pub fn json_output(&self) -> bool {
+        match std::env::var("RTIK_OUTPUT") {
+            Ok(v) => v.eq_ignore_ascii_case("json"),
+            Err(_) => self.output == Some(OutputMode::Json),
+        }
+    }
+
+    /// What ticket ids are written with in text output: `#12`, `T-12`.
+    pub fn id_prefix(&self) -> &str {
+        self.id_prefix.as_deref().unwrap_or("#")
+    }
+
+    pub fn ids(&self) -> IdPrefix {
+        self.id_prefix
+            .as_ref()
+            .map(IdPrefix::new)
+            .unwrap_or_default()
+    }
+
+    pub fn busy_timeout(&self) -> Duration {
+        Duration::from_secs(self.busy_timeout.unwrap_or(DEFAULT_BUSY_TIMEOUT_SECS))
+    }
+
+    /// Lease for `claim` and `next` without `--lease`.
+    pub fn claim_lease(&self) -> u64 {
+        self.lease.claim.unwrap_or(DEFAULT_LEASE_SECS)
+    }
+
+    /// Lease for `heartbeat` without `--lease`; the claim lease unless set.
+    pub fn heartbeat_lease(&self) -> u64 {
+        self.lease.heartbeat.unwrap_or_else(|| self.claim_lease())
+    }
+
+    pub fn name_width(&self) -> usize {
+        self.list.name_width.unwrap_or(DEFAULT_NAME_WIDTH)
+    }
+
+    /// The effective value of `key` and where it came from.
+    pub fn setting(&self, key: &str) -> Result<Setting, AppError> {
+        let key = KEYS
+            .into_iter()
+            .find(|k| *k == key)
+            .ok_or_else(|| AppError::UnknownConfigKey(key.to_string()))?;
+        let env = |var: &str| std::env::var(var).ok();
+        let (value, source) = match key {
+            "agent" => layer(env("RTIK_AGENT"), self.agent.clone(), None),
+            "output" => layer(
+                env("RTIK_OUTPUT"),
+                self.output.map(|o| format!("{:?}", o).to_lowercase()),
+                Some("text".into()),
+            ),
+            "id_prefix" => layer(None, self.id_prefix.clone(), Some("#".into())),
+            "busy_timeout" => layer(
+                None,
+                self.busy_timeout.map(format_duration),
+                Some(format_duration(DEFAULT_BUSY_TIMEOUT_SECS)),
+            ),
+            "lease.claim" => layer(
+                None,
+                self.lease.claim.map(format_duration),
+                Some(format_duration(DEFAULT_LEASE_SECS)),
+            ),
+            "lease.heartbeat" => layer(
+                None,
+                self.lease.heartbeat.map(format_duration),
+                Some(format_duration(self.claim_lease())),
+            ),
+            "list.columns" => {
+                let names = |cols: &[Column]| {
+                    cols.iter()
+                        .filter_map(|c| c.to_possible_value())
+                        .map(|v| v.get_name().to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                };
+                layer(
+                    None,
+                    self.list.columns.as_deref().map(names),
+                    Some(names(&cli::DEFAULT_COLUMNS)),
+                )
+            }
+            "list.name_width" => layer(
+                None,
+                self.list.name_width.map(|w| w.to_string()),
+                Some(DEFAULT_NAME_WIDTH.to_string()),
+            ),
+            "list.filter" => layer(
+                None,
+                self.list.filter.as_ref().map(|f| f.text.clone()),
+                None,
+            ),
+            "workflow.claim" => layer(
+                None,
+                self.workflow.claim.clone(),
+                Some(Status::InProgress.to_string()),
+            ),
+            "workflow.release" => layer(
+                None,
+                self.workflow.release.clone(),
+                Some(Status::Todo.to_string()),
+            ),
+            _ => unreachable!("every key in KEYS is handled"),
+        };
+        Ok(Setting { key, value, source })
+    }
+
+    /// Every key in `KEYS`, resolved.
+    pub fn settings(&self) -> Vec<Setting> {
+        KEYS.iter()
+            .map(|k| self.setting(k).expect("known key"))
+            .collect()
+    }
+}
+
+fn layer(
+    env: Option<String>,
+    config: Option<String>,
+    default: Option<String>,
+) -> (Option<String>, Source) {
+    match (env, config) {
+        (Some(v), _) => (Some(v), Source::Env),
+        (None, Some(v)) => (Some(v), Source::Config),
+        (None, None) => (default, Source::Default),
+    }
+}
+
+/// `3600` → `1h`, `90` → `90s`: the largest unit that divides evenly.
+fn format_duration(secs: u64) -> String {
+    for (unit, size) in [("d", 86400), ("h", 3600), ("m", 60)] {
+        if secs != 0 && secs.is_multiple_of(size) {
+            return format!("{}{}", secs / size, unit);
+        }
+    }
+    format!("{}s", secs)
+}
+
+/// Set `key` to `value` in the config at `path`, or remove it when `value`
+/// is `None`. The file keeps its comments and layout, and is only written
+/// if the result is a valid config.
+pub fn set(path: &Path, key: &str, value: Option<&str>) -> Result<(), AppError> {
+    if !KEYS.contains(&key) {
+        return Err(AppError::UnknownConfigKey(key.to_string()));
+    }
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(AppError::InvalidConfig(format!(
+                "cannot read {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    let mut doc: toml_edit::DocumentMut = text.parse().map_err(|e: toml_edit::TomlError| {
+        AppError::InvalidConfig(format!("{}: {}", path.display(), e.message()))
+    })?;
+    let (table, field) = match key.split_once('.') {
+        Some((table, field)) => {
+            let item = doc
+                .entry(table)
+                .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()));
+            let table = item.as_table_mut().ok_or_else(|| {
+                AppError::InvalidConfig(format!("{}: '{}' is not a table", path.display(), table))
+            })?;
+            (table, field)
+        }
+        None => (doc.as_table_mut(), key),
+    };
+    match value {
+        None => {
+            table.remove(field);
+        }
+        Some(raw) => {
+            let item = match key {
+                "list.columns" => {
+                    let cols: toml_edit::Array = raw
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|c| !c.is_empty())
+                        .collect();
+                    toml_edit::value(cols)
+                }
+                "list.name_width" => {
+                    let width: i64 = raw.trim().parse().map_err(|_| {
+                        AppError::InvalidConfig(format!(
+                            "list.name_width must be a number, not '{}'",
+                            raw
+                        ))
+                    })?;
+                    toml_edit::value(width)
+                }
+                _ => toml_edit::value(raw),
+            };
+            table.insert(field, item);
+        }
+    }
+    let text = doc.to_string();
+    Config::parse(&text)?;
+    std::fs::write(path, text)
+        .map_err(|e| AppError::InvalidConfig(format!("cannot write {}: {}", path.display(), e)))
+}
+
+/// The config file that belongs to the database at `db`.
+pub fn path_for_db(db: &Path) -> PathBuf {
+    db.parent().unwrap_or(Path::new("")).join(CONFIG_FILE)
+}
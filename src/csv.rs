@@ -0,0 +1,104 @@
+//! CSV rendering for `export --format csv`.
+
+use crate::cli::Column;
+use crate::ticket::TicketExport;
+
+/// Every exported field, in `TicketExport` order; the default CSV layout.
+pub const ALL_COLUMNS: [Column; 18] = [
+    Column::Id,
+    Column::Name,
+    Column::Description,
+    Column::Status,
+    Column::Priority,
+    Column::Parent,
+    Column::Claimer,
+    Column::ClaimedAt,
+    Column::LeaseExpiresAt,
+    Column::BlockReason,
+    Column::Dependencies,
+    Column::Labels,
+    Column::Ready,
+    Column::Waiting,
+    Column::Blocking,
+    Column::Created,
+    Column::Updated,
+    Column::ExternalKey,
+];
+
+fn column_name(c: Column) -> &'static str {
+    match c {
+        Column::Id => "id",
+        Column::Name => "name",
+        Column::Description => "description",
+        Column::Status => "status",
+        Column::Priority => "priority",
+        Column::Parent => "parent_id",
+        Column::Claimer => "claimed_by",
+        Column::ClaimedAt => "claimed_at",
+        Column::LeaseExpiresAt => "lease_expires_at",
+        Column::BlockReason => "block_reason",
+        Column::Dependencies => "dependencies",
+        Column::Labels => "labels",
+        Column::Ready => "ready",
+        Column::Waiting => "waiting",
+        Column::Blocking => "blocking",
+        Column::Created => "created_at",
+        Column::Updated => "updated_at",
+        Column::ExternalKey => "external_key",
+    }
+}
+
+fn field(t: &TicketExport, c: Column) -> String {
+    let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+    let join = |ids: &[i64]| {
+        ids.iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    match c {
+        Column::Id => t.id.to_string(),
+        Column::Name => t.name.clone(),
+        Column::Description => t.description.clone(),
+        Column::Status => t.status.to_string(),
+        Column::Priority => t.priority.to_string(),
+        Column::Parent => t.parent_id.map(|p| p.to_string()).unwrap_or_default(),
+        Column::Claimer => opt(&t.claimed_by),
+        Column::ClaimedAt => opt(&t.claimed_at),
+        Column::LeaseExpiresAt => opt(&t.lease_expires_at),
+        Column::BlockReason => opt(&t.block_reason),
+        Column::Dependencies => join(&t.dependencies),
+        Column::Labels => t.labels.join(","),
+        Column::Ready => t.ready.to_string(),
+        Column::Waiting => t.waiting.to_string(),
+        Column::Blocking => t.blocking.to_string(),
+        Column::Created => t.created_at.clone(),
+        Column::Updated => t.updated_at.clone(),
+        Column::ExternalKey => opt(&t.external_key),
+    }
+}
+
+/// Quote a field per RFC 4180 when it contains a separator, quote or newline.
+fn escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn header(columns: &[Column]) -> String {
+    columns
+        .iter()
+        .map(|&c| column_name(c))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn record(t: &TicketExport, columns: &[Column]) -> String {
+    columns
+        .iter()
+        .map(|&c| escape(&field(t, c)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -1,9 +1,10 @@
 use rusqlite::Connection;
 use rusqlite_migration::{Migrations, M};
 use std::path::PathBuf;
-use std::time::Duration;
 
+use crate::config::{self, Config};
 use crate::ticket::AppError;
+use crate::workflow::Workflow;
 
 static MIGRATIONS: &[M<'static>] = &[
     M::up(
@@ -45,35 +46,187 @@ static MIGRATIONS: &[M<'static>] = &[
             CHECK (ticket_id != depends_on)
         );",
     ),
+    M::up(
+        "ALTER TABLE tickets ADD COLUMN lease_expires_at TEXT;
+        ALTER TABLE tickets ADD COLUMN claim_prev_status TEXT;",
+    ),
+    // No foreign key on ticket_id: history must outlive deleted tickets.
+    M::up(
+        "CREATE TABLE ticket_events (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            ticket_id   INTEGER NOT NULL,
+            actor       TEXT,
+            kind        TEXT NOT NULL,
+            old_value   TEXT,
+            new_value   TEXT,
+            created_at  TEXT NOT NULL
+        );
+        CREATE INDEX idx_ticket_events_ticket ON ticket_events(ticket_id, id);
+        CREATE INDEX idx_ticket_events_created ON ticket_events(created_at);",
+    ),
+    M::up(
+        "CREATE TABLE ticket_notes (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            ticket_id   INTEGER NOT NULL REFERENCES tickets(id) ON DELETE CASCADE,
+            author      TEXT,
+            body        TEXT NOT NULL,
+            created_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ','now'))
+        );
+        CREATE INDEX idx_ticket_notes_ticket ON ticket_notes(ticket_id, id);",
+    ),
+    M::up(
+        "ALTER TABLE tickets ADD COLUMN priority INTEGER NOT NULL DEFAULT 2
+            CHECK(priority BETWEEN 0 AND 4);",
+    ),
+    M::up(
+        "CREATE TABLE ticket_labels (
+            ticket_id  INTEGER NOT NULL REFERENCES tickets(id) ON DELETE CASCADE,
+            label      TEXT NOT NULL,
+            PRIMARY KEY (ticket_id, label)
+        );
+        CREATE INDEX idx_ticket_labels_label ON ticket_labels(label);",
+    ),
+    M::up(
+        "ALTER TABLE tickets ADD COLUMN parent_id INTEGER
+            REFERENCES tickets(id) ON DELETE SET NULL;
+        CREATE INDEX idx_tickets_parent ON tickets(parent_id);",
+    ),
+    // Full-text index keyed by ticket id (rowid). Notes are concatenated
+    // into one column and rebuilt whenever a note is added or removed.
+    M::up(
+        "CREATE VIRTUAL TABLE ticket_fts USING fts5(name, description, notes);
+        INSERT INTO ticket_fts (rowid, name, description, notes)
+            SELECT t.id, t.name, t.description,
+                   COALESCE((SELECT group_concat(body, char(10))
+                             FROM (SELECT body FROM ticket_notes
+                                   WHERE ticket_id = t.id ORDER BY id)), '')
+            FROM tickets t;
+        CREATE TRIGGER ticket_fts_insert AFTER INSERT ON tickets BEGIN
+            INSERT INTO ticket_fts (rowid, name, description, notes)
+            VALUES (new.id, new.name, new.description, '');
+        END;
+        CREATE TRIGGER ticket_fts_update AFTER UPDATE OF name, description ON tickets BEGIN
+            UPDATE ticket_fts SET name = new.name, description = new.description
+            WHERE rowid = new.id;
+        END;
+        CREATE TRIGGER ticket_fts_delete AFTER DELETE ON tickets BEGIN
+            DELETE FROM ticket_fts WHERE rowid = old.id;
+        END;
+        CREATE TRIGGER ticket_fts_note_insert AFTER INSERT ON ticket_notes BEGIN
+            UPDATE ticket_fts SET notes = COALESCE((SELECT group_concat(body, char(10))
+                FROM (SELECT body FROM ticket_notes
+                      WHERE ticket_id = new.ticket_id ORDER BY id)), '')
+            WHERE rowid = new.ticket_id;
+        END;
+        CREATE TRIGGER ticket_fts_note_delete AFTER DELETE ON ticket_notes BEGIN
+            UPDATE ticket_fts SET notes = COALESCE((SELECT group_concat(body, char(10))
+                FROM (SELECT body FROM ticket_notes
+                      WHERE ticket_id = old.ticket_id ORDER BY id)), '')
+            WHERE rowid = old.ticket_id;
+        END;",
+    ),
+    // Saved views: `args` is a JSON array of list/export flags.
+    M::up(
+        "CREATE TABLE views (
+            name        TEXT PRIMARY KEY,
+            args        TEXT NOT NULL,
+            created_by  TEXT,
+            created_at  TEXT NOT NULL,
+            updated_at  TEXT NOT NULL
+        );",
+    ),
+    // Stable key from an external system, matched by `import --upsert`.
+    M::up(
+        "ALTER TABLE tickets ADD COLUMN external_key TEXT;
+        CREATE UNIQUE INDEX idx_tickets_external_key ON tickets(external_key)
+            WHERE external_key IS NOT NULL;",
+    ),
+    // Drop the status CHECK: the workflow decides which statuses exist.
+    // Rebuilding the table drops its indexes and triggers, so they are
+    // recreated; migrations run before foreign keys are switched on.
+    M::up(
+        "CREATE TABLE tickets_new (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            name        TEXT NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            status      TEXT NOT NULL DEFAULT 'todo',
+            claimed_by  TEXT,
+            claimed_at  TEXT,
+            block_reason TEXT,
+            created_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ','now')),
+            updated_at  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ','now')),
+            lease_expires_at TEXT,
+            claim_prev_status TEXT,
+            priority    INTEGER NOT NULL DEFAULT 2 CHECK(priority BETWEEN 0 AND 4),
+            parent_id   INTEGER REFERENCES tickets(id) ON DELETE SET NULL,
+            external_key TEXT
+        );
+        INSERT INTO tickets_new
+            SELECT id, name, description, status, claimed_by, claimed_at, block_reason,
+                   created_at, updated_at, lease_expires_at, claim_prev_status,
+                   priority, parent_id, external_key
+            FROM tickets;
+        DROP TABLE tickets;
+        ALTER TABLE tickets_new RENAME TO tickets;
+        CREATE INDEX idx_tickets_parent ON tickets(parent_id);
+        CREATE UNIQUE INDEX idx_tickets_external_key ON tickets(external_key)
+            WHERE external_key IS NOT NULL;
+        CREATE TRIGGER ticket_fts_insert AFTER INSERT ON tickets BEGIN
+            INSERT INTO ticket_fts (rowid, name, description, notes)
+            VALUES (new.id, new.name, new.description, '');
+        END;
+        CREATE TRIGGER ticket_fts_update AFTER UPDATE OF name, description ON tickets BEGIN
+            UPDATE ticket_fts SET name = new.name, description = new.description
+            WHERE rowid = new.id;
+        END;
+        CREATE TRIGGER ticket_fts_delete AFTER DELETE ON tickets BEGIN
+            DELETE FROM ticket_fts WHERE rowid = old.id;
+        END;",
+    )
+    .foreign_key_check(),
 ];
 
+/// The database to use: `RTIK_DB`, else `.rtik.db` in the nearest directory
+/// (walking up from the current one) holding a `.rtik.db` or `.rtik.toml`,
+/// else `.rtik.db` in the current directory.
 pub fn resolve_db_path() -> PathBuf {
     if let Ok(path) = std::env::var("RTIK_DB") {
         return PathBuf::from(path);
     }
-    let mut dir = std::env::current_dir().expect("cannot read cwd");
+    // Without a readable cwd, fall back to a relative path.
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut dir = cwd.clone();
     loop {
         let candidate = dir.join(".rtik.db");
-        if candidate.exists() {
+        if candidate.exists() || dir.join(config::CONFIG_FILE).exists() {
             return candidate;
         }
         if !dir.pop() {
             break;
         }
     }
-    std::env::current_dir()
-        .expect("cannot read cwd")
-        .join(".rtik.db")
+    cwd.join(".rtik.db")
 }
 
+/// Open the database at `path` with the `.rtik.toml` beside it.
 pub fn open_connection(path: &std::path::Path) -> Result<Connection, AppError> {
+    open_connection_with(path, &Config::load(&config::path_for_db(path))?)
+}
+
+/// Open the database at `path`, migrate it, and install `config`'s workflow.
+pub fn open_connection_with(
+    path: &std::path::Path,
+    config: &Config,
+) -> Result<Connection, AppError> {
     let mut conn = Connection::open(path)?;
     conn.pragma_update(None, "journal_mode", "WAL")?;
     conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.busy_timeout(config.busy_timeout())?;
+    // Migrations that rebuild a table drop the old one; with foreign keys on
+    // that drop would first delete every row through the cascades.
+    conn.pragma_update(None, "foreign_keys", "OFF")?;
+    Migrations::from_slice(MIGRATIONS).to_latest(&mut conn)?;
     conn.pragma_update(None, "foreign_keys", "ON")?;
-    conn.busy_timeout(Duration::from_secs(5))?;
-    Migrations::from_slice(MIGRATIONS)
-        .to_latest(&mut conn)
-        .expect("migration failed");
+    Workflow::from_config(&config.workflow)?.install(&conn)?;
     Ok(conn)
 }
@@ -0,0 +1,329 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::model::{IdPrefix, Status};
+use crate::ticket::{self, AppError, Ticket};
+use crate::workflow::Workflow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Follow edges to the tickets this one depends on.
+    DependsOn,
+    /// Follow edges to the tickets that depend on this one.
+    RequiredBy,
+}
+
+pub struct DepNode {
+    pub ticket: Ticket,
+    pub children: Vec<DepNode>,
+    /// Already expanded elsewhere in the tree; children are omitted.
+    pub repeated: bool,
+}
+
+/// Walk the transitive closure of `ticket_deps` from `id` in one direction.
+/// A ticket reachable by several paths is expanded once; later occurrences
+/// are marked `repeated`.
+pub fn dep_tree(conn: &Connection, id: i64, direction: Direction) -> Result<DepNode, AppError> {
+    let root = ticket::get_ticket(conn, id)?;
+    let forward = ticket::load_dep_graph(conn)?;
+    let adj = match direction {
+        Direction::DependsOn => forward,
+        Direction::RequiredBy => reverse(&forward),
+    };
+    let tickets: HashMap<i64, Ticket> = ticket::list_tickets(conn)?
+        .into_iter()
+        .map(|t| (t.id, t))
+        .collect();
+    let mut expanded = HashSet::new();
+    Ok(expand(root, &adj, &tickets, &mut expanded))
+}
+
+fn expand(
+    ticket: Ticket,
+    adj: &HashMap<i64, Vec<i64>>,
+    tickets: &HashMap<i64, Ticket>,
+    expanded: &mut HashSet<i64>,
+) -> DepNode {
+    if !expanded.insert(ticket.id) {
+        return DepNode {
+            ticket,
+            children: vec![],
+            repeated: true,
+        };
+    }
+    let mut children = Vec::new();
+    for next in adj.get(&ticket.id).into_iter().flatten() {
+        if let Some(t) = tickets.get(next) {
+            children.push(expand(t.clone(), adj, tickets, expanded));
+        }
+    }
+    DepNode {
+        ticket,
+        children,
+        repeated: false,
+    }
+}
+
+/// Invert a forward adjacency map (ticket -> deps) into dep -> dependents.
+pub fn reverse(forward: &HashMap<i64, Vec<i64>>) -> HashMap<i64, Vec<i64>> {
+    let mut rev: HashMap<i64, Vec<i64>> = HashMap::new();
+    for (&from, tos) in forward {
+        for &to in tos {
+            rev.entry(to).or_default().push(from);
+        }
+    }
+    for list in rev.values_mut() {
+        list.sort_unstable();
+    }
+    rev
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+/// Every ticket and every dependency edge. Edges run from a dependency to
+/// the ticket waiting on it, i.e. in execution order.
+#[derive(Debug, Serialize)]
+pub struct DepGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    pub id: i64,
+    pub name: String,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct GraphEdge {
+    pub from: i64,
+    pub to: i64,
+}
+
+pub fn dep_graph(conn: &Connection) -> Result<DepGraph, AppError> {
+    let mut edges: Vec<GraphEdge> = ticket::load_dep_graph(conn)?
+        .into_iter()
+        .flat_map(|(to, deps)| deps.into_iter().map(move |from| GraphEdge { from, to }))
+        .collect();
+    edges.sort_unstable();
+    let nodes = ticket::list_tickets(conn)?
+        .into_iter()
+        .map(|t| GraphNode {
+            id: t.id,
+            name: t.name,
+            status: t.status,
+        })
+        .collect();
+    Ok(DepGraph { nodes, edges })
+}
+
+impl DepGraph {
+    /// Render the edges as a Graphviz or Mermaid graph, with the tickets
+    /// that have any labelled in `ids` form.
+    pub fn render(&self, format: GraphFormat, ids: &IdPrefix) -> String {
+        let linked: BTreeSet<i64> = self.edges.iter().flat_map(|e| [e.from, e.to]).collect();
+        let nodes: Vec<&GraphNode> = self
+            .nodes
+            .iter()
+            .filter(|n| linked.contains(&n.id))
+            .collect();
+        match format {
+            GraphFormat::Dot => render_dot(&nodes, &self.edges, ids),
+            GraphFormat::Mermaid => render_mermaid(&nodes, &self.edges, ids),
+        }
+    }
+}
+
+fn status_color(status: &Status) -> &'static str {
+    match status {
+        Status::Todo => "#ffffff",
+        Status::InProgress => "#fff3b0",
+        Status::Blocked => "#f4a6a6",
+        Status::Done => "#b7e4c7",
+        Status::Custom(_) => "#cfe2ff",
+    }
+}
+
+fn render_dot(tickets: &[&GraphNode], edges: &[GraphEdge], ids: &IdPrefix) -> String {
+    let mut out =
+        String::from("digraph deps {\n    rankdir=LR;\n    node [shape=box, style=filled];\n");
+    for t in tickets {
+        let name = t.name.replace('\\', "\\\\").replace('"', "\\\"");
+        let label = format!("{} {}\\n[{}]", ids.show_export(t.id), name, t.status);
+        out.push_str(&format!(
+            "    t{} [label=\"{}\", fillcolor=\"{}\"];\n",
+            t.id,
+            label,
+            status_color(&t.status)
+        ));
+    }
+    for e in edges {
+        out.push_str(&format!("    t{} -> t{};\n", e.from, e.to));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_mermaid(tickets: &[&GraphNode], edges: &[GraphEdge], ids: &IdPrefix) -> String {
+    let mut out = String::from("flowchart LR\n");
+    for t in tickets {
+        out.push_str(&format!(
+            "    t{}[\"{} {}\"]:::{}\n",
+            t.id,
+            ids.show_export(t.id),
+            t.name.replace('"', "#quot;"),
+            t.status.as_str().replace('-', "_")
+        ));
+    }
+    for e in edges {
+        out.push_str(&format!("    t{} --> t{}\n", e.from, e.to));
+    }
+    let mut statuses = Status::ALL.to_vec();
+    for t in tickets {
+        if !statuses.contains(&t.status) {
+            statuses.push(t.status.clone());
+        }
+    }
+    for status in &statuses {
+        out.push_str(&format!(
+            "    classDef {} fill:{},stroke:#555\n",
+            status.as_str().replace('-', "_"),
+            status_color(status)
+        ));
+    }
+    out
+}
+
+/// One batch of the execution plan: every ticket in a wave has all of its
+/// open dependencies in earlier waves, so a wave can be worked in parallel.
+#[derive(Debug, Serialize)]
+pub struct PlanWave {
+    pub wave: usize,
+    pub tickets: Vec<PlanItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanItem {
+    pub id: i64,
+    pub name: String,
+    pub status: Status,
+    pub priority: i64,
+    pub claimed_by: Option<String>,
+    /// Open dependencies only; done ones are already satisfied.
+    pub dependencies: Vec<i64>,
+}
+
+/// Topologically order the open (not done) tickets into waves. With `to`,
+/// only the target and its transitive open dependencies are planned. Tickets
+/// in a status the workflow counts as done are satisfied and left out. Returns `CyclicDependency`
+/// naming one cycle, with ids written by `ids`, if the stored graph is not acyclic.
+pub fn plan(conn: &Connection, to: Option<i64>, ids: &IdPrefix) -> Result<Vec<PlanWave>, AppError> {
+    let forward = ticket::load_dep_graph(conn)?;
+    let workflow = Workflow::of(conn)?;
+    let mut open: HashMap<i64, Ticket> = ticket::list_tickets(conn)?
+        .into_iter()
+        .filter(|t| !workflow.is_done(&t.status))
+        .map(|t| (t.id, t))
+        .collect();
+
+    if let Some(target) = to {
+        let root = ticket::get_ticket(conn, target)?;
+        if workflow.is_done(&root.status) {
+            return Ok(vec![]);
+        }
+        let mut keep = HashSet::new();
+        let mut stack = vec![target];
+        while let Some(id) = stack.pop() {
+            if !open.contains_key(&id) || !keep.insert(id) {
+                continue;
+            }
+            stack.extend(forward.get(&id).into_iter().flatten().copied());
+        }
+        open.retain(|id, _| keep.contains(id));
+    }
+
+    let deps: HashMap<i64, Vec<i64>> = open
+        .keys()
+        .map(|&id| {
+            let list = forward
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|d| open.contains_key(d))
+                .collect();
+            (id, list)
+        })
+        .collect();
+
+    let mut placed: HashSet<i64> = HashSet::new();
+    let mut waves = Vec::new();
+    while placed.len() < open.len() {
+        let mut ready: Vec<&Ticket> = open
+            .values()
+            .filter(|t| !placed.contains(&t.id))
+            .filter(|t| deps[&t.id].iter().all(|d| placed.contains(d)))
+            .collect();
+        if ready.is_empty() {
+            let remaining: HashSet<i64> = open
+                .keys()
+                .copied()
+                .filter(|id| !placed.contains(id))
+                .collect();
+            return Err(AppError::CyclicDependency(describe_cycle(
+                &deps, &remaining, ids,
+            )));
+        }
+        ready.sort_by_key(|t| (t.priority, t.id));
+        let tickets: Vec<PlanItem> = ready
+            .iter()
+            .map(|t| PlanItem {
+                id: t.id,
+                name: t.name.clone(),
+                status: t.status.clone(),
+                priority: t.priority,
+                claimed_by: t.claimed_by.clone(),
+                dependencies: deps[&t.id].clone(),
+            })
+            .collect();
+        placed.extend(tickets.iter().map(|t| t.id));
+        waves.push(PlanWave {
+            wave: waves.len() + 1,
+            tickets,
+        });
+    }
+    Ok(waves)
+}
+
+/// Every ticket left over by the wave loop has an unplaced dependency, so
+/// following those edges from the lowest id must revisit a ticket.
+fn describe_cycle(
+    deps: &HashMap<i64, Vec<i64>>,
+    remaining: &HashSet<i64>,
+    ids: &IdPrefix,
+) -> String {
+    let mut path: Vec<i64> = Vec::new();
+    let mut current = *remaining.iter().min().expect("cycle has members");
+    while !path.contains(&current) {
+        path.push(current);
+        current = deps[&current]
+            .iter()
+            .copied()
+            .find(|d| remaining.contains(d))
+            .expect("unplaced ticket has an unplaced dependency");
+    }
+    let start = path.iter().position(|&id| id == current).unwrap_or(0);
+    let mut cycle = path[start..].to_vec();
+    cycle.push(current);
+    cycle
+        .iter()
+        .map(|&id| ids.show(id))
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
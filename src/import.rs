@@ -0,0 +1,627 @@
+//! `rtik import`: load tickets and dependencies from an export file.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+
+use crate::model::Status;
+use crate::ticket::{
+    add_dep, add_note, chrono_free_utc_now, create_ticket_with, get_ticket, in_tx, list_deps,
+    list_labels, record_event, update_ticket_with, validate_priority, would_create_cycle, AppError,
+    NewTicket, TicketUpdate,
+};
+use crate::workflow::Workflow;
+
+/// One ticket as read from the input. Accepts the `export --json` shape;
+/// fields that only describe live state (claims, flags, timestamps) are
+/// ignored. `id`, `parent_id` and `dependencies` are references local to
+/// the file and may be numbers or strings.
+#[derive(Debug, Default, Deserialize)]
+pub struct ImportTicket {
+    #[serde(default, deserialize_with = "reference")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// None = todo
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i64>,
+    #[serde(default, deserialize_with = "reference")]
+    pub parent_id: Option<String>,
+    #[serde(default, deserialize_with = "references")]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub block_reason: Option<String>,
+    #[serde(default)]
+    pub notes: Vec<ImportNote>,
+    /// Matched against existing tickets by `--upsert`.
+    #[serde(default, alias = "key")]
+    pub external_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportNote {
+    #[serde(default)]
+    pub author: Option<String>,
+    pub body: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawRef {
+    Num(i64),
+    Str(String),
+}
+
+impl From<RawRef> for String {
+    fn from(r: RawRef) -> String {
+        match r {
+            RawRef::Num(n) => n.to_string(),
+            RawRef::Str(s) => s,
+        }
+    }
+}
+
+fn reference<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    Option::<RawRef>::deserialize(d).map(|r| r.map(String::from))
+}
+
+fn references<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    Vec::<RawRef>::deserialize(d).map(|v| v.into_iter().map(String::from).collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// A JSON array, an `{"tickets": [...]}` page or a single object.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    /// A checklist plan; see [`parse_markdown`].
+    Markdown(Nesting),
+}
+
+/// Parse import input. Without an explicit format, input that is one JSON
+/// document is read as JSON and anything else as JSON Lines; Markdown is
+/// never guessed from content.
+pub fn parse_import(
+    input: &str,
+    format: Option<ImportFormat>,
+) -> Result<Vec<ImportTicket>, AppError> {
+    let format = format.unwrap_or_else(|| {
+        if serde_json::from_str::<serde_json::Value>(input).is_ok() {
+            ImportFormat::Json
+        } else {
+            ImportFormat::Jsonl
+        }
+    });
+    match format {
+        ImportFormat::Json => {
+            let value: serde_json::Value = serde_json::from_str(input).map_err(invalid_json)?;
+            let value = match value {
+                serde_json::Value::Object(mut map) if map.contains_key("tickets") => {
+                    map.remove("tickets").unwrap_or_default()
+                }
+                obj @ serde_json::Value::Object(_) => serde_json::Value::Array(vec![obj]),
+                other => other,
+            };
+            serde_json::from_value(value).map_err(invalid_json)
+        }
+        ImportFormat::Jsonl => input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| AppError::InvalidImport(format!("line {}: {}", i + 1, e)))
+            })
+            .collect(),
+        ImportFormat::Markdown(nesting) => Ok(parse_markdown(input, nesting)),
+    }
+}
+
+/// How nested checklist items relate to the item they sit under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Nesting {
+    /// Nested items become subtasks of the outer item.
+    #[default]
+    Parent,
+    /// The outer item depends on each nested item.
+    Deps,
+}
+
+/// Read a Markdown checklist plan. Every `- [ ]` item (also `*`, `+` or
+/// `1.` bullets) becomes a ticket and `- [x]` items become done tickets.
+/// Text indented under an item is its description; anything else is
+/// skipped. Items are referenced as `L<line>` so the mapping printed after
+/// import points back into the file.
+pub fn parse_markdown(input: &str, nesting: Nesting) -> Vec<ImportTicket> {
+    let mut records: Vec<ImportTicket> = Vec::new();
+    let mut descriptions: Vec<Vec<(usize, &str)>> = Vec::new();
+    // (indent, index into records) for each item still open for nesting.
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+
+    for (n, line) in input.lines().enumerate() {
+        let indent = indent_width(line);
+        let text = line.trim_start();
+        if let Some((done, title)) = checklist_item(text) {
+            while stack.last().is_some_and(|&(i, _)| i >= indent) {
+                stack.pop();
+            }
+            let source = format!("L{}", n + 1);
+            let mut record = ImportTicket {
+                id: Some(source.clone()),
+                name: title.to_string(),
+                status: done.then(|| "done".to_string()),
+                ..Default::default()
+            };
+            if let Some(&(_, outer)) = stack.last() {
+                match nesting {
+                    Nesting::Parent => record.parent_id = records[outer].id.clone(),
+                    Nesting::Deps => records[outer].dependencies.push(source),
+                }
+            }
+            records.push(record);
+            descriptions.push(Vec::new());
+            stack.push((indent, records.len() - 1));
+        } else if text.is_empty() {
+            if let Some(&(_, owner)) = stack.last() {
+                descriptions[owner].push((0, ""));
+            }
+        } else if let Some(&(_, owner)) = stack.iter().rev().find(|&&(i, _)| i < indent) {
+            descriptions[owner].push((indent, line));
+        } else {
+            // Unindented prose or a heading ends every open item.
+            stack.clear();
+        }
+    }
+
+    for (record, lines) in records.iter_mut().zip(descriptions) {
+        record.description = dedent(&lines);
+    }
+    records
+}
+
+/// Leading whitespace in columns, counting a tab as four.
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// `- [ ] title` → `(false, "title")`, `- [x] title` → `(true, "title")`.
+fn checklist_item(text: &str) -> Option<(bool, &str)> {
+    let rest = match text.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 {
+                return None;
+            }
+            text[digits..].strip_prefix(['.', ')'])?
+        }
+    };
+    let rest = rest.strip_prefix([' ', '\t'])?.trim_start();
+    let (done, title) = if let Some(t) = rest.strip_prefix("[ ]") {
+        (false, t)
+    } else if let Some(t) = rest
+        .strip_prefix("[x]")
+        .or_else(|| rest.strip_prefix("[X]"))
+    {
+        (true, t)
+    } else {
+        return None;
+    };
+    if !title.is_empty() && !title.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some((done, title.trim()))
+}
+
+/// Join description lines, removing their common indentation and any
+/// leading or trailing blank lines.
+fn dedent(lines: &[(usize, &str)]) -> String {
+    let strip = lines
+        .iter()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|&(i, _)| i)
+        .min()
+        .unwrap_or(0);
+    let body: Vec<String> = lines
+        .iter()
+        .map(|&(_, l)| {
+            let mut cut = 0;
+            let mut width = 0;
+            for (i, c) in l.char_indices() {
+                if width >= strip || !c.is_whitespace() {
+                    break;
+                }
+                width += if c == '\t' { 4 } else { 1 };
+                cut = i + c.len_utf8();
+            }
+            l[cut..].trim_end().to_string()
+        })
+        .collect();
+    body.join("\n").trim_matches('\n').to_string()
+}
+
+fn invalid_json(e: serde_json::Error) -> AppError {
+    AppError::InvalidImport(e.to_string())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Update tickets whose `external_key` matches instead of creating new ones.
+    pub upsert: bool,
+    /// Do all the work, report it, then roll back.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+/// Where one input ticket ended up.
+#[derive(Debug, Serialize)]
+pub struct ImportedTicket {
+    /// The ticket's `id` in the input, if it had one.
+    pub source_id: Option<String>,
+    pub id: i64,
+    pub name: String,
+    pub action: ImportAction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub tickets: Vec<ImportedTicket>,
+    /// Dependency links added.
+    pub dependencies: usize,
+    pub dry_run: bool,
+}
+
+/// Import `records` in one transaction: create (or, with `upsert`, update)
+/// every ticket first, then resolve parents and dependencies through the map
+/// from input ids to database ids. Any error rolls back the whole import.
+/// Upserts add labels and dependencies but never remove them.
+pub fn import_tickets(
+    conn: &Connection,
+    records: &[ImportTicket],
+    opts: ImportOptions,
+    actor: Option<&str>,
+) -> Result<ImportReport, AppError> {
+    validate(records, &Workflow::of(conn)?)?;
+    in_tx(conn, |conn| {
+        if !opts.dry_run {
+            return apply(conn, records, opts, actor);
+        }
+        conn.execute_batch("SAVEPOINT import_dry_run")?;
+        let report = apply(conn, records, opts, actor);
+        conn.execute_batch("ROLLBACK TO import_dry_run; RELEASE import_dry_run")?;
+        report.map(|r| ImportReport { dry_run: true, ..r })
+    })
+}
+
+fn label(record: &ImportTicket, index: usize) -> String {
+    match &record.id {
+        Some(id) => format!("ticket '{}'", id),
+        None => format!("ticket #{} in input", index + 1),
+    }
+}
+
+/// Checks on the input alone, so bad input fails before any write.
+fn validate(records: &[ImportTicket], workflow: &Workflow) -> Result<(), AppError> {
+    let mut seen = HashMap::new();
+    let mut keys = HashMap::new();
+    for (i, r) in records.iter().enumerate() {
+        if let Some(id) = &r.id {
+            if seen.insert(id.as_str(), i).is_some() {
+                return Err(AppError::InvalidImport(format!("duplicate id '{}'", id)));
+            }
+        }
+        if let Some(key) = &r.external_key {
+            if keys.insert(key.as_str(), i).is_some() {
+                return Err(AppError::InvalidImport(format!(
+                    "duplicate external key '{}'",
+                    key
+                )));
+            }
+        }
+        if r.name.trim().is_empty() {
+            return Err(AppError::InvalidImport(format!(
+                "{}: name is required",
+                label(r, i)
+            )));
+        }
+        if let Some(p) = r.priority {
+            validate_priority(p)?;
+        }
+        let status = imported_status(r)?;
+        workflow.check(&status)?;
+        if status == Status::Blocked
+            && r.block_reason
+                .as_deref()
+                .is_none_or(|b| b.trim().is_empty())
+        {
+            return Err(AppError::InvalidImport(format!(
+                "{}: blocked tickets need a block_reason",
+                label(r, i)
+            )));
+        }
+    }
+    for (i, r) in records.iter().enumerate() {
+        for reference in r.parent_id.iter().chain(&r.dependencies) {
+            if !seen.contains_key(reference.as_str()) {
+                return Err(AppError::InvalidImport(format!(
+                    "{}: unknown reference '{}'",
+                    label(r, i),
+                    reference
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply(
+    conn: &Connection,
+    records: &[ImportTicket],
+    opts: ImportOptions,
+    actor: Option<&str>,
+) -> Result<ImportReport, AppError> {
+    let mut tickets = Vec::with_capacity(records.len());
+    let mut id_map: HashMap<&str, i64> = HashMap::new();
+
+    for r in records {
+        let existing = match &r.external_key {
+            Some(key) => find_by_external_key(conn, key)?,
+            None => None,
+        };
+        if let (Some(id), false) = (existing, opts.upsert) {
+            return Err(AppError::InvalidImport(format!(
+                "external key '{}' already belongs to #{} — use --upsert to update it",
+                r.external_key.as_deref().unwrap_or_default(),
+                id
+            )));
+        }
+        let (id, mut action) = match existing {
+            Some(id) => (id, update_existing(conn, id, r, actor)?),
+            None => (create(conn, r, actor)?, ImportAction::Created),
+        };
+        if set_status(conn, id, r, actor)? && action == ImportAction::Unchanged {
+            action = ImportAction::Updated;
+        }
+        if let Some(source) = &r.id {
+            id_map.insert(source, id);
+        }
+        tickets.push(ImportedTicket {
+            source_id: r.id.clone(),
+            id,
+            name: r.name.clone(),
+            action,
+        });
+    }
+
+    let sources: HashMap<i64, &str> = id_map.iter().map(|(s, id)| (*id, *s)).collect();
+    let mut dependencies = 0;
+    for (r, imported) in records.iter().zip(tickets.iter_mut()) {
+        let mut changed = false;
+        if let Some(parent) = &r.parent_id {
+            changed |= set_parent(conn, imported.id, id_map[parent.as_str()], &sources, actor)?;
+        }
+        let existing = list_deps(conn, imported.id)?.forward;
+        let mut added: Vec<i64> = Vec::new();
+        for dep in &r.dependencies {
+            let dep = id_map[dep.as_str()];
+            if existing.contains(&dep) || added.contains(&dep) {
+                continue;
+            }
+            if dep == imported.id {
+                return Err(cycle_error(&[dep], &sources));
+            }
+            if let Some(path) = would_create_cycle(conn, imported.id, dep)? {
+                return Err(cycle_error(&path, &sources));
+            }
+            add_dep(conn, imported.id, dep, actor)?;
+            added.push(dep);
+        }
+        dependencies += added.len();
+        if (changed || !added.is_empty()) && imported.action == ImportAction::Unchanged {
+            imported.action = ImportAction::Updated;
+        }
+    }
+
+    Ok(ImportReport {
+        tickets,
+        dependencies,
+        dry_run: false,
+    })
+}
+
+/// Spell out a cycle using the input's ids where there are any, since the
+/// database ids it was given are about to be rolled back.
+fn describe_cycle(cycle: impl Iterator<Item = i64>, sources: &HashMap<i64, &str>) -> String {
+    cycle
+        .map(|id| match sources.get(&id) {
+            Some(s) => format!("'{}'", s),
+            None => format!("#{}", id),
+        })
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+fn cycle_error(path: &[i64], sources: &HashMap<i64, &str>) -> AppError {
+    let cycle = path.iter().chain(path.first()).copied();
+    AppError::CyclicDependency(describe_cycle(cycle, sources))
+}
+
+fn find_by_external_key(conn: &Connection, key: &str) -> Result<Option<i64>, AppError> {
+    match conn.query_row(
+        "SELECT id FROM tickets WHERE external_key = ?1",
+        rusqlite::params![key],
+        |r| r.get(0),
+    ) {
+        Ok(id) => Ok(Some(id)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn create(conn: &Connection, r: &ImportTicket, actor: Option<&str>) -> Result<i64, AppError> {
+    let id = create_ticket_with(
+        conn,
+        &NewTicket {
+            name: &r.name,
+            description: &r.description,
+            priority: r.priority,
+            labels: &r.labels,
+            parent: None,
+        },
+        actor,
+    )?;
+    if let Some(key) = &r.external_key {
+        conn.execute(
+            "UPDATE tickets SET external_key = ?1 WHERE id = ?2",
+            rusqlite::params![key, id],
+        )?;
+    }
+    for note in &r.notes {
+        add_note(conn, id, note.author.as_deref(), &note.body)?;
+    }
+    Ok(id)
+}
+
+/// Bring name, description, priority and labels in line with the input.
+/// Notes are only imported with new tickets.
+fn update_existing(
+    conn: &Connection,
+    id: i64,
+    r: &ImportTicket,
+    actor: Option<&str>,
+) -> Result<ImportAction, AppError> {
+    let current = get_ticket(conn, id)?;
+    let labels = list_labels(conn, id)?;
+    let add_labels: Vec<String> = r
+        .labels
+        .iter()
+        .filter(|l| !labels.contains(&l.to_lowercase()))
+        .cloned()
+        .collect();
+    let update = TicketUpdate {
+        name: Some(r.name.as_str()).filter(|n| *n != current.name),
+        description: Some(r.description.as_str()).filter(|d| *d != current.description),
+        priority: r.priority.filter(|p| *p != current.priority),
+        add_labels: &add_labels,
+        ..Default::default()
+    };
+    if update.is_empty() {
+        return Ok(ImportAction::Unchanged);
+    }
+    update_ticket_with(conn, id, &update, actor)?;
+    Ok(ImportAction::Updated)
+}
+
+/// The record's status, `todo` when it has none.
+fn imported_status(r: &ImportTicket) -> Result<Status, AppError> {
+    r.status.as_deref().map_or(Ok(Status::Todo), str::parse)
+}
+
+/// Set the imported status directly: the file describes an end state, not a
+/// step through the workflow, so transition rules do not apply. Claims are
+/// never imported, and a status that clears claims (like done) clears any
+/// existing one.
+fn set_status(
+    conn: &Connection,
+    id: i64,
+    r: &ImportTicket,
+    actor: Option<&str>,
+) -> Result<bool, AppError> {
+    let status = imported_status(r)?;
+    let current = get_ticket(conn, id)?;
+    let reason = r
+        .block_reason
+        .as_deref()
+        .filter(|_| status == Status::Blocked);
+    let reason_changed = reason.is_some() && reason != current.block_reason.as_deref();
+    if status == current.status && !reason_changed {
+        return Ok(false);
+    }
+    let now = chrono_free_utc_now();
+    conn.execute(
+        "UPDATE tickets SET status = ?1, block_reason = COALESCE(?2, block_reason), updated_at = ?3
+         WHERE id = ?4",
+        rusqlite::params![status, reason, now, id],
+    )?;
+    if Workflow::of(conn)?.check(&status)?.clears_claim {
+        conn.execute(
+            "UPDATE tickets SET claimed_by = NULL, claimed_at = NULL, lease_expires_at = NULL,
+             claim_prev_status = NULL WHERE id = ?1",
+            rusqlite::params![id],
+        )?;
+    }
+    if status != current.status {
+        record_event(
+            conn,
+            id,
+            actor,
+            "status",
+            Some(current.status.as_str()),
+            Some(status.as_str()),
+            &now,
+        )?;
+    }
+    if reason_changed {
+        record_event(conn, id, actor, "block_reason", None, reason, &now)?;
+    }
+    Ok(true)
+}
+
+fn set_parent(
+    conn: &Connection,
+    id: i64,
+    parent: i64,
+    sources: &HashMap<i64, &str>,
+    actor: Option<&str>,
+) -> Result<bool, AppError> {
+    let current = get_ticket(conn, id)?;
+    if current.parent_id == Some(parent) {
+        return Ok(false);
+    }
+    // Walk up from the new parent; reaching `id` would make it its own ancestor.
+    let mut chain = vec![id, parent];
+    let mut cursor = Some(parent);
+    while let Some(p) = cursor {
+        if p == id {
+            return Err(AppError::InvalidImport(format!(
+                "parent cycle: {}",
+                describe_cycle(chain.into_iter(), sources)
+            )));
+        }
+        cursor = get_ticket(conn, p)?.parent_id;
+        if let Some(next) = cursor {
+            chain.push(next);
+        }
+    }
+    let now = chrono_free_utc_now();
+    conn.execute(
+        "UPDATE tickets SET parent_id = ?1, updated_at = ?2 WHERE id = ?3",
+        rusqlite::params![parent, now, id],
+    )?;
+    record_event(
+        conn,
+        id,
+        actor,
+        "parent",
+        current.parent_id.map(|p| p.to_string()).as_deref(),
+        Some(&parent.to_string()),
+        &now,
+    )?;
+    Ok(true)
+}
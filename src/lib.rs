@@ -1,134 +1,389 @@
+pub mod budget;
 pub mod cli;
+pub mod config;
+pub mod csv;
 pub mod db;
+pub mod graph;
+pub mod import;
+pub mod markdown;
+pub mod mcp;
+pub mod model;
+pub mod ops;
+pub mod query;
+pub mod server;
+pub mod store;
 pub mod ticket;
+pub mod view;
+pub mod workflow;
 
-use rusqlite::Connection;
-use ticket::AppError;
+pub use model::{IdPrefix, Status, TicketId};
+pub use store::{Store, TicketPatch};
+pub use ticket::{AppError, ListFilter};
 
-fn resolve_agent() -> Result<String, AppError> {
-    std::env::var("RTIK_AGENT").map_err(|_| AppError::AgentNotSet)
+/// The `--json` form of an error, written to stderr as one line:
+/// `{"code": "not_found", "message": "ticket #9 not found"}`.
+pub fn error_json(code: &str, message: &str) -> String {
+    serde_json::json!({ "code": code, "message": message }).to_string()
 }
 
-pub fn run(cli: cli::Cli, conn: Connection) -> Result<(), AppError> {
-    let mut conn = conn;
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("serialize")
+    );
+}
+
+/// A ticket's export object plus any warnings the command produced.
+#[derive(serde::Serialize)]
+struct WithWarnings {
+    #[serde(flatten)]
+    ticket: ticket::TicketExport,
+    warnings: Vec<String>,
+}
+
+fn print_warnings(warnings: &[String]) {
+    for w in warnings {
+        eprintln!("Warning: {}", w);
+    }
+}
+
+pub fn run(cli: cli::Cli, mut store: Store) -> Result<(), AppError> {
+    // `--json` wins over everything; RTIK_OUTPUT or the configured output
+    // only fills in where no other output format was asked for.
+    let env_json = !cli.json && store.config().json_output();
+    let json = cli.json || env_json;
+    let ids = store.ids().clone();
     use cli::Commands;
     match cli.command {
         Commands::Create(args) => {
             let desc = args.desc.as_deref().unwrap_or("");
-            let id = ticket::create_ticket(&conn, &args.name, desc)?;
-            println!("Created: #{} {}", id, args.name);
-        }
-        Commands::Get { id } => {
-            let t = ticket::get_ticket(&conn, id)?;
-            let created_date = t.created_at.split('T').next().unwrap_or(&t.created_at);
-            let updated_date = t.updated_at.split('T').next().unwrap_or(&t.updated_at);
-            println!("#{} {} [{}]", t.id, t.name, t.status);
-            if !t.description.is_empty() {
-                println!("{}", t.description);
-            }
-            println!("Created: {} | Updated: {}", created_date, updated_date);
-            let deps = ticket::list_deps(&conn, id)?;
-            if !deps.forward.is_empty() {
-                let fwd = deps
-                    .forward
-                    .iter()
-                    .map(|i| format!("#{}", i))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                println!("Depends on: {}", fwd);
+            let id = store.create(&ticket::NewTicket {
+                name: &args.name,
+                description: desc,
+                priority: args.priority,
+                labels: &args.labels,
+                parent: args.parent.map(i64::from),
+            })?;
+            if json {
+                print_json(&store.export(id)?);
+            } else {
+                println!("Created: {} {}", ids.show(id), args.name);
             }
-            if !deps.reverse.is_empty() {
-                let rev = deps
-                    .reverse
-                    .iter()
-                    .map(|i| format!("#{}", i))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                println!("Required by: {}", rev);
+        }
+        Commands::Get(args) => {
+            if json {
+                let mut e = store.export(args.id)?;
+                let older = e.notes.len().saturating_sub(args.notes);
+                e.notes.drain(..older);
+                print_json(&e);
+                return Ok(());
+            }
+            print_ticket(&store, &store.get(args.id)?)?;
+            let notes = store.notes(args.id, Some(args.notes))?;
+            if !notes.is_empty() {
+                println!("Notes:");
+                for n in &notes {
+                    println!(
+                        "  {} {}: {}",
+                        n.created_at,
+                        n.author.as_deref().unwrap_or("-"),
+                        n.body
+                    );
+                }
             }
         }
         Commands::Update(args) => {
-            let name = args.name.as_deref();
-            let desc = args.desc.as_deref();
-            let status = args.status.as_deref();
-            if name.is_none() && desc.is_none() && status.is_none() {
-                eprintln!("Error: at least one field required (--name, --desc, --status)");
-                std::process::exit(1);
+            let patch = TicketPatch {
+                name: args.name,
+                description: args.desc,
+                status: args.status,
+                priority: args.priority,
+                add_labels: args.labels,
+                remove_labels: args.unlabels,
+            };
+            let t = store.update(args.id, &patch)?;
+            if json {
+                print_json(&store.export(args.id)?);
+            } else {
+                println!("Updated: {} {}", ids.show(args.id), t.name);
             }
-            let ticket_name = ticket::update_ticket(&conn, args.id, name, desc, status)?;
-            println!("Updated: #{} {}", args.id, ticket_name);
         }
-        Commands::Delete { id } => {
-            let t = ticket::get_ticket(&conn, id)?;
-            ticket::delete_ticket(&conn, id)?;
-            println!("Deleted: #{} {}", id, t.name);
+        Commands::Delete(args) => {
+            let t = store.delete(args.id, args.children.into())?;
+            if json {
+                print_json(&serde_json::json!({ "id": t.id, "name": t.name, "deleted": true }));
+            } else {
+                println!("Deleted: {} {}", ids.show(args.id), t.name);
+            }
         }
         Commands::List(args) => {
-            let filter = build_filter_from_list(&args)?;
-            let tickets = ticket::list_tickets_filtered(&conn, &filter)?;
+            let mut filter = build_filter(&args.filter);
+            if !args.all && !args.filter.has_filters() {
+                filter.query = store.config().list.filter.as_ref().map(|f| f.expr.clone());
+            }
+            let format = match args.output.format {
+                _ if cli.json => Some(cli::OutputFormat::Json),
+                None if env_json => Some(cli::OutputFormat::Json),
+                other => other,
+            };
+            if let Some(format @ (cli::OutputFormat::Jsonl | cli::OutputFormat::Csv)) = format {
+                return stream_export(&store, &filter, format, &args.output.columns, false);
+            }
+            if format == Some(cli::OutputFormat::Json) {
+                let page = store.export_page(&filter)?;
+                println!("{}", export_json(&page, args.filter.paged));
+                return Ok(());
+            }
+            if format == Some(cli::OutputFormat::Markdown) {
+                let page = store.export_page(&filter)?;
+                print!(
+                    "{}",
+                    markdown::render_markdown(
+                        &page.tickets,
+                        markdown::Grouping::Status,
+                        &store.workflow()?,
+                        &ids,
+                    )
+                );
+                return Ok(());
+            }
+            let page = store.list_flagged(&filter)?;
+            let tickets = page.tickets;
             if tickets.is_empty() {
                 println!("No tickets.");
                 return Ok(());
             }
-            let dep_counts = load_dep_counts(&conn)?;
-            if args.timestamps {
-                println!(
-                    "{:>4}  {:<9}  {:<40}  {:<10}  {:<10}",
-                    "ID", "STATUS", "NAME", "CREATED", "UPDATED"
-                );
-                println!("{}", "-".repeat(80));
-                for t in &tickets {
-                    let name = format_name_with_deps(&t.name, dep_counts.get(&t.id).copied());
-                    let created = t.created_at.split('T').next().unwrap_or("");
-                    let updated = t.updated_at.split('T').next().unwrap_or("");
-                    println!(
-                        "{:>4}  {:<9}  {:<40}  {:<10}  {:<10}",
-                        t.id, t.status, name, created, updated
-                    );
-                }
+            let columns = if !args.output.columns.is_empty() {
+                args.output.columns.clone()
             } else {
-                println!("{:>4}  {:<9}  NAME", "ID", "STATUS");
-                println!("{}", "-".repeat(60));
-                for t in &tickets {
-                    let name = format_name_with_deps(&t.name, dep_counts.get(&t.id).copied());
-                    println!("{:>4}  {:<9}  {}", t.id, t.status, name);
+                let mut columns = store
+                    .config()
+                    .list
+                    .columns
+                    .clone()
+                    .unwrap_or_else(|| cli::DEFAULT_COLUMNS.to_vec());
+                if args.timestamps {
+                    for c in [cli::Column::Created, cli::Column::Updated] {
+                        if !columns.contains(&c) {
+                            columns.push(c);
+                        }
+                    }
                 }
-            }
+                columns
+            };
+            let name_width = store.config().name_width();
+            print_ticket_table(&store, &tickets, &columns, name_width)?;
+            print_more(page.next_cursor);
         }
         Commands::Claim(args) => {
-            let agent = resolve_agent()?;
-            ticket::claim_ticket(&mut conn, args.id, &agent, args.force)?;
-            println!("Claimed #{}", args.id);
+            let lease = args.lease.unwrap_or(store.config().claim_lease());
+            let warnings = store.claim(args.id, lease, args.force)?;
+            if json {
+                let ticket = store.export(args.id)?;
+                print_json(&WithWarnings { ticket, warnings });
+            } else {
+                print_warnings(&warnings);
+                println!("Claimed {}", ids.show(args.id));
+            }
+        }
+        Commands::Next(args) => {
+            let lease = args.lease.unwrap_or(store.config().claim_lease());
+            let t = store.claim_next(lease)?.ok_or(AppError::NoReadyTicket)?;
+            if json {
+                let ticket = store.export(t.id)?;
+                print_json(&WithWarnings {
+                    ticket,
+                    warnings: Vec::new(),
+                });
+            } else {
+                print_ticket(&store, &t)?;
+            }
+        }
+        Commands::Heartbeat(args) => {
+            let lease = args.lease.unwrap_or(store.config().heartbeat_lease());
+            let expires = store.heartbeat(args.id, lease)?;
+            if json {
+                print_json(&store.export(args.id)?);
+            } else {
+                println!("Lease on {} extended until {}", ids.show(args.id), expires);
+            }
+        }
+        Commands::Reap => {
+            let reaped = store.reap()?;
+            if json {
+                print_json(&reaped);
+                return Ok(());
+            }
+            if reaped.is_empty() {
+                println!("No expired leases.");
+            }
+            for r in &reaped {
+                println!(
+                    "Reaped: {} {} (lease held by {} expired {}) -> {}",
+                    ids.show(r.id),
+                    r.name,
+                    r.agent,
+                    r.lease_expired_at,
+                    r.restored_status
+                );
+            }
         }
         Commands::Release(args) => {
-            let agent = resolve_agent()?;
-            ticket::release_ticket(&mut conn, args.id, &agent, args.force)?;
-            println!("Released #{}", args.id);
+            let warnings = store.release(args.id, args.force)?;
+            if json {
+                let ticket = store.export(args.id)?;
+                print_json(&WithWarnings { ticket, warnings });
+            } else {
+                print_warnings(&warnings);
+                println!("Released {}", ids.show(args.id));
+            }
         }
         Commands::Block(args) => {
-            let name = ticket::block_ticket(&conn, args.id, &args.reason)?;
-            println!("Blocked: #{} {}", args.id, name);
+            store.block(args.id, &args.reason)?;
+            if json {
+                print_json(&store.export(args.id)?);
+            } else {
+                println!(
+                    "Blocked: {} {}",
+                    ids.show(args.id),
+                    store.get(args.id)?.name
+                );
+            }
         }
-        Commands::Dep(args) => match args.action {
-            cli::DepAction::Add { ticket_id, dep_id } => {
-                ticket::add_dep(&conn, ticket_id, dep_id)?;
-                println!("Added: #{} depends on #{}", ticket_id, dep_id);
+        Commands::Labels => {
+            let counts = store.labels()?;
+            if json {
+                let labels: Vec<_> = counts
+                    .iter()
+                    .map(|(label, count)| serde_json::json!({ "label": label, "count": count }))
+                    .collect();
+                print_json(&labels);
+                return Ok(());
             }
-            cli::DepAction::Remove { ticket_id, dep_id } => {
-                ticket::remove_dep(&conn, ticket_id, dep_id)?;
-                println!("Removed: #{} no longer depends on #{}", ticket_id, dep_id);
+            if counts.is_empty() {
+                println!("No labels.");
             }
-        },
+            for (label, count) in &counts {
+                println!("{:>4}  #{}", count, label);
+            }
+        }
+        Commands::Search(args) => {
+            let hits = store.search(&args.query, args.limit)?;
+            if json {
+                print_json(&hits);
+            } else if hits.is_empty() {
+                println!("No matches.");
+            } else {
+                for h in &hits {
+                    println!("{} {} [{}]", ids.show(h.id), h.name, h.status);
+                    println!("    {}", h.snippet.replace('\n', " "));
+                }
+            }
+        }
+        Commands::Note(args) => {
+            store.add_note(args.id, &args.text)?;
+            if json {
+                print_json(&store.export(args.id)?);
+            } else {
+                println!("Noted: {}", ids.show(args.id));
+            }
+        }
+        Commands::Dep(args) => {
+            let (ticket_id, dep_id, action) = match args.action {
+                cli::DepAction::Add { ticket_id, dep_id } => {
+                    store.add_dep(ticket_id, dep_id)?;
+                    (ticket_id, dep_id, "added")
+                }
+                cli::DepAction::Remove { ticket_id, dep_id } => {
+                    store.remove_dep(ticket_id, dep_id)?;
+                    (ticket_id, dep_id, "removed")
+                }
+            };
+            if json {
+                let deps = store.deps(ticket_id)?;
+                print_json(&serde_json::json!({
+                    "ticket_id": ticket_id,
+                    "depends_on": dep_id,
+                    "action": action,
+                    "dependencies": deps.forward,
+                }));
+            } else if action == "added" {
+                println!(
+                    "Added: {} depends on {}",
+                    ids.show(ticket_id),
+                    ids.show(dep_id)
+                );
+            } else {
+                println!(
+                    "Removed: {} no longer depends on {}",
+                    ids.show(ticket_id),
+                    ids.show(dep_id)
+                );
+            }
+        }
         Commands::Deps(args) => {
-            let deps = ticket::list_deps(&conn, args.id)?;
-            if deps.forward.is_empty() && deps.reverse.is_empty() {
-                println!("#{} has no dependencies.", args.id);
+            if args.all {
+                if json {
+                    print_json(&store.graph()?);
+                    return Ok(());
+                }
+                let format = match args.format {
+                    cli::GraphFormatArg::Dot => graph::GraphFormat::Dot,
+                    cli::GraphFormatArg::Mermaid => graph::GraphFormat::Mermaid,
+                };
+                print!("{}", store.graph()?.render(format, &ids));
+                return Ok(());
+            }
+            let id = args.id.expect("clap requires id unless --all");
+            if args.tree {
+                let up = store.dep_tree(id, graph::Direction::DependsOn)?;
+                let down = store.dep_tree(id, graph::Direction::RequiredBy)?;
+                if json {
+                    let t = &up.ticket;
+                    print_json(&serde_json::json!({
+                        "id": t.id,
+                        "name": t.name,
+                        "status": t.status,
+                        "depends_on": up.children.iter().map(dep_node_json).collect::<Vec<_>>(),
+                        "required_by": down.children.iter().map(dep_node_json).collect::<Vec<_>>(),
+                    }));
+                    return Ok(());
+                }
+                let t = &up.ticket;
+                println!("{} {} [{}]", ids.show(t.id), t.name, t.status);
+                if up.children.is_empty() && down.children.is_empty() {
+                    println!("No dependencies.");
+                }
+                if !up.children.is_empty() {
+                    println!("Depends on:");
+                    print_branches(&up.children, "", &|n| format_dep_node(&ids, n), &|n| {
+                        &n.children
+                    });
+                }
+                if !down.children.is_empty() {
+                    println!("Required by:");
+                    print_branches(&down.children, "", &|n| format_dep_node(&ids, n), &|n| {
+                        &n.children
+                    });
+                }
+                return Ok(());
+            }
+            let deps = store.deps(id)?;
+            if json {
+                store.get(id)?;
+                print_json(&serde_json::json!({
+                    "id": id,
+                    "depends_on": deps.forward,
+                    "required_by": deps.reverse,
+                }));
+            } else if deps.forward.is_empty() && deps.reverse.is_empty() {
+                println!("{} has no dependencies.", ids.show(id));
             } else {
                 if !deps.forward.is_empty() {
                     let fwd = deps
                         .forward
                         .iter()
-                        .map(|i| format!("#{}", i))
+                        .map(|&i| ids.show(i))
                         .collect::<Vec<_>>()
                         .join(", ");
                     println!("Depends on: {}", fwd);
@@ -137,56 +392,549 @@ pub fn run(cli: cli::Cli, conn: Connection) -> Result<(), AppError> {
                     let rev = deps
                         .reverse
                         .iter()
-                        .map(|i| format!("#{}", i))
+                        .map(|&i| ids.show(i))
                         .collect::<Vec<_>>()
                         .join(", ");
                     println!("Required by: {}", rev);
                 }
             }
         }
+        Commands::Tree { id } => {
+            let tree = store.tree(id)?;
+            if json {
+                print_json(&tree_node_json(&tree));
+                return Ok(());
+            }
+            println!("{}", format_tree_node(&ids, &tree));
+            print_branches(&tree.children, "", &|n| format_tree_node(&ids, n), &|n| {
+                &n.children
+            });
+        }
+        Commands::Plan(args) => {
+            let waves = store.plan(args.to)?;
+            if json {
+                print_json(&waves);
+            } else if waves.is_empty() {
+                println!("Nothing to plan.");
+            } else {
+                for w in &waves {
+                    let noun = if w.tickets.len() == 1 {
+                        "ticket"
+                    } else {
+                        "tickets"
+                    };
+                    println!("Wave {} ({} {}):", w.wave, w.tickets.len(), noun);
+                    for t in &w.tickets {
+                        let claimed = t
+                            .claimed_by
+                            .as_deref()
+                            .map(|a| format!(" @{}", a))
+                            .unwrap_or_default();
+                        println!(
+                            "  {} {} [{}] P{}{}",
+                            ids.show(t.id),
+                            t.name,
+                            t.status,
+                            t.priority,
+                            claimed
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Log(args) => {
+            let events = store.events(args.id, args.since.as_deref())?;
+            if json {
+                print_json(&events);
+            } else if events.is_empty() {
+                println!("No events.");
+            } else {
+                for e in &events {
+                    println!("{}", format_event(&ids, e));
+                }
+            }
+        }
         Commands::Export(args) => {
-            let filter = build_filter_from_export(&args);
-            let exports = ticket::tickets_to_export(&conn, &filter)?;
-            if args.json {
-                println!(
+            let filter = build_filter(&args.filter);
+            let format = match args.output.format {
+                _ if cli.json => Some(cli::OutputFormat::Json),
+                None if env_json && args.budget.is_none() && args.template.is_none() => {
+                    Some(cli::OutputFormat::Json)
+                }
+                other => other,
+            };
+            if let Some(format @ (cli::OutputFormat::Jsonl | cli::OutputFormat::Csv)) = format {
+                if args.budget.is_some() || args.template.is_some() {
+                    return Err(AppError::Usage(
+                        "--budget and --template do not apply to jsonl or csv".into(),
+                    ));
+                }
+                return stream_export(
+                    &store,
+                    &filter,
+                    format,
+                    &args.output.columns,
+                    args.latest_note,
+                );
+            }
+            let json = format == Some(cli::OutputFormat::Json);
+            let markdown = format == Some(cli::OutputFormat::Markdown);
+            if args.template.is_some() && !markdown {
+                return Err(AppError::Usage(
+                    "--template requires --format markdown".into(),
+                ));
+            }
+            if args.budget.is_some() && (json || markdown) {
+                return Err(AppError::Usage(
+                    "--budget only applies to text export".into(),
+                ));
+            }
+            let mut page = store.export_page(&filter)?;
+            if args.latest_note {
+                for e in &mut page.tickets {
+                    let older = e.notes.len().saturating_sub(1);
+                    e.notes.drain(..older);
+                }
+            }
+            if let Some(budget) = args.budget {
+                print!(
+                    "{}",
+                    budget::render_budgeted(
+                        &page.tickets,
+                        store.agent(),
+                        budget.chars(),
+                        &store.workflow()?,
+                        &ids,
+                    )
+                );
+            } else if json {
+                println!("{}", export_json(&page, args.filter.paged));
+            } else if markdown {
+                let grouping = args.template.map(Into::into).unwrap_or_default();
+                let workflow = store.workflow()?;
+                print!(
                     "{}",
-                    serde_json::to_string_pretty(&exports).expect("serialize")
+                    markdown::render_markdown(&page.tickets, grouping, &workflow, &ids)
                 );
             } else {
-                for e in &exports {
-                    println!("{}", ticket::format_export_text(e));
+                for e in &page.tickets {
+                    println!("{}", ticket::format_export_text_with(e, &ids));
+                }
+                print_more(page.next_cursor);
+            }
+        }
+        Commands::Import(args) => {
+            let input = if args.file == "-" {
+                let mut buf = String::new();
+                std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf).map(|_| buf)
+            } else {
+                std::fs::read_to_string(&args.file)
+            }
+            .map_err(|e| AppError::InvalidImport(format!("cannot read {}: {}", args.file, e)))?;
+            let nesting = match args.nesting {
+                cli::NestingArg::Parent => import::Nesting::Parent,
+                cli::NestingArg::Deps => import::Nesting::Deps,
+            };
+            let format = match args.format {
+                Some(cli::ImportFormatArg::Json) => Some(import::ImportFormat::Json),
+                Some(cli::ImportFormatArg::Jsonl) => Some(import::ImportFormat::Jsonl),
+                Some(cli::ImportFormatArg::Markdown) => {
+                    Some(import::ImportFormat::Markdown(nesting))
+                }
+                None if args.file.ends_with(".md") || args.file.ends_with(".markdown") => {
+                    Some(import::ImportFormat::Markdown(nesting))
                 }
+                None => None,
+            };
+            let records = import::parse_import(&input, format)?;
+            let report = store.import(
+                &records,
+                import::ImportOptions {
+                    upsert: args.upsert,
+                    dry_run: args.dry_run,
+                },
+            )?;
+            if json {
+                print_json(&report);
+            } else {
+                print_import_report(&ids, &report);
+            }
+        }
+        Commands::Mcp => {
+            let stdin = std::io::stdin();
+            mcp::serve(store, stdin.lock(), std::io::stdout().lock())?;
+        }
+        Commands::Serve(args) => {
+            let listen = server::Listen::parse(&args.listen)?;
+            let server = server::Server::bind(store, &listen)?;
+            // Tests and scripts read this line to learn the chosen port.
+            if json {
+                print_json(&serde_json::json!({ "url": server.url() }));
+            } else {
+                println!("Listening on {}", server.url());
             }
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+            server.run();
         }
+        Commands::Config(args) => run_config(args, json)?,
+        Commands::View(args) => match args.action {
+            cli::ViewAction::Save { name, args } => {
+                validate_view_args(&args, &ids)?;
+                let replaced = store.save_view(&name, &args)?;
+                if json {
+                    print_json(&serde_json::json!({
+                        "name": name,
+                        "args": args,
+                        "replaced": replaced,
+                    }));
+                } else {
+                    let verb = if replaced { "Updated" } else { "Saved" };
+                    println!("{} view @{}", verb, name);
+                }
+            }
+            cli::ViewAction::List => {
+                let views = store.views()?;
+                if json {
+                    print_json(&views);
+                    return Ok(());
+                }
+                if views.is_empty() {
+                    println!("No views.");
+                }
+                for v in &views {
+                    println!("@{:<16} {}", v.name, v.args.join(" "));
+                }
+            }
+            cli::ViewAction::Rm { name } => {
+                store.delete_view(&name)?;
+                if json {
+                    print_json(&serde_json::json!({ "name": name, "deleted": true }));
+                } else {
+                    println!("Deleted view @{}", name);
+                }
+            }
+        },
     }
     Ok(())
 }
 
-fn build_filter_from_list(args: &cli::ListArgs) -> Result<ticket::ListFilter, ticket::AppError> {
-    if args.claimed && args.unclaimed {
-        eprintln!("Error: --claimed and --unclaimed are mutually exclusive");
-        std::process::exit(1);
+/// `rtik config`: runs without opening the database, so a config that no
+/// longer parses can still be fixed with `set` or `unset`.
+pub fn run_config(args: cli::ConfigArgs, json: bool) -> Result<(), AppError> {
+    let path = config::path_for_db(&db::resolve_db_path());
+    let print_setting = |s: &config::Setting| {
+        if json {
+            print_json(s);
+        } else if let Some(v) = &s.value {
+            println!("{}", v);
+        }
+    };
+    match args.action {
+        cli::ConfigAction::Get { key } => {
+            print_setting(&config::Config::load(&path)?.setting(&key)?);
+        }
+        cli::ConfigAction::Set { key, value } => {
+            config::set(&path, &key, Some(&value))?;
+            if json {
+                print_setting(&config::Config::load(&path)?.setting(&key)?);
+            } else {
+                println!("Set {} = {} in {}", key, value, path.display());
+            }
+        }
+        cli::ConfigAction::Unset { key } => {
+            config::set(&path, &key, None)?;
+            if json {
+                print_setting(&config::Config::load(&path)?.setting(&key)?);
+            } else {
+                println!("Unset {} in {}", key, path.display());
+            }
+        }
+        cli::ConfigAction::List => {
+            let settings = config::Config::load(&path)?.settings();
+            if json {
+                print_json(&settings);
+                return Ok(());
+            }
+            for s in &settings {
+                let source = match s.source {
+                    config::Source::Env => "env",
+                    config::Source::Config => "config",
+                    config::Source::Default => "default",
+                };
+                println!(
+                    "{:<16} {:<32} ({})",
+                    s.key,
+                    s.value.as_deref().unwrap_or("-"),
+                    source
+                );
+            }
+        }
     }
-    let claimed = if args.claimed {
-        Some(true)
-    } else if args.unclaimed {
-        Some(false)
-    } else {
-        None
+    Ok(())
+}
+
+/// Print tickets with their computed states as a table. Labels (unless
+/// shown as a column) and the states trail each row; names are cut to `name_width`.
+fn print_ticket_table(
+    store: &Store,
+    tickets: &[(ticket::Ticket, ticket::TicketFlags)],
+    columns: &[cli::Column],
+    name_width: usize,
+) -> Result<(), AppError> {
+    use cli::Column;
+    let ids = store.ids();
+    let dep_counts = store.dep_counts()?;
+    let label_map = store.labels_by_ticket()?;
+    let now = ticket::chrono_free_utc_now();
+    let spec = |c: Column| -> (&'static str, usize) {
+        match c {
+            Column::Id => ("ID", 4),
+            Column::Priority => ("PRI", 3),
+            Column::Status => ("STATUS", 9),
+            Column::Name => ("NAME", name_width),
+            Column::Claimer => ("CLAIMER", 12),
+            Column::Labels => ("LABELS", 20),
+            Column::Parent => ("PARENT", 6),
+            Column::Created => ("CREATED", 10),
+            Column::Updated => ("UPDATED", 10),
+            Column::Description => ("DESCRIPTION", 40),
+            Column::ClaimedAt => ("CLAIMED AT", 20),
+            Column::LeaseExpiresAt => ("LEASE UNTIL", 20),
+            Column::BlockReason => ("BLOCK REASON", 30),
+            Column::Dependencies => ("DEPS", 12),
+            Column::Ready => ("READY", 5),
+            Column::Waiting => ("WAITING", 7),
+            Column::Blocking => ("BLOCKING", 8),
+            Column::ExternalKey => ("KEY", 12),
+        }
     };
-    Ok(ticket::ListFilter {
-        status: args.status.clone(),
-        claimed,
-        claimer: args.claimer.clone(),
-        search: args.search.clone(),
+    let row = |cells: Vec<String>| -> String {
+        let last = cells.len().saturating_sub(1);
+        cells
+            .iter()
+            .zip(columns)
+            .enumerate()
+            .map(|(i, (cell, &c))| {
+                let width = spec(c).1;
+                if c == Column::Id {
+                    format!("{:>width$}", cell)
+                } else if i == last {
+                    cell.clone()
+                } else {
+                    format!("{:<width$}", cell)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+
+    println!(
+        "{}",
+        row(columns.iter().map(|&c| spec(c).0.to_string()).collect())
+    );
+    let width: usize = columns.iter().map(|&c| spec(c).1 + 2).sum::<usize>() - 2;
+    println!("{}", "-".repeat(width.max(65)));
+    let yes = |b: bool| if b { "yes" } else { "" }.to_string();
+    for (t, flags) in tickets {
+        let labels = label_map.get(&t.id).cloned().unwrap_or_default();
+        let active_claim = !t.lease_expired(&now);
+        let deps = if columns.contains(&Column::Dependencies) {
+            store.deps(t.id)?.forward
+        } else {
+            Vec::new()
+        };
+        let cells = columns
+            .iter()
+            .map(|&c| match c {
+                Column::Id => t.id.to_string(),
+                Column::Priority => format!("P{}", t.priority),
+                Column::Status => t.status.to_string(),
+                Column::Name => {
+                    format_name_with_deps(&t.name, dep_counts.get(&t.id).copied(), name_width)
+                }
+                Column::Claimer => match &t.claimed_by {
+                    Some(agent) if active_claim => agent.clone(),
+                    _ => "-".to_string(),
+                },
+                Column::ClaimedAt => match &t.claimed_at {
+                    Some(at) if active_claim && t.claimed_by.is_some() => at.clone(),
+                    _ => "-".to_string(),
+                },
+                Column::LeaseExpiresAt => match &t.lease_expires_at {
+                    Some(at) if active_claim && t.claimed_by.is_some() => at.clone(),
+                    _ => "-".to_string(),
+                },
+                Column::Description => truncate_name(&t.description.replace('\n', " "), 40),
+                Column::BlockReason => match &t.block_reason {
+                    Some(r) if t.status == "blocked" => truncate_name(r, 30),
+                    _ => "-".to_string(),
+                },
+                Column::Dependencies => deps
+                    .iter()
+                    .map(|&d| ids.show(d))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                Column::Ready => yes(flags.ready),
+                Column::Waiting => yes(flags.waiting),
+                Column::Blocking => yes(flags.blocking),
+                Column::Labels => labels
+                    .iter()
+                    .map(|l| format!("#{}", l))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Column::Parent => t
+                    .parent_id
+                    .map(|p| ids.show(p))
+                    .unwrap_or_else(|| "-".to_string()),
+                Column::Created => t.created_at.split('T').next().unwrap_or("").to_string(),
+                Column::Updated => t.updated_at.split('T').next().unwrap_or("").to_string(),
+                Column::ExternalKey => t.external_key.clone().unwrap_or_else(|| "-".to_string()),
+            })
+            .collect();
+        let mut suffix = String::new();
+        if !columns.contains(&Column::Labels) {
+            for l in &labels {
+                suffix.push_str(&format!(" #{}", l));
+            }
+        }
+        suffix.push_str(&ticket::format_flags(*flags));
+        println!("{}{}", row(cells), suffix);
+    }
+    Ok(())
+}
+
+fn tree_node_json(node: &ticket::TreeNode) -> serde_json::Value {
+    let t = &node.ticket;
+    let (done, total) = node.rollup();
+    serde_json::json!({
+        "id": t.id,
+        "name": t.name,
+        "status": t.status,
+        "done": done,
+        "total": total,
+        "children": node.children.iter().map(tree_node_json).collect::<Vec<_>>(),
     })
 }
 
-fn build_filter_from_export(args: &cli::ExportArgs) -> ticket::ListFilter {
-    if args.claimed && args.unclaimed {
-        eprintln!("Error: --claimed and --unclaimed are mutually exclusive");
-        std::process::exit(1);
+fn dep_node_json(node: &graph::DepNode) -> serde_json::Value {
+    let t = &node.ticket;
+    serde_json::json!({
+        "id": t.id,
+        "name": t.name,
+        "status": t.status,
+        "repeated": node.repeated,
+        "children": node.children.iter().map(dep_node_json).collect::<Vec<_>>(),
+    })
+}
+
+fn format_tree_node(ids: &IdPrefix, node: &ticket::TreeNode) -> String {
+    let t = &node.ticket;
+    let mut line = format!("{} {} [{}]", ids.show(t.id), t.name, t.status);
+    if !node.children.is_empty() {
+        let (done, total) = node.rollup();
+        line.push_str(&format!(" ({}/{} done)", done, total));
+    }
+    line
+}
+
+fn format_dep_node(ids: &IdPrefix, node: &graph::DepNode) -> String {
+    let t = &node.ticket;
+    let seen = if node.repeated { " (see above)" } else { "" };
+    format!("{} {} [{}]{}", ids.show(t.id), t.name, t.status, seen)
+}
+
+/// Print `nodes` as box-drawing branches, recursing through `children`.
+fn print_branches<T>(
+    nodes: &[T],
+    prefix: &str,
+    label: &dyn Fn(&T) -> String,
+    children: &dyn Fn(&T) -> &[T],
+) {
+    let last = nodes.len().saturating_sub(1);
+    for (i, node) in nodes.iter().enumerate() {
+        let (branch, indent) = if i == last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        println!("{}{}{}", prefix, branch, label(node));
+        print_branches(
+            children(node),
+            &format!("{}{}", prefix, indent),
+            label,
+            children,
+        );
+    }
+}
+
+fn print_ticket(store: &Store, t: &ticket::Ticket) -> Result<(), AppError> {
+    let ids = store.ids();
+    let created_date = t.created_at.split('T').next().unwrap_or(&t.created_at);
+    let updated_date = t.updated_at.split('T').next().unwrap_or(&t.updated_at);
+    println!(
+        "{} {} [{}] P{}",
+        ids.show(t.id),
+        t.name,
+        t.status,
+        t.priority
+    );
+    if !t.description.is_empty() {
+        println!("{}", t.description);
     }
+    println!("Created: {} | Updated: {}", created_date, updated_date);
+    if let Some(parent) = t.parent_id {
+        println!("Parent: {}", ids.show(parent));
+    }
+    let subtasks = store.list(&ticket::ListFilter::new().parent(t.id))?.tickets;
+    if !subtasks.is_empty() {
+        let workflow = store.workflow()?;
+        let done = subtasks
+            .iter()
+            .filter(|s| workflow.is_done(&s.status))
+            .count();
+        let listed = subtasks
+            .iter()
+            .map(|s| ids.show(s.id))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("Subtasks: {} ({}/{} done)", listed, done, subtasks.len());
+    }
+    let labels = store.ticket_labels(t.id)?;
+    if !labels.is_empty() {
+        println!("Labels: {}", labels.join(", "));
+    }
+    if let Some(ref owner) = t.claimed_by {
+        match t.lease_expires_at {
+            Some(ref exp) if t.lease_expired(&ticket::chrono_free_utc_now()) => {
+                println!("Claimed by: {} (lease expired {})", owner, exp)
+            }
+            Some(ref exp) => println!("Claimed by: {} (lease until {})", owner, exp),
+            None => println!("Claimed by: {}", owner),
+        }
+    }
+    let deps = store.deps(t.id)?;
+    if !deps.forward.is_empty() {
+        let fwd = deps
+            .forward
+            .iter()
+            .map(|&i| ids.show(i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("Depends on: {}", fwd);
+    }
+    if !deps.reverse.is_empty() {
+        let rev = deps
+            .reverse
+            .iter()
+            .map(|&i| ids.show(i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("Required by: {}", rev);
+    }
+    Ok(())
+}
+
+fn build_filter(args: &cli::FilterArgs) -> ticket::ListFilter {
     let claimed = if args.claimed {
         Some(true)
     } else if args.unclaimed {
@@ -199,38 +947,169 @@ fn build_filter_from_export(args: &cli::ExportArgs) -> ticket::ListFilter {
         claimed,
         claimer: args.claimer.clone(),
         search: args.search.clone(),
+        labels: args
+            .labels
+            .iter()
+            .map(|g| g.split(',').map(str::to_string).collect())
+            .collect(),
+        exclude_labels: args.no_labels.clone(),
+        parent: args.parent.map(i64::from),
+        top_level: args.top_level,
+        ready: args.ready,
+        waiting: args.waiting,
+        blocking: args.blocking,
+        query: args.query.clone(),
+        sort: args.sort.clone(),
+        reverse: args.reverse,
+        limit: args.limit,
+        offset: args.offset,
+        after: args.after,
+    }
+}
+
+/// Check view flags at save time so a bad view fails then, not at use. A
+/// view may hold flags for either `list` or `export`; when neither accepts
+/// them the `list` error is reported.
+fn validate_view_args(args: &[String], ids: &IdPrefix) -> Result<(), AppError> {
+    let parse = |command: &str| {
+        let argv = ["rtik", command]
+            .into_iter()
+            .map(String::from)
+            .chain(args.iter().cloned());
+        cli::command(ids).try_get_matches_from(argv).map(|_| ())
+    };
+    let Err(e) = parse("list").or_else(|e| parse("export").map_err(|_| e)) else {
+        return Ok(());
+    };
+    let msg = e.to_string();
+    let first = msg
+        .lines()
+        .next()
+        .unwrap_or("")
+        .trim_start_matches("error: ");
+    Err(AppError::InvalidViewArgs(first.to_string()))
+}
+
+/// JSON for a listing: a bare array, or with `--paged` an object carrying
+/// `next_cursor` for the following page.
+fn export_json(page: &ticket::ExportPage, paged: bool) -> String {
+    if paged {
+        serde_json::to_string_pretty(page).expect("serialize")
+    } else {
+        serde_json::to_string_pretty(&page.tickets).expect("serialize")
+    }
+}
+
+/// Write `jsonl` or `csv` output one ticket at a time as rows are read.
+/// CSV uses `columns`, or every field when none are given.
+fn stream_export(
+    store: &Store,
+    filter: &ticket::ListFilter,
+    format: cli::OutputFormat,
+    columns: &[cli::Column],
+    latest_note: bool,
+) -> Result<(), AppError> {
+    let columns = if columns.is_empty() {
+        &csv::ALL_COLUMNS[..]
+    } else {
+        columns
+    };
+    if format == cli::OutputFormat::Csv {
+        println!("{}", csv::header(columns));
+    }
+    store.for_each_export(filter, |mut e| {
+        if latest_note {
+            let older = e.notes.len().saturating_sub(1);
+            e.notes.drain(..older);
+        }
+        if format == cli::OutputFormat::Csv {
+            println!("{}", csv::record(&e, columns));
+        } else {
+            println!("{}", serde_json::to_string(&e).expect("serialize"));
+        }
+        Ok(())
+    })
+}
+
+/// One `source → #id` line per ticket, so the input can be annotated with
+/// the ids it was given, then a tally.
+fn print_import_report(ids: &IdPrefix, report: &import::ImportReport) {
+    let mut counts = [0usize; 3];
+    for t in &report.tickets {
+        let (i, verb) = match t.action {
+            import::ImportAction::Created => (0, "created"),
+            import::ImportAction::Updated => (1, "updated"),
+            import::ImportAction::Unchanged => (2, "unchanged"),
+        };
+        counts[i] += 1;
+        println!(
+            "{:>8} → {:<6} {:<9} {}",
+            t.source_id.as_deref().unwrap_or("-"),
+            ids.show(t.id),
+            verb,
+            t.name
+        );
+    }
+    println!(
+        "{} {} ticket(s): {} created, {} updated, {} unchanged; {} dependency link(s) added",
+        if report.dry_run {
+            "Would import"
+        } else {
+            "Imported"
+        },
+        report.tickets.len(),
+        counts[0],
+        counts[1],
+        counts[2],
+        report.dependencies
+    );
+    if report.dry_run {
+        println!("Dry run: nothing was written.");
+    }
+}
+
+fn print_more(next_cursor: Option<i64>) {
+    if let Some(id) = next_cursor {
+        println!("... more: --after {}", id);
     }
 }
 
 fn truncate_name(name: &str, max_len: usize) -> String {
-    if name.len() > max_len {
-        format!("{}...", &name[..max_len - 3])
+    if name.chars().count() > max_len {
+        let kept: String = name.chars().take(max_len - 3).collect();
+        format!("{}...", kept)
     } else {
         name.to_string()
     }
 }
 
-fn format_name_with_deps(name: &str, dep_count: Option<i64>) -> String {
+fn format_event(ids: &IdPrefix, e: &ticket::TicketEvent) -> String {
+    let value = |v: &Option<String>| match v {
+        Some(v) => truncate_name(&v.replace('\n', " "), 40),
+        None => "-".to_string(),
+    };
+    let change = match (&e.old_value, &e.new_value) {
+        (None, Some(_)) => value(&e.new_value),
+        (Some(_), None) if e.kind != "claim" => value(&e.old_value),
+        _ => format!("{} → {}", value(&e.old_value), value(&e.new_value)),
+    };
+    format!(
+        "{}  {:<5} {:<12} {:<12} {}",
+        e.created_at,
+        ids.show(e.ticket_id),
+        e.actor.as_deref().unwrap_or("-"),
+        e.kind,
+        change
+    )
+}
+
+fn format_name_with_deps(name: &str, dep_count: Option<i64>, width: usize) -> String {
     match dep_count {
         Some(n) if n > 0 => {
             let suffix = format!(" [{} dep{}]", n, if n == 1 { "" } else { "s" });
-            let truncated = truncate_name(name, 35);
+            let truncated = truncate_name(name, width - 5);
             format!("{}{}", truncated, suffix)
         }
-        _ => truncate_name(name, 40),
-    }
-}
-
-fn load_dep_counts(
-    conn: &Connection,
-) -> Result<std::collections::HashMap<i64, i64>, ticket::AppError> {
-    let mut stmt =
-        conn.prepare("SELECT ticket_id, COUNT(*) FROM ticket_deps GROUP BY ticket_id")?;
-    let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, i64>(1)?)))?;
-    let mut map = std::collections::HashMap::new();
-    for row in rows {
-        let (id, count) = row?;
-        map.insert(id, count);
+        _ => truncate_name(name, width),
     }
-    Ok(map)
 }
@@ -1,20 +1,64 @@
-use clap::Parser;
+use clap::FromArgMatches;
 
 fn main() {
     sigpipe::reset(); // CLI-06: must be first — resets SIGPIPE before any I/O
 
-    let cli = rtik::cli::Cli::parse(); // exits with code 2 on usage error (CLI-05)
-    let db_path = rtik::db::resolve_db_path();
-    let conn = match rtik::db::open_connection(&db_path) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error: cannot open database: {e}");
-            std::process::exit(1);
+    // Read before parsing so usage errors can be reported as JSON too, and
+    // ids given with the project's prefix parse. A config that fails to
+    // load is reported once a command opens the database.
+    let config =
+        rtik::config::Config::load(&rtik::config::path_for_db(&rtik::db::resolve_db_path()))
+            .unwrap_or_default();
+    let mut args: Vec<String> = std::env::args().collect();
+    let json = config.json_output() || args.iter().skip(1).any(|a| a == "--json");
+    let fail = |code: &str, message: String, exit_code: i32| -> ! {
+        if json {
+            eprintln!("{}", rtik::error_json(code, &message));
+        } else {
+            eprintln!("Error: {message}");
         }
+        std::process::exit(exit_code);
     };
 
-    if let Err(e) = rtik::run(cli, conn) {
-        eprintln!("Error: {e}");
-        std::process::exit(1);
+    let open = || match rtik::Store::open_default() {
+        Ok(store) => store,
+        Err(e @ rtik::AppError::InvalidConfig(_)) => fail(e.code(), e.to_string(), 1),
+        Err(e) => fail("database", format!("cannot open database: {e}"), 1),
+    };
+
+    // `list @name` / `export @name` splice in a saved view before parsing;
+    // the store opened for that is kept for the command itself.
+    let mut store = None;
+    if rtik::view::view_ref(&args).is_some() {
+        let opened = open();
+        args = rtik::view::expand_view(opened.conn(), args)
+            .unwrap_or_else(|e| fail(e.code(), e.to_string(), e.exit_code()));
+        store = Some(opened);
+    }
+
+    let parsed = rtik::cli::command(&config.ids())
+        .try_get_matches_from(args)
+        .and_then(|matches| rtik::cli::Cli::from_arg_matches(&matches));
+    let cli = match parsed {
+        Ok(cli) => cli,
+        // Help and version still print normally; usage errors exit 2 (CLI-05).
+        Err(e) if json && e.use_stderr() => {
+            let msg = e.to_string();
+            let first = msg
+                .lines()
+                .next()
+                .unwrap_or("")
+                .trim_start_matches("error: ");
+            fail("usage", first.to_string(), 2)
+        }
+        Err(e) => e.exit(),
+    };
+    let result = match cli.command {
+        // Needs no database, and must work while the config is broken.
+        rtik::cli::Commands::Config(args) => rtik::run_config(args, json),
+        _ => rtik::run(cli, store.unwrap_or_else(open)),
+    };
+    if let Err(e) = result {
+        fail(e.code(), e.to_string(), e.exit_code());
     }
 }
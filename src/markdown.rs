@@ -0,0 +1,146 @@
+//! Markdown status reports for `export --format markdown`.
+
+use crate::model::IdPrefix;
+use crate::ticket::{TicketExport, DEFAULT_PRIORITY};
+use crate::workflow::Workflow;
+
+/// How tickets are split into sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Grouping {
+    #[default]
+    Status,
+    Claimer,
+    Label,
+}
+
+/// Section title for a status: `in-progress` becomes `In progress`.
+fn status_title(status: &str) -> String {
+    let spaced = status.replace('-', " ");
+    let mut chars = spaced.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Render tickets as a checklist under one `##` section per group. Status
+/// sections follow `workflow` order, and a ticket is checked off when its
+/// status is done. A ticket with several labels appears under each of them
+/// when grouping by label.
+pub fn render_markdown(
+    exports: &[TicketExport],
+    grouping: Grouping,
+    workflow: &Workflow,
+    ids: &IdPrefix,
+) -> String {
+    let mut sections: Vec<(String, Vec<&TicketExport>)> = Vec::new();
+    match grouping {
+        Grouping::Status => {
+            let mut statuses: Vec<&str> = workflow
+                .statuses()
+                .iter()
+                .map(|d| d.status.as_str())
+                .collect();
+            // Statuses the workflow no longer has go last.
+            for t in exports {
+                if !statuses.contains(&t.status.as_str()) {
+                    statuses.push(t.status.as_str());
+                }
+            }
+            for status in statuses {
+                let items = exports.iter().filter(|t| t.status == status).collect();
+                sections.push((status_title(status), items));
+            }
+        }
+        Grouping::Claimer => {
+            let mut claimers: Vec<&str> = exports
+                .iter()
+                .filter_map(|t| t.claimed_by.as_deref())
+                .collect();
+            claimers.sort_unstable();
+            claimers.dedup();
+            for c in claimers {
+                let items = exports
+                    .iter()
+                    .filter(|t| t.claimed_by.as_deref() == Some(c))
+                    .collect();
+                sections.push((format!("@{}", c), items));
+            }
+            let rest = exports.iter().filter(|t| t.claimed_by.is_none()).collect();
+            sections.push(("Unclaimed".to_string(), rest));
+        }
+        Grouping::Label => {
+            let mut labels: Vec<&str> = exports
+                .iter()
+                .flat_map(|t| t.labels.iter().map(String::as_str))
+                .collect();
+            labels.sort_unstable();
+            labels.dedup();
+            for l in labels {
+                let items = exports
+                    .iter()
+                    .filter(|t| t.labels.iter().any(|x| x == l))
+                    .collect();
+                sections.push((format!("#{}", l), items));
+            }
+            let rest = exports.iter().filter(|t| t.labels.is_empty()).collect();
+            sections.push(("Unlabelled".to_string(), rest));
+        }
+    }
+
+    let mut out = String::from("# Tickets\n");
+    for (title, items) in sections.iter().filter(|(_, items)| !items.is_empty()) {
+        out.push_str(&format!("\n## {} ({})\n\n", title, items.len()));
+        for t in items {
+            out.push_str(&render_item(t, grouping != Grouping::Status, workflow, ids));
+        }
+    }
+    out
+}
+
+fn render_item(t: &TicketExport, show_status: bool, workflow: &Workflow, ids: &IdPrefix) -> String {
+    let check = if workflow.is_done(&t.status) {
+        "x"
+    } else {
+        " "
+    };
+    let mut meta = Vec::new();
+    if show_status {
+        meta.push(format!("`{}`", t.status));
+    }
+    if t.priority != DEFAULT_PRIORITY {
+        meta.push(format!("P{}", t.priority));
+    }
+    if let Some(c) = &t.claimed_by {
+        meta.push(format!("@{}", c));
+    }
+    if let Some(p) = t.parent_id {
+        meta.push(format!("parent {}", ids.show(p)));
+    }
+    if !t.dependencies.is_empty() {
+        let deps: Vec<String> = t.dependencies.iter().map(|&d| ids.show(d)).collect();
+        meta.push(format!("depends on {}", deps.join(", ")));
+    }
+    if !t.labels.is_empty() {
+        let labels: Vec<String> = t.labels.iter().map(|l| format!("`{}`", l)).collect();
+        meta.push(labels.join(" "));
+    }
+
+    let mut out = format!("- [{}] {} **{}**", check, ids.show(t.id), t.name);
+    if !meta.is_empty() {
+        out.push_str(" — ");
+        out.push_str(&meta.join(" · "));
+    }
+    out.push('\n');
+    if let Some(reason) = &t.block_reason {
+        out.push_str(&format!("  > Blocked: {}\n", reason));
+    }
+    for line in t.description.trim_end().lines() {
+        if line.trim().is_empty() {
+            out.push('\n');
+        } else {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+    out
+}
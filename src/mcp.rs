@@ -0,0 +1,348 @@
+//! `rtik mcp`: a Model Context Protocol server speaking JSON-RPC 2.0 over
+//! stdio, one message per line. Tools are the operations in `ops` and
+//! return the same JSON shapes as `--json`.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+use crate::ops::{self, OpError};
+use crate::store::Store;
+use crate::ticket::{self, AppError, ListFilter, SortField, SortKey};
+
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+const CLAIMED_URI: &str = "rtik://tickets/claimed";
+const READY_URI: &str = "rtik://tickets/ready";
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// One client session. Holds a single store for its whole lifetime.
+pub struct Session {
+    store: Store,
+}
+
+impl Session {
+    /// The store's agent identifies this client for claims and the
+    /// claimed-tickets resource; tools may override it per call.
+    pub fn new(store: Store) -> Self {
+        Session { store }
+    }
+
+    /// Handle one line of input. Returns the response to write, or `None`
+    /// for notifications.
+    pub fn handle_line(&mut self, line: &str) -> Option<Value> {
+        let msg: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        let id = msg.get("id").cloned();
+        let Some(method) = msg.get("method").and_then(Value::as_str) else {
+            let id = id.unwrap_or(Value::Null);
+            return Some(error_response(id, INVALID_REQUEST, "missing method"));
+        };
+        let params = msg.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.dispatch(method, params);
+        // Notifications get no reply, not even an error.
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn dispatch(&mut self, method: &str, params: Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => {
+                let version = params
+                    .get("protocolVersion")
+                    .and_then(Value::as_str)
+                    .unwrap_or(PROTOCOL_VERSION);
+                Ok(json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {}, "resources": {} },
+                    "serverInfo": { "name": "rtik", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "ping" | "notifications/initialized" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => {
+                let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+                let args = params
+                    .get("arguments")
+                    .cloned()
+                    .unwrap_or_else(|| json!({}));
+                // Only the advertised tools; `ops` has a few more for HTTP.
+                let listed = tool_definitions()
+                    .as_array()
+                    .is_some_and(|tools| tools.iter().any(|t| t["name"] == name));
+                let result = if listed {
+                    ops::call(&mut self.store, None, name, args)
+                } else {
+                    Err(OpError::Unknown)
+                };
+                match result {
+                    Ok(value) => Ok(tool_result(value, false)),
+                    Err(OpError::Unknown) => {
+                        Err((INVALID_PARAMS, format!("unknown tool '{}'", name)))
+                    }
+                    Err(OpError::Params(msg)) => Err((INVALID_PARAMS, msg)),
+                    Err(OpError::App(e)) => Ok(tool_result(
+                        json!({ "code": e.code(), "message": e.to_string() }),
+                        true,
+                    )),
+                }
+            }
+            "resources/list" => Ok(json!({
+                "resources": [
+                    {
+                        "uri": CLAIMED_URI,
+                        "name": "My claimed tickets",
+                        "description": "Tickets currently claimed by this agent",
+                        "mimeType": "application/json",
+                    },
+                    {
+                        "uri": READY_URI,
+                        "name": "Ready queue",
+                        "description": "Todo, unclaimed tickets whose dependencies are done, in the order `next` takes them",
+                        "mimeType": "application/json",
+                    },
+                ]
+            })),
+            "resources/read" => {
+                let uri = params.get("uri").and_then(Value::as_str).unwrap_or("");
+                let tickets = match self.read_resource(uri) {
+                    Ok(Some(t)) => t,
+                    Ok(None) => {
+                        return Err((INVALID_PARAMS, format!("unknown resource '{}'", uri)))
+                    }
+                    Err(e) => return Err((INVALID_PARAMS, e.to_string())),
+                };
+                let text = serde_json::to_string_pretty(&tickets).expect("serialize");
+                Ok(json!({
+                    "contents": [{ "uri": uri, "mimeType": "application/json", "text": text }]
+                }))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("method '{}' not found", method))),
+        }
+    }
+
+    /// `Ok(None)` for an unknown URI.
+    fn read_resource(&self, uri: &str) -> Result<Option<Vec<ticket::TicketExport>>, AppError> {
+        let filter = match uri {
+            CLAIMED_URI => ListFilter {
+                claimer: Some(self.store.agent().ok_or(AppError::AgentNotSet)?.to_string()),
+                ..Default::default()
+            },
+            READY_URI => ListFilter {
+                ready: true,
+                sort: vec![SortKey {
+                    field: SortField::Priority,
+                    descending: false,
+                }],
+                ..Default::default()
+            },
+            _ => return Ok(None),
+        };
+        ticket::tickets_to_export(self.store.conn(), &filter).map(Some)
+    }
+}
+
+/// Serve requests from `input` until it closes, writing one response line
+/// per request to `output`.
+pub fn serve(store: Store, input: impl BufRead, mut output: impl Write) -> Result<(), AppError> {
+    let mut session = Session::new(store);
+    for line in input.lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = session.handle_line(&line) {
+            if writeln!(output, "{}", response)
+                .and_then(|_| output.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn tool_result(value: Value, is_error: bool) -> Value {
+    let text = match &value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).expect("serialize"),
+    };
+    let mut result = json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    });
+    if value.is_object() {
+        result["structuredContent"] = value;
+    }
+    result
+}
+
+fn filter_schema() -> Value {
+    json!({
+        "status": { "type": "string", "description": "A workflow status: todo, in-progress, blocked, done, or one the project defines" },
+        "labels": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Only tickets carrying all of these labels",
+        },
+        "claimer": { "type": "string", "description": "Only tickets claimed by this agent" },
+        "ready": {
+            "type": "boolean",
+            "description": "Only todo, unclaimed tickets whose dependencies are all done",
+        },
+        "parent": { "type": "integer", "description": "Only direct subtasks of this ticket" },
+        "query": {
+            "type": "string",
+            "description": "Query expression, e.g. 'status:todo AND (label:db OR priority<=1)'",
+        },
+        "sort": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Keys among priority, created, updated, id; append :desc to reverse",
+        },
+        "limit": { "type": "integer", "minimum": 1 },
+        "after": {
+            "type": "integer",
+            "description": "Cursor: the next_cursor of the previous page",
+        },
+    })
+}
+
+fn tool_definitions() -> Value {
+    let id = json!({ "type": "integer", "description": "Ticket id" });
+    let priority = json!({ "type": "integer", "minimum": 0, "maximum": 4, "description": "0 is most urgent; default 2" });
+    let labels = json!({ "type": "array", "items": { "type": "string" } });
+    let agent = json!({ "type": "string", "description": "Defaults to the server's agent (RTIK_AGENT or .rtik.toml)" });
+    let dep = json!({
+        "type": "object",
+        "properties": {
+            "ticket_id": id,
+            "depends_on": { "type": "integer", "description": "Ticket that must be done first" },
+        },
+        "required": ["ticket_id", "depends_on"],
+    });
+    let mut export_props = filter_schema();
+    export_props["format"] =
+        json!({ "type": "string", "enum": ["json", "text", "markdown"], "default": "json" });
+    export_props["budget"] = json!({
+        "type": "integer",
+        "minimum": 1,
+        "description": "Text format only: cap output at about this many tokens, most relevant first",
+    });
+
+    json!([
+        {
+            "name": "create_ticket",
+            "description": "Create a ticket. Returns the new ticket.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "description": { "type": "string" },
+                    "priority": priority,
+                    "labels": labels,
+                    "parent": { "type": "integer", "description": "Make this a subtask of that ticket" },
+                },
+                "required": ["name"],
+            },
+        },
+        {
+            "name": "get_ticket",
+            "description": "Fetch one ticket with its dependencies, labels and notes.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "notes": { "type": "integer", "minimum": 0, "description": "Keep only the most recent N notes" },
+                },
+                "required": ["id"],
+            },
+        },
+        {
+            "name": "update_ticket",
+            "description": "Change ticket fields. Status changes follow the usual transition rules.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "name": { "type": "string" },
+                    "description": { "type": "string" },
+                    "status": { "type": "string", "description": "A workflow status: todo, in-progress, blocked, done, or one the project defines" },
+                    "priority": priority,
+                    "add_labels": labels,
+                    "remove_labels": labels,
+                },
+                "required": ["id"],
+            },
+        },
+        {
+            "name": "claim_ticket",
+            "description": "Claim a ticket, or the next ready one when id is omitted. Returns the ticket and any warnings.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "agent": agent,
+                    "force": { "type": "boolean", "description": "Take over a live claim" },
+                    "lease_secs": { "type": "integer", "minimum": 0, "description": "Lease length; default lease.claim from .rtik.toml, else 3600" },
+                },
+            },
+        },
+        {
+            "name": "release_ticket",
+            "description": "Release a claim, returning the ticket to todo.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "agent": agent,
+                    "force": { "type": "boolean", "description": "Release someone else's claim" },
+                },
+                "required": ["id"],
+            },
+        },
+        {
+            "name": "block_ticket",
+            "description": "Mark a ticket blocked with a reason.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "id": id, "reason": { "type": "string" } },
+                "required": ["id", "reason"],
+            },
+        },
+        {
+            "name": "add_dependency",
+            "description": "Record that ticket_id depends on depends_on. Cycles are rejected.",
+            "inputSchema": dep,
+        },
+        {
+            "name": "remove_dependency",
+            "description": "Remove a dependency between two tickets.",
+            "inputSchema": dep,
+        },
+        {
+            "name": "list_tickets",
+            "description": "List tickets as short summaries, one page at a time.",
+            "inputSchema": { "type": "object", "properties": filter_schema() },
+        },
+        {
+            "name": "export_tickets",
+            "description": "Export full tickets as JSON, compact text or a Markdown report.",
+            "inputSchema": { "type": "object", "properties": export_props },
+        },
+    ])
+}
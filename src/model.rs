@@ -0,0 +1,192 @@
+//! Typed values shared by the library API: ticket ids and statuses.
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::ticket::AppError;
+
+/// A ticket's workflow state. The four built-in statuses always exist;
+/// others come from the project's workflow (see `crate::workflow`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Status {
+    Todo,
+    InProgress,
+    Blocked,
+    Done,
+    Custom(String),
+}
+
+impl Status {
+    /// The built-in statuses, in workflow order.
+    pub const ALL: [Status; 4] = [
+        Status::Todo,
+        Status::InProgress,
+        Status::Blocked,
+        Status::Done,
+    ];
+
+    /// The name stored in the database and shown to users.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Status::Todo => "todo",
+            Status::InProgress => "in-progress",
+            Status::Blocked => "blocked",
+            Status::Done => "done",
+            Status::Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Case-insensitive; `in_progress` is accepted for `in-progress`. Any other
+/// well-formed name parses as `Custom`: whether the workflow has it is
+/// checked where the status is used.
+impl FromStr for Status {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let normalized = raw.trim().to_lowercase().replace('_', "-");
+        if let Some(builtin) = Status::ALL.into_iter().find(|s| s.as_str() == normalized) {
+            return Ok(builtin);
+        }
+        let well_formed = normalized.starts_with(|c: char| c.is_ascii_lowercase())
+            && normalized
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if well_formed {
+            Ok(Status::Custom(normalized))
+        } else {
+            Err(AppError::InvalidStatus(
+                raw.to_string(),
+                "status names are a letter followed by letters, digits and '-'".into(),
+            ))
+        }
+    }
+}
+
+impl PartialEq<str> for Status {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Status {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Serialize for Status {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Status {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql for Status {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Status {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: AppError| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// A ticket id. Displays as `#12`; use `IdPrefix` for a project's own form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TicketId(pub i64);
+
+impl TicketId {
+    pub fn get(self) -> i64 {
+        self.0
+    }
+}
+
+impl fmt::Display for TicketId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+impl From<i64> for TicketId {
+    fn from(id: i64) -> Self {
+        TicketId(id)
+    }
+}
+
+impl From<TicketId> for i64 {
+    fn from(id: TicketId) -> Self {
+        id.0
+    }
+}
+
+/// Accepts `12` or `#12`.
+impl FromStr for TicketId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        IdPrefix::default().parse(raw)
+    }
+}
+
+/// How a project writes ticket ids: `#12` by default, or with the
+/// `id_prefix` from `.rtik.toml`, e.g. `T-12`. Every place that shows or
+/// reads ids for people goes through the store's `IdPrefix`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdPrefix(Option<String>);
+
+impl IdPrefix {
+    pub fn new(prefix: impl Into<String>) -> IdPrefix {
+        IdPrefix(Some(prefix.into()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_deref().unwrap_or("#")
+    }
+
+    /// `id` as shown to people, e.g. `#12` or `T-12`.
+    pub fn show(&self, id: impl Into<TicketId>) -> String {
+        format!("{}{}", self.as_str(), id.into().0)
+    }
+
+    /// `id` in text export and graphs. These keep `T-12` unless a prefix
+    /// is configured, since a `#12` there reads like the `#label` suffix.
+    pub fn show_export(&self, id: impl Into<TicketId>) -> String {
+        format!("{}{}", self.0.as_deref().unwrap_or("T-"), id.into().0)
+    }
+
+    /// Accepts the prefix followed by the number, `#12` or a bare `12`.
+    pub fn parse(&self, raw: &str) -> Result<TicketId, std::num::ParseIntError> {
+        raw.strip_prefix(self.as_str())
+            .or_else(|| raw.strip_prefix('#'))
+            .unwrap_or(raw)
+            .parse()
+            .map(TicketId)
+    }
+}
+
+impl PartialEq<i64> for TicketId {
+    fn eq(&self, other: &i64) -> bool {
+        self.0 == *other
+    }
+}
@@ -0,0 +1,425 @@
+//! Named ticket operations with JSON arguments and JSON results, shared by
+//! the MCP server and the HTTP API so both front ends behave the same. The
+//! result shapes match `--json`.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::model::{IdPrefix, TicketId};
+use crate::store::{Store, TicketPatch};
+use crate::ticket::{self, AppError, ListFilter};
+
+/// Why an operation produced no result.
+#[derive(Debug)]
+pub enum OpError {
+    /// There is no operation with that name.
+    Unknown,
+    /// The arguments did not fit the operation.
+    Params(String),
+    /// The operation itself failed.
+    App(AppError),
+}
+
+impl From<AppError> for OpError {
+    fn from(e: AppError) -> Self {
+        OpError::App(e)
+    }
+}
+
+fn params<T: for<'de> Deserialize<'de>>(args: Value) -> Result<T, OpError> {
+    serde_json::from_value(args).map_err(|e| OpError::Params(e.to_string()))
+}
+
+/// Run `f` with `agent` acting in place of the store's own, when given.
+fn acting_as<T>(
+    store: &mut Store,
+    agent: Option<String>,
+    f: impl FnOnce(&mut Store) -> Result<T, OpError>,
+) -> Result<T, OpError> {
+    let Some(agent) = agent else {
+        return f(store);
+    };
+    let previous = store.set_agent(Some(agent));
+    let result = f(store);
+    store.set_agent(previous);
+    result
+}
+
+fn export(store: &Store, id: i64) -> Result<Value, AppError> {
+    Ok(serde_json::to_value(store.export(id)?).expect("serialize"))
+}
+
+fn with_warnings(store: &Store, id: i64, warnings: Vec<String>) -> Result<Value, AppError> {
+    let mut v = export(store, id)?;
+    v["warnings"] = json!(warnings);
+    Ok(v)
+}
+
+/// Run the operation `name` with `args`. `agent`, when given, acts in place
+/// of the store's agent; arguments that name an agent override both.
+pub fn call(
+    store: &mut Store,
+    agent: Option<&str>,
+    name: &str,
+    args: Value,
+) -> Result<Value, OpError> {
+    acting_as(store, agent.map(String::from), |store| {
+        run(store, name, args)
+    })
+}
+
+fn run(store: &mut Store, name: &str, args: Value) -> Result<Value, OpError> {
+    let value = match name {
+        "create_ticket" => {
+            let a: CreateParams = params(args)?;
+            let id = store.create(&ticket::NewTicket {
+                name: &a.name,
+                description: &a.description,
+                priority: a.priority,
+                labels: &a.labels,
+                parent: a.parent,
+            })?;
+            export(store, id.get())?
+        }
+        "get_ticket" => {
+            let a: GetParams = params(args)?;
+            let mut v = export(store, a.id)?;
+            if let (Some(n), Some(notes)) = (a.notes, v["notes"].as_array_mut()) {
+                let older = notes.len().saturating_sub(n);
+                notes.drain(..older);
+            }
+            v
+        }
+        "update_ticket" => {
+            let a: UpdateParams = params(args)?;
+            let patch = TicketPatch {
+                name: a.name,
+                description: a.description,
+                status: a.status.as_deref().map(str::parse).transpose()?,
+                priority: a.priority,
+                add_labels: a.add_labels,
+                remove_labels: a.remove_labels,
+            };
+            store.update(a.id, &patch)?;
+            export(store, a.id)?
+        }
+        "delete_ticket" => {
+            let a: DeleteParams = params(args)?;
+            let t = store.delete(a.id, a.children)?;
+            json!({ "id": t.id, "name": t.name, "deleted": true })
+        }
+        "claim_ticket" => {
+            let a: ClaimParams = params(args)?;
+            let lease = a.lease_secs.unwrap_or(store.config().claim_lease());
+            acting_as(store, a.agent, |store| {
+                Ok(match a.id {
+                    Some(id) => {
+                        let warnings = store.claim(id, lease, a.force)?;
+                        with_warnings(store, id, warnings)?
+                    }
+                    None => {
+                        let t = store.claim_next(lease)?.ok_or(AppError::NoReadyTicket)?;
+                        with_warnings(store, t.id, Vec::new())?
+                    }
+                })
+            })?
+        }
+        "heartbeat_ticket" => {
+            let a: HeartbeatParams = params(args)?;
+            let lease = a.lease_secs.unwrap_or(store.config().heartbeat_lease());
+            acting_as(store, a.agent, |store| {
+                store.heartbeat(a.id, lease)?;
+                Ok(export(store, a.id)?)
+            })?
+        }
+        "release_ticket" => {
+            let a: ReleaseParams = params(args)?;
+            acting_as(store, a.agent, |store| {
+                let warnings = store.release(a.id, a.force)?;
+                Ok(with_warnings(store, a.id, warnings)?)
+            })?
+        }
+        "block_ticket" => {
+            let a: BlockParams = params(args)?;
+            if a.reason.trim().is_empty() {
+                return Err(AppError::BlockReasonRequired.into());
+            }
+            store.block(a.id, &a.reason)?;
+            export(store, a.id)?
+        }
+        "add_note" => {
+            let a: NoteParams = params(args)?;
+            acting_as(store, a.author, |store| {
+                store.add_note(a.id, &a.body)?;
+                Ok(export(store, a.id)?)
+            })?
+        }
+        "add_dependency" | "remove_dependency" => {
+            let a: DepParams = params(args)?;
+            let action = if name == "add_dependency" {
+                store.add_dep(a.ticket_id, a.depends_on)?;
+                "added"
+            } else {
+                store.remove_dep(a.ticket_id, a.depends_on)?;
+                "removed"
+            };
+            let deps = store.deps(a.ticket_id)?;
+            json!({
+                "ticket_id": a.ticket_id,
+                "depends_on": a.depends_on,
+                "action": action,
+                "dependencies": deps.forward,
+            })
+        }
+        "list_tickets" => {
+            let a: FilterParams = params(args)?;
+            let page = store.export_page(&a.to_filter(store.ids())?)?;
+            let tickets: Vec<Value> = page
+                .tickets
+                .iter()
+                .map(|t| {
+                    json!({
+                        "id": t.id,
+                        "name": t.name,
+                        "status": t.status,
+                        "priority": t.priority,
+                        "parent_id": t.parent_id,
+                        "claimed_by": t.claimed_by,
+                        "labels": t.labels,
+                        "ready": t.ready,
+                        "waiting": t.waiting,
+                        "blocking": t.blocking,
+                    })
+                })
+                .collect();
+            json!({ "tickets": tickets, "next_cursor": page.next_cursor })
+        }
+        "export_tickets" => {
+            let a: ExportParams = params(args)?;
+            let page = store.export_page(&a.filter.to_filter(store.ids())?)?;
+            match (a.format.as_str(), a.budget) {
+                ("json", None) => serde_json::to_value(&page).expect("serialize"),
+                ("json" | "markdown", Some(_)) => {
+                    return Err(OpError::Params(
+                        "budget only applies to the text format".into(),
+                    ))
+                }
+                ("text", Some(tokens)) => {
+                    let max = crate::budget::Budget::Tokens(tokens).chars();
+                    Value::String(crate::budget::render_budgeted(
+                        &page.tickets,
+                        store.agent(),
+                        max,
+                        &store.workflow()?,
+                        store.ids(),
+                    ))
+                }
+                ("text", None) => Value::String(
+                    page.tickets
+                        .iter()
+                        .map(|e| ticket::format_export_text_with(e, store.ids()) + "\n")
+                        .collect(),
+                ),
+                ("markdown", None) => Value::String(crate::markdown::render_markdown(
+                    &page.tickets,
+                    crate::markdown::Grouping::Status,
+                    &store.workflow()?,
+                    store.ids(),
+                )),
+                (other, _) => {
+                    return Err(OpError::Params(format!(
+                        "invalid format '{}': must be json, text or markdown",
+                        other
+                    )))
+                }
+            }
+        }
+        "search_tickets" => {
+            let a: SearchParams = params(args)?;
+            let hits = store.search(&a.query, a.limit.unwrap_or(20))?;
+            serde_json::to_value(hits).expect("serialize")
+        }
+        "list_labels" => {
+            let labels: Vec<Value> = store
+                .labels()?
+                .into_iter()
+                .map(|(label, count)| json!({ "label": label, "count": count }))
+                .collect();
+            Value::Array(labels)
+        }
+        "plan" => {
+            let a: PlanParams = params(args)?;
+            serde_json::to_value(store.plan(a.to.map(TicketId))?).expect("serialize")
+        }
+        "reap" => serde_json::to_value(store.reap()?).expect("serialize"),
+        "list_events" => {
+            let a: EventParams = params(args)?;
+            let events = store.events_after(a.after.unwrap_or(0), a.ticket_id.map(TicketId))?;
+            let cursor = events.last().map_or(a.after.unwrap_or(0), |e| e.id);
+            json!({ "events": events, "cursor": cursor })
+        }
+        _ => return Err(OpError::Unknown),
+    };
+    Ok(value)
+}
+
+#[derive(Deserialize)]
+struct CreateParams {
+    name: String,
+    #[serde(default)]
+    description: String,
+    priority: Option<i64>,
+    #[serde(default)]
+    labels: Vec<String>,
+    parent: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct GetParams {
+    id: i64,
+    notes: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct UpdateParams {
+    id: i64,
+    name: Option<String>,
+    description: Option<String>,
+    status: Option<String>,
+    priority: Option<i64>,
+    #[serde(default)]
+    add_labels: Vec<String>,
+    #[serde(default)]
+    remove_labels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct DeleteParams {
+    id: i64,
+    #[serde(default)]
+    children: ticket::ChildPolicy,
+}
+
+#[derive(Deserialize)]
+struct ClaimParams {
+    id: Option<i64>,
+    agent: Option<String>,
+    #[serde(default)]
+    force: bool,
+    lease_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct HeartbeatParams {
+    id: i64,
+    agent: Option<String>,
+    lease_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseParams {
+    id: i64,
+    agent: Option<String>,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+struct BlockParams {
+    id: i64,
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct NoteParams {
+    id: i64,
+    body: String,
+    author: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DepParams {
+    ticket_id: i64,
+    depends_on: i64,
+}
+
+#[derive(Deserialize, Default)]
+struct FilterParams {
+    status: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    claimer: Option<String>,
+    #[serde(default)]
+    ready: bool,
+    parent: Option<i64>,
+    query: Option<String>,
+    #[serde(default)]
+    sort: Vec<String>,
+    limit: Option<usize>,
+    after: Option<i64>,
+}
+
+impl FilterParams {
+    fn to_filter(&self, ids: &IdPrefix) -> Result<ListFilter, OpError> {
+        let status = self
+            .status
+            .as_deref()
+            .map(crate::cli::parse_status)
+            .transpose()
+            .map_err(OpError::Params)?;
+        let query = self
+            .query
+            .as_deref()
+            .map(|q| crate::query::parse_with(q, ids))
+            .transpose()
+            .map_err(AppError::from)?;
+        let sort = self
+            .sort
+            .iter()
+            .map(|s| crate::cli::parse_sort_key(s))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(OpError::Params)?;
+        Ok(ListFilter {
+            status,
+            labels: self.labels.iter().map(|l| vec![l.clone()]).collect(),
+            claimer: self.claimer.clone(),
+            ready: self.ready,
+            parent: self.parent,
+            query,
+            sort,
+            limit: self.limit,
+            after: self.after,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    #[serde(flatten)]
+    filter: FilterParams,
+    #[serde(default = "default_export_format")]
+    format: String,
+    budget: Option<usize>,
+}
+
+fn default_export_format() -> String {
+    "json".to_string()
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct PlanParams {
+    to: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct EventParams {
+    after: Option<i64>,
+    ticket_id: Option<i64>,
+}
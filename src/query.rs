@@ -0,0 +1,616 @@
+//! The `--query` expression language for `list` and `export`.
+//!
+//! ```text
+//! status:(todo,blocked) AND claimer:bot-* AND updated>2026-10-01 AND NOT text:"flaky"
+//! ```
+//!
+//! Terms are `field:value`, `field:(a,b)` (any of) or `field<op>value` with
+//! `<`, `<=`, `>`, `>=`, `=` for ordered fields. Terms combine with `AND`
+//! (also implied by juxtaposition), `OR`, `NOT` and parentheses. A bare word
+//! or quoted string is shorthand for `text:`.
+
+use rusqlite::types::ToSql;
+use thiserror::Error;
+
+use crate::model::{IdPrefix, Status};
+use crate::ticket;
+
+const FIELDS: &str =
+    "status, claimer, label, text, name, priority, id, parent, created, updated, is";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("column {column}: {message}")]
+pub struct QueryError {
+    /// 1-based character offset into the query string.
+    pub column: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        QueryError {
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// Multi-line message with the query and a caret under the failing column.
+    pub fn render(&self, input: &str) -> String {
+        format!(
+            "{} at column {}\n  {}\n  {}^",
+            self.message,
+            self.column,
+            input,
+            " ".repeat(self.column.saturating_sub(1))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Pred(Predicate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Ready,
+    Waiting,
+    Blocking,
+    Claimed,
+    Unclaimed,
+}
+
+/// A single field test. List-valued variants match any of their values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Status(Vec<Status>),
+    /// Glob patterns (`*`, `?`) over the claiming agent.
+    Claimer(Vec<String>),
+    Label(Vec<String>),
+    /// Case-insensitive substring of name or description.
+    Text(Vec<String>),
+    /// Case-insensitive substring of name.
+    Name(Vec<String>),
+    Priority(CmpOp, Vec<i64>),
+    Id(CmpOp, Vec<i64>),
+    /// `None` matches top-level tickets.
+    Parent(Option<i64>),
+    /// Timestamp or prefix such as `2026-10`; compared as the whole range it covers.
+    Created(CmpOp, String),
+    Updated(CmpOp, String),
+    Is(State),
+}
+
+/// Parse with ids written as `#12` or `12`.
+pub fn parse(input: &str) -> Result<Expr, QueryError> {
+    parse_with(input, &IdPrefix::default())
+}
+
+/// Parse, also accepting ids written with `ids`' prefix.
+pub fn parse_with(input: &str, ids: &IdPrefix) -> Result<Expr, QueryError> {
+    let tokens = lex(input)?;
+    let end = input.chars().count() + 1;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end,
+        ids,
+    };
+    let expr = parser.parse_or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(t) => Err(QueryError::new(t.col, "unexpected token")),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Word(String),
+    Quoted(String),
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug)]
+struct Token {
+    tok: Tok,
+    col: usize,
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i + 1;
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' | ')' | ',' => {
+                let tok = match c {
+                    '(' => Tok::LParen,
+                    ')' => Tok::RParen,
+                    _ => Tok::Comma,
+                };
+                tokens.push(Token { tok, col });
+                i += 1;
+            }
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(QueryError::new(col, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'"') => {
+                            value.push('"');
+                            i += 2;
+                        }
+                        Some(&ch) => {
+                            value.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push(Token {
+                    tok: Tok::Quoted(value),
+                    col,
+                });
+            }
+            _ => {
+                let start = i;
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !matches!(chars[i], '(' | ')' | ',' | '"')
+                {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    tok: Tok::Word(chars[start..i].iter().collect()),
+                    col,
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Column reported for errors at end of input.
+    end: usize,
+    ids: &'a IdPrefix,
+}
+
+/// A literal value and the column it started at.
+struct Value {
+    text: String,
+    col: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let t = self.tokens.get(self.pos);
+        self.pos += 1;
+        t
+    }
+
+    fn peek_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token { tok: Tok::Word(w), .. }) if w == kw)
+    }
+
+    fn here(&self) -> usize {
+        self.peek().map(|t| t.col).unwrap_or(self.end)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.peek_keyword("OR") {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            if self.peek_keyword("AND") {
+                self.pos += 1;
+            } else if self.peek_keyword("OR")
+                || matches!(
+                    self.peek(),
+                    None | Some(Token {
+                        tok: Tok::RParen | Tok::Comma,
+                        ..
+                    })
+                )
+            {
+                break;
+            }
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        if self.peek_keyword("NOT") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        let col = self.here();
+        match self.next().map(|t| t.tok.clone()) {
+            Some(Tok::LParen) => {
+                let inner = self.parse_or()?;
+                self.expect_rparen()?;
+                Ok(inner)
+            }
+            Some(Tok::Quoted(s)) => Ok(Expr::Pred(Predicate::Text(vec![s]))),
+            Some(Tok::Word(w)) if w == "AND" || w == "OR" => Err(QueryError::new(
+                col,
+                format!("expected a term before '{}'", w),
+            )),
+            Some(Tok::Word(w)) => self.parse_term(&w, col),
+            Some(_) => Err(QueryError::new(col, "expected a term")),
+            None => Err(QueryError::new(col, "unexpected end of query")),
+        }
+    }
+
+    fn expect_rparen(&mut self) -> Result<(), QueryError> {
+        let col = self.here();
+        match self.next() {
+            Some(Token {
+                tok: Tok::RParen, ..
+            }) => Ok(()),
+            _ => Err(QueryError::new(col, "expected ')'")),
+        }
+    }
+
+    fn parse_term(&mut self, word: &str, col: usize) -> Result<Expr, QueryError> {
+        let Some(split) = word.find([':', '<', '>', '=']) else {
+            return Ok(Expr::Pred(Predicate::Text(vec![word.to_string()])));
+        };
+        if split == 0 {
+            return Err(QueryError::new(col, "missing field name"));
+        }
+        let field = &word[..split];
+        let rest = &word[split..];
+        let (op, op_len) = if rest.starts_with(">=") {
+            (CmpOp::Ge, 2)
+        } else if rest.starts_with("<=") {
+            (CmpOp::Le, 2)
+        } else if rest.starts_with('>') {
+            (CmpOp::Gt, 1)
+        } else if rest.starts_with('<') {
+            (CmpOp::Lt, 1)
+        } else {
+            (CmpOp::Eq, 1)
+        };
+        let op_col = col + field.chars().count();
+        let value_col = op_col + op_len;
+        let inline = &rest[op_len..];
+        let values = if inline.is_empty() {
+            self.parse_values(field)?
+        } else {
+            vec![Value {
+                text: inline.to_string(),
+                col: value_col,
+            }]
+        };
+        if op != CmpOp::Eq && values.len() > 1 {
+            return Err(QueryError::new(
+                op_col,
+                "a list of values is only valid with ':'",
+            ));
+        }
+        build_predicate(field, col, op, op_col, values, self.ids).map(Expr::Pred)
+    }
+
+    fn parse_values(&mut self, field: &str) -> Result<Vec<Value>, QueryError> {
+        let col = self.here();
+        match self.next().map(|t| t.tok.clone()) {
+            Some(Tok::Word(w)) => Ok(vec![Value { text: w, col }]),
+            Some(Tok::Quoted(s)) => Ok(vec![Value { text: s, col }]),
+            Some(Tok::LParen) => {
+                let mut values = Vec::new();
+                loop {
+                    let col = self.here();
+                    match self.next().map(|t| t.tok.clone()) {
+                        Some(Tok::Word(w)) => values.push(Value { text: w, col }),
+                        Some(Tok::Quoted(s)) => values.push(Value { text: s, col }),
+                        _ => return Err(QueryError::new(col, "expected a value")),
+                    }
+                    let col = self.here();
+                    match self.next().map(|t| t.tok.clone()) {
+                        Some(Tok::Comma) => continue,
+                        Some(Tok::RParen) => break,
+                        _ => return Err(QueryError::new(col, "expected ',' or ')'")),
+                    }
+                }
+                Ok(values)
+            }
+            _ => Err(QueryError::new(
+                col,
+                format!("expected a value after '{}'", field),
+            )),
+        }
+    }
+}
+
+fn build_predicate(
+    field: &str,
+    field_col: usize,
+    op: CmpOp,
+    op_col: usize,
+    values: Vec<Value>,
+    ids: &IdPrefix,
+) -> Result<Predicate, QueryError> {
+    let field_lc = field.to_lowercase();
+    let ordered = matches!(field_lc.as_str(), "priority" | "id" | "created" | "updated");
+    if op != CmpOp::Eq && !ordered {
+        return Err(QueryError::new(
+            op_col,
+            format!(
+                "'{}' only supports ':'; comparisons work on priority, id, created, updated",
+                field
+            ),
+        ));
+    }
+    let texts = |values: Vec<Value>| values.into_iter().map(|v| v.text).collect::<Vec<_>>();
+    let single = |values: &[Value]| -> Result<(), QueryError> {
+        match values.get(1) {
+            Some(v) => Err(QueryError::new(
+                v.col,
+                format!("'{}' takes a single value", field),
+            )),
+            None => Ok(()),
+        }
+    };
+    Ok(match field_lc.as_str() {
+        "status" => Predicate::Status(
+            values
+                .iter()
+                .map(|v| {
+                    v.text
+                        .parse::<Status>()
+                        .map_err(|e| QueryError::new(v.col, e.to_string()))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        "claimer" => Predicate::Claimer(texts(values)),
+        "label" => Predicate::Label(texts(values)),
+        "text" => Predicate::Text(texts(values)),
+        "name" => Predicate::Name(texts(values)),
+        "priority" => Predicate::Priority(
+            op,
+            values
+                .iter()
+                .map(|v| crate::cli::parse_priority(&v.text).map_err(|e| QueryError::new(v.col, e)))
+                .collect::<Result<_, _>>()?,
+        ),
+        "id" => Predicate::Id(
+            op,
+            values
+                .iter()
+                .map(|v| parse_id(v, ids))
+                .collect::<Result<_, _>>()?,
+        ),
+        "parent" => {
+            single(&values)?;
+            let v = &values[0];
+            if v.text == "none" {
+                Predicate::Parent(None)
+            } else {
+                Predicate::Parent(Some(parse_id(v, ids)?))
+            }
+        }
+        "created" | "updated" => {
+            single(&values)?;
+            let v = &values[0];
+            let at = crate::cli::parse_since(&v.text).map_err(|e| QueryError::new(v.col, e))?;
+            if field_lc == "created" {
+                Predicate::Created(op, at)
+            } else {
+                Predicate::Updated(op, at)
+            }
+        }
+        "is" => {
+            single(&values)?;
+            let v = &values[0];
+            Predicate::Is(match v.text.as_str() {
+                "ready" => State::Ready,
+                "waiting" => State::Waiting,
+                "blocking" => State::Blocking,
+                "claimed" => State::Claimed,
+                "unclaimed" => State::Unclaimed,
+                other => {
+                    return Err(QueryError::new(
+                        v.col,
+                        format!(
+                            "invalid state '{}': must be one of ready, waiting, blocking, claimed, unclaimed",
+                            other
+                        ),
+                    ))
+                }
+            })
+        }
+        _ => {
+            return Err(QueryError::new(
+                field_col,
+                format!("unknown field '{}': expected one of {}", field, FIELDS),
+            ))
+        }
+    })
+}
+
+fn parse_id(v: &Value, ids: &IdPrefix) -> Result<i64, QueryError> {
+    ids.parse(&v.text)
+        .map(|id| id.get())
+        .map_err(|_| QueryError::new(v.col, format!("invalid ticket id '{}'", v.text)))
+}
+
+/// Expand a timestamp prefix (`2026-10`, `2026-10-01T12`) to the first and
+/// last full timestamps it covers.
+fn timestamp_bounds(prefix: &str) -> (String, String) {
+    const LOW: &str = "0000-01-01T00:00:00Z";
+    const HIGH: &str = "9999-12-31T23:59:59Z";
+    let pad = |template: &str| match template.get(prefix.len()..) {
+        Some(tail) => format!("{}{}", prefix, tail),
+        None => prefix.to_string(),
+    };
+    (pad(LOW), pad(HIGH))
+}
+
+impl Expr {
+    /// Every status the expression mentions, for checking against the
+    /// workflow before the query runs.
+    pub fn statuses(&self) -> Vec<&Status> {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                let mut out = a.statuses();
+                out.extend(b.statuses());
+                out
+            }
+            Expr::Not(a) => a.statuses(),
+            Expr::Pred(Predicate::Status(values)) => values.iter().collect(),
+            Expr::Pred(_) => Vec::new(),
+        }
+    }
+
+    /// Compile to a SQL condition over `tickets`, appending bound values to
+    /// `params` in placeholder order.
+    pub(crate) fn to_sql(&self, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        match self {
+            Expr::And(a, b) => format!("({} AND {})", a.to_sql(params), b.to_sql(params)),
+            Expr::Or(a, b) => format!("({} OR {})", a.to_sql(params), b.to_sql(params)),
+            Expr::Not(a) => format!("NOT {}", a.to_sql(params)),
+            Expr::Pred(p) => p.to_sql(params),
+        }
+    }
+}
+
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
+
+fn cmp_sql(op: CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => "=",
+        CmpOp::Lt => "<",
+        CmpOp::Le => "<=",
+        CmpOp::Gt => ">",
+        CmpOp::Ge => ">=",
+    }
+}
+
+fn ordered_sql(
+    column: &str,
+    op: CmpOp,
+    values: &[i64],
+    params: &mut Vec<Box<dyn ToSql>>,
+) -> String {
+    for v in values {
+        params.push(Box::new(*v));
+    }
+    if op == CmpOp::Eq {
+        format!("{} IN ({})", column, placeholders(values.len()))
+    } else {
+        format!("{} {} ?", column, cmp_sql(op))
+    }
+}
+
+fn time_sql(column: &str, op: CmpOp, at: &str, params: &mut Vec<Box<dyn ToSql>>) -> String {
+    let (low, high) = timestamp_bounds(at);
+    match op {
+        CmpOp::Eq => {
+            params.push(Box::new(low));
+            params.push(Box::new(high));
+            return format!("{} BETWEEN ? AND ?", column);
+        }
+        CmpOp::Lt | CmpOp::Ge => params.push(Box::new(low)),
+        CmpOp::Le | CmpOp::Gt => params.push(Box::new(high)),
+    }
+    format!("{} {} ?", column, cmp_sql(op))
+}
+
+impl Predicate {
+    fn to_sql(&self, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        match self {
+            Predicate::Status(values) => {
+                for v in values {
+                    params.push(Box::new(v.clone()));
+                }
+                format!("tickets.status IN ({})", placeholders(values.len()))
+            }
+            // IS NOT NULL keeps NOT claimer:x true for unclaimed tickets.
+            Predicate::Claimer(patterns) => {
+                for p in patterns {
+                    params.push(Box::new(p.clone()));
+                }
+                format!(
+                    "(tickets.claimed_by IS NOT NULL AND ({}))",
+                    vec!["tickets.claimed_by GLOB ?"; patterns.len()].join(" OR ")
+                )
+            }
+            Predicate::Label(labels) => {
+                for l in labels {
+                    params.push(Box::new(ticket::normalize_label(l)));
+                }
+                format!(
+                    "tickets.id IN (SELECT ticket_id FROM ticket_labels WHERE label IN ({}))",
+                    placeholders(labels.len())
+                )
+            }
+            Predicate::Text(terms) => {
+                let mut parts = Vec::new();
+                for term in terms {
+                    let pattern = format!("%{}%", term.to_lowercase());
+                    params.push(Box::new(pattern.clone()));
+                    params.push(Box::new(pattern));
+                    parts.push("LOWER(tickets.name) LIKE ? OR LOWER(tickets.description) LIKE ?");
+                }
+                format!("({})", parts.join(" OR "))
+            }
+            Predicate::Name(terms) => {
+                for term in terms {
+                    params.push(Box::new(format!("%{}%", term.to_lowercase())));
+                }
+                format!(
+                    "({})",
+                    vec!["LOWER(tickets.name) LIKE ?"; terms.len()].join(" OR ")
+                )
+            }
+            Predicate::Priority(op, values) => ordered_sql("tickets.priority", *op, values, params),
+            Predicate::Id(op, values) => ordered_sql("tickets.id", *op, values, params),
+            Predicate::Parent(None) => "tickets.parent_id IS NULL".to_string(),
+            Predicate::Parent(Some(id)) => {
+                params.push(Box::new(*id));
+                "(tickets.parent_id IS NOT NULL AND tickets.parent_id = ?)".to_string()
+            }
+            Predicate::Created(op, at) => time_sql("tickets.created_at", *op, at, params),
+            Predicate::Updated(op, at) => time_sql("tickets.updated_at", *op, at, params),
+            Predicate::Is(state) => ticket::state_sql(*state, params),
+        }
+    }
+}
@@ -0,0 +1,467 @@
+//! `rtik serve`: a small HTTP/JSON API over the operations in `ops`, for
+//! tools that would rather not shell out. It only listens on loopback
+//! addresses or a unix socket; there is no authentication. Over TCP a
+//! request must name a loopback host, and a body must be sent as
+//! `application/json`, so a web page cannot reach the API through DNS
+//! rebinding or a plain form post.
+//!
+//! Errors come back as `{"code": ..., "message": ...}` with the same codes
+//! as `--json`, and an HTTP status from `AppError::http_status`.
+
+use serde_json::{json, Map, Value};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ops::{self, OpError};
+use crate::store::Store;
+use crate::ticket::AppError;
+
+/// Longest a `GET /events?wait=` request is held open, in seconds.
+pub const MAX_WAIT_SECS: u64 = 60;
+
+/// Largest request body read, in bytes; anything longer gets a 413.
+pub const MAX_BODY: u64 = 1 << 20;
+
+/// How often a held `/events` request checks for new events.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Header naming the agent for claims and notes when the body does not.
+const AGENT_HEADER: &str = "X-Rtik-Agent";
+
+/// Where to listen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Listen {
+    /// `host:port` on a loopback address, or a unix socket path (anything
+    /// containing `/`).
+    pub fn parse(raw: &str) -> Result<Listen, AppError> {
+        if raw.contains('/') {
+            return Ok(Listen::Unix(PathBuf::from(raw)));
+        }
+        let addrs: Vec<SocketAddr> = raw
+            .to_socket_addrs()
+            .map_err(|e| AppError::Usage(format!("invalid listen address '{}': {}", raw, e)))?
+            .collect();
+        match addrs.first() {
+            Some(addr) if addrs.iter().all(|a| a.ip().is_loopback()) => Ok(Listen::Tcp(*addr)),
+            _ => Err(AppError::Usage(format!(
+                "refusing to listen on '{}': only loopback addresses and unix sockets are allowed",
+                raw
+            ))),
+        }
+    }
+}
+
+/// A bound server, ready to `run`.
+pub struct Server {
+    http: tiny_http::Server,
+    store: Arc<Mutex<Store>>,
+}
+
+impl Server {
+    /// Bind to `listen`. The store's agent is the default for requests that
+    /// name none. A TCP address is not checked for loopback here (that is
+    /// `Listen::parse`'s job), but requests must still name a loopback host
+    /// or the bound address, so a server bound to `0.0.0.0` only answers
+    /// clients that connect through loopback.
+    pub fn bind(store: Store, listen: &Listen) -> Result<Server, AppError> {
+        let bound = match listen {
+            Listen::Tcp(addr) => tiny_http::Server::http(addr),
+            #[cfg(unix)]
+            Listen::Unix(path) => {
+                remove_stale_socket(path);
+                tiny_http::Server::http_unix(path)
+            }
+            #[cfg(not(unix))]
+            Listen::Unix(_) => {
+                return Err(AppError::Usage(
+                    "unix sockets are not supported on this platform".into(),
+                ))
+            }
+        };
+        let http = bound.map_err(|e| AppError::Listen(describe(listen), e.to_string()))?;
+        Ok(Server {
+            http,
+            store: Arc::new(Mutex::new(store)),
+        })
+    }
+
+    /// Where the server is listening, e.g. `http://127.0.0.1:7777`. With
+    /// port 0 this reports the port actually chosen.
+    pub fn url(&self) -> String {
+        match self.http.server_addr() {
+            tiny_http::ListenAddr::IP(addr) => format!("http://{}", addr),
+            #[cfg(unix)]
+            tiny_http::ListenAddr::Unix(addr) => match addr.as_pathname() {
+                Some(path) => format!("unix:{}", path.display()),
+                None => "unix:".to_string(),
+            },
+        }
+    }
+
+    /// Serve requests until the process is stopped. Each request gets its
+    /// own thread so a held `/events` request does not stall the others;
+    /// the store is shared and used by one request at a time.
+    pub fn run(self) {
+        let bound = match self.http.server_addr() {
+            tiny_http::ListenAddr::IP(addr) => Some(addr.ip()),
+            #[cfg(unix)]
+            tiny_http::ListenAddr::Unix(_) => None,
+        };
+        for request in self.http.incoming_requests() {
+            let store = Arc::clone(&self.store);
+            std::thread::spawn(move || handle(request, &store, bound));
+        }
+    }
+}
+
+fn describe(listen: &Listen) -> String {
+    match listen {
+        Listen::Tcp(addr) => addr.to_string(),
+        Listen::Unix(path) => path.display().to_string(),
+    }
+}
+
+/// A socket file left behind by a server that did not shut down cleanly
+/// would make the bind fail. Only sockets are removed, never other files.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) {
+    use std::os::unix::fs::FileTypeExt;
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// A request-level failure: anything that is not an `AppError` from the
+/// operation itself.
+struct HttpError {
+    status: u16,
+    code: &'static str,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, code: &'static str, message: impl Into<String>) -> Self {
+        HttpError {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<OpError> for HttpError {
+    fn from(e: OpError) -> Self {
+        match e {
+            OpError::Unknown => HttpError::new(404, "unknown_route", "no such operation"),
+            OpError::Params(msg) => HttpError::new(400, "invalid_params", msg),
+            OpError::App(e) => HttpError::new(e.http_status(), e.code(), e.to_string()),
+        }
+    }
+}
+
+fn header<'a>(request: &'a tiny_http::Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+/// Whether `host` (a `Host` header) names this server: `localhost`, a
+/// loopback address or the address it is bound to, with any port. The
+/// machine's other interface addresses are not known here, so an
+/// unspecified `bound` (`0.0.0.0`) accepts loopback names only.
+fn allowed_host(host: &str, bound: IpAddr) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(""),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback() || ip == bound)
+}
+
+/// Reject requests a browser could have been tricked into sending: over
+/// TCP (`bound` is the listening address) the `Host` must name this server,
+/// and any body must be declared as JSON.
+fn check_headers(request: &tiny_http::Request, bound: Option<IpAddr>) -> Result<(), HttpError> {
+    if let Some(bound) = bound {
+        match header(request, "Host") {
+            Some(host) if allowed_host(host, bound) => {}
+            Some(host) => {
+                return Err(HttpError::new(
+                    403,
+                    "invalid_host",
+                    format!("host '{}' is not this server", host),
+                ))
+            }
+            None => return Err(HttpError::new(403, "invalid_host", "missing Host header")),
+        }
+    }
+    let has_body =
+        request.body_length().unwrap_or(0) > 0 || header(request, "Transfer-Encoding").is_some();
+    if has_body {
+        let json = header(request, "Content-Type").is_some_and(|ct| {
+            ct.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .eq_ignore_ascii_case("application/json")
+        });
+        if !json {
+            return Err(HttpError::new(
+                415,
+                "unsupported_media_type",
+                "request body must be sent as application/json",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn handle(mut request: tiny_http::Request, store: &Mutex<Store>, bound: Option<IpAddr>) {
+    let agent = header(&request, AGENT_HEADER).map(str::to_string);
+    let result = check_headers(&request, bound)
+        .and_then(|()| respond(&mut request, store, agent.as_deref()));
+    let (status, body) = match result {
+        Ok((status, value)) => (status, value),
+        Err(e) => (e.status, json!({ "code": e.code, "message": e.message })),
+    };
+    let (text, content_type) = match body {
+        Value::String(s) => (s, "text/plain; charset=utf-8"),
+        other => (other.to_string() + "\n", "application/json"),
+    };
+    let header = tiny_http::Header::from_bytes("Content-Type", content_type).expect("valid header");
+    let response = tiny_http::Response::from_string(text)
+        .with_status_code(status)
+        .with_header(header);
+    // The client may have gone away; there is no one left to tell.
+    let _ = request.respond(response);
+}
+
+fn respond(
+    request: &mut tiny_http::Request,
+    store: &Mutex<Store>,
+    agent: Option<&str>,
+) -> Result<(u16, Value), HttpError> {
+    let method = request.method().as_str().to_ascii_uppercase();
+    let (path, query) = match request.url().split_once('?') {
+        Some((p, q)) => (p.to_string(), parse_query(q)?),
+        None => (request.url().to_string(), Map::new()),
+    };
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| HttpError::new(400, "invalid_body", e.to_string()))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(HttpError::new(
+            413,
+            "body_too_large",
+            format!("request body is over {} bytes", MAX_BODY),
+        ));
+    }
+    let body = || -> Result<Map<String, Value>, HttpError> {
+        if body.trim().is_empty() {
+            return Ok(Map::new());
+        }
+        match serde_json::from_str(&body) {
+            Ok(Value::Object(map)) => Ok(map),
+            Ok(_) => Err(HttpError::new(
+                400,
+                "invalid_body",
+                "body must be a JSON object",
+            )),
+            Err(e) => Err(HttpError::new(400, "invalid_body", e.to_string())),
+        }
+    };
+    let id = |raw: &str| -> Result<Value, HttpError> {
+        raw.parse::<i64>().map(Value::from).map_err(|_| {
+            HttpError::new(
+                400,
+                "invalid_params",
+                format!("invalid ticket id '{}'", raw),
+            )
+        })
+    };
+    let with = |mut args: Map<String, Value>, key: &str, value: Value| {
+        args.insert(key.to_string(), value);
+        Value::Object(args)
+    };
+
+    let (op, args, created) = match (method.as_str(), segments.as_slice()) {
+        ("GET", ["tickets"]) => (
+            "export_tickets",
+            with(query, "format", json!("json")),
+            false,
+        ),
+        ("POST", ["tickets"]) => ("create_ticket", Value::Object(body()?), true),
+        ("POST", ["tickets", "next"]) => ("claim_ticket", Value::Object(body()?), false),
+        ("GET", ["tickets", t]) => ("get_ticket", with(query, "id", id(t)?), false),
+        ("PATCH", ["tickets", t]) => ("update_ticket", with(body()?, "id", id(t)?), false),
+        ("DELETE", ["tickets", t]) => ("delete_ticket", with(query, "id", id(t)?), false),
+        ("POST", ["tickets", t, action]) => {
+            let op = match *action {
+                "claim" => "claim_ticket",
+                "release" => "release_ticket",
+                "heartbeat" => "heartbeat_ticket",
+                "block" => "block_ticket",
+                "notes" => "add_note",
+                "deps" => "add_dependency",
+                _ => return Err(not_found(&path)),
+            };
+            let key = if op == "add_dependency" {
+                "ticket_id"
+            } else {
+                "id"
+            };
+            (op, with(body()?, key, id(t)?), *action == "notes")
+        }
+        ("DELETE", ["tickets", t, "deps", dep]) => (
+            "remove_dependency",
+            json!({ "ticket_id": id(t)?, "depends_on": id(dep)? }),
+            false,
+        ),
+        ("GET", ["export"]) => ("export_tickets", Value::Object(query), false),
+        ("GET", ["search"]) => ("search_tickets", Value::Object(query), false),
+        ("GET", ["labels"]) => ("list_labels", json!({}), false),
+        ("GET", ["plan"]) => ("plan", Value::Object(query), false),
+        ("POST", ["reap"]) => ("reap", json!({}), false),
+        ("GET", ["events"]) => return wait_for_events(store, query).map(|v| (200, v)),
+        (_, [..]) if known_path(&segments) => {
+            return Err(HttpError::new(
+                405,
+                "method_not_allowed",
+                format!("{} is not supported on {}", method, path),
+            ))
+        }
+        _ => return Err(not_found(&path)),
+    };
+
+    let mut store = store.lock().unwrap_or_else(|e| e.into_inner());
+    let value = ops::call(&mut store, agent, op, args)?;
+    Ok((if created { 201 } else { 200 }, value))
+}
+
+fn not_found(path: &str) -> HttpError {
+    HttpError::new(404, "unknown_route", format!("no route for {}", path))
+}
+
+fn known_path(segments: &[&str]) -> bool {
+    matches!(
+        segments,
+        ["tickets"]
+            | ["tickets", _]
+            | ["tickets", _, "deps", _]
+            | ["export"]
+            | ["search"]
+            | ["labels"]
+            | ["plan"]
+            | ["reap"]
+            | ["events"]
+    )
+}
+
+/// `GET /events?after=ID&ticket=ID&wait=SECS`: events newer than `after`.
+/// With `wait`, an empty answer is held back until an event arrives or the
+/// wait runs out, so clients can follow changes without hammering the
+/// server. Changes made by other processes are seen too.
+fn wait_for_events(store: &Mutex<Store>, query: Map<String, Value>) -> Result<Value, HttpError> {
+    let wait = query
+        .get("wait")
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+        .min(MAX_WAIT_SECS);
+    let mut args = Map::new();
+    if let Some(after) = query.get("after") {
+        args.insert("after".into(), after.clone());
+    }
+    if let Some(ticket) = query.get("ticket") {
+        args.insert("ticket_id".into(), ticket.clone());
+    }
+    let deadline = Instant::now() + Duration::from_secs(wait);
+    loop {
+        let result = {
+            let mut store = store.lock().unwrap_or_else(|e| e.into_inner());
+            ops::call(&mut store, None, "list_events", Value::Object(args.clone()))?
+        };
+        let empty = result["events"].as_array().is_none_or(Vec::is_empty);
+        if !empty || Instant::now() >= deadline {
+            return Ok(result);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Parameters that are always strings, even when they look like numbers.
+const STRING_PARAMS: &[&str] = &["query", "status", "claimer", "format"];
+
+/// Turn a query string into JSON arguments. Numbers become numbers, `true`
+/// and `false` become booleans, and `label`/`labels`/`sort` (repeatable or
+/// comma-separated) become arrays; `q` is shorthand for `query`.
+fn parse_query(raw: &str) -> Result<Map<String, Value>, HttpError> {
+    let mut map = Map::new();
+    for pair in raw.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+        let key = percent_decode(key)?;
+        let value = percent_decode(value)?;
+        match key.as_str() {
+            "label" | "labels" | "sort" => {
+                let key = if key == "sort" { "sort" } else { "labels" };
+                let list = map
+                    .entry(key)
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                    .as_array_mut()
+                    .expect("array");
+                list.extend(value.split(',').filter(|v| !v.is_empty()).map(|v| json!(v)));
+            }
+            _ => {
+                let key = if key == "q" { "query".to_string() } else { key };
+                let parsed = match value.as_str() {
+                    _ if STRING_PARAMS.contains(&key.as_str()) => json!(value),
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    v => v.parse::<i64>().map_or_else(|_| json!(v), Value::from),
+                };
+                map.insert(key, parsed);
+            }
+        }
+    }
+    Ok(map)
+}
+
+fn percent_decode(raw: &str) -> Result<String, HttpError> {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex = raw
+                    .get(i + 1..i + 3)
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| {
+                        HttpError::new(400, "invalid_params", "bad percent-encoding in query")
+                    })?;
+                out.push(hex);
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).map_err(|_| HttpError::new(400, "invalid_params", "query is not UTF-8"))
+}
@@ -0,0 +1,446 @@
+//! `Store`: the library API for embedding rtik. It owns the database
+//! connection and the agent acting through it; the CLI, the MCP server and
+//! the HTTP API are all layers over it.
+//!
+//! ```no_run
+//! use rtik::{ListFilter, Status, Store, TicketPatch};
+//!
+//! let mut store = rtik::Store::open(".rtik.db")?.with_agent("planner");
+//! let id = store.create(&rtik::ticket::NewTicket {
+//!     name: "Write the schema",
+//!     ..Default::default()
+//! })?;
+//! store.claim(id, rtik::ticket::DEFAULT_LEASE_SECS, false)?;
+//! store.update(id, &TicketPatch::new().status(Status::Done))?;
+//! let open = store.list(&ListFilter::new().status(Status::Todo).limit(20))?;
+//! # Ok::<(), rtik::AppError>(())
+//! ```
+
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::{self, Config};
+use crate::graph::{self, DepGraph, DepNode, Direction, PlanWave};
+use crate::import::{self, ImportOptions, ImportReport, ImportTicket};
+use crate::model::{IdPrefix, Status, TicketId};
+use crate::ticket::{
+    self, AppError, ChildPolicy, DepInfo, ExportPage, FlaggedPage, ListFilter, NewTicket,
+    ReapedTicket, SearchHit, Ticket, TicketEvent, TicketExport, TicketNote, TicketPage,
+    TicketUpdate, TreeNode,
+};
+use crate::view::{self, View};
+use crate::workflow::Workflow;
+
+/// An open rtik database plus the agent that claims, releases and is
+/// recorded as the actor of every change made through it.
+pub struct Store {
+    conn: Connection,
+    agent: Option<String>,
+    config: Config,
+    ids: IdPrefix,
+}
+
+impl Store {
+    /// Open (creating and migrating if needed) the database at `path`, with
+    /// no agent set.
+    pub fn open(path: impl AsRef<Path>) -> Result<Store, AppError> {
+        let path = path.as_ref();
+        let config = Config::load(&config::path_for_db(path))?;
+        let conn = crate::db::open_connection_with(path, &config)?;
+        Ok(Store {
+            conn,
+            agent: None,
+            ids: config.ids(),
+            config,
+        })
+    }
+
+    /// Open the database the CLI would use (`RTIK_DB`, or the nearest
+    /// `.rtik.db`), acting as `RTIK_AGENT` or the configured agent.
+    pub fn open_default() -> Result<Store, AppError> {
+        let mut store = Store::open(crate::db::resolve_db_path())?;
+        store.agent = store.config.agent();
+        Ok(store)
+    }
+
+    /// Wrap a connection that is already open and migrated, with the
+    /// default config. The default workflow is installed unless the
+    /// connection has one already.
+    pub fn from_connection(conn: Connection) -> Result<Store, AppError> {
+        let installed: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_temp_master WHERE name = 'workflow_statuses')",
+            [],
+            |r| r.get(0),
+        )?;
+        if !installed {
+            Workflow::default().install(&conn)?;
+        }
+        Ok(Store {
+            conn,
+            agent: None,
+            config: Config::default(),
+            ids: IdPrefix::default(),
+        })
+    }
+
+    pub fn with_agent(mut self, agent: impl Into<String>) -> Store {
+        self.agent = Some(agent.into());
+        self
+    }
+
+    /// Change the acting agent, returning the previous one.
+    pub fn set_agent(&mut self, agent: Option<String>) -> Option<String> {
+        std::mem::replace(&mut self.agent, agent)
+    }
+
+    pub fn agent(&self) -> Option<&str> {
+        self.agent.as_deref()
+    }
+
+    /// The `.rtik.toml` settings this store was opened with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// How ticket ids are shown and read: the configured `id_prefix`.
+    pub fn ids(&self) -> &IdPrefix {
+        &self.ids
+    }
+
+    /// The underlying connection, for queries the `Store` does not cover.
+    pub fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn into_connection(self) -> Connection {
+        self.conn
+    }
+
+    fn require_agent(&self) -> Result<String, AppError> {
+        self.agent.clone().ok_or(AppError::AgentNotSet)
+    }
+
+    // ---- Tickets ----
+
+    pub fn create(&self, new: &NewTicket) -> Result<TicketId, AppError> {
+        ticket::create_ticket_with(&self.conn, new, self.agent.as_deref()).map(TicketId)
+    }
+
+    pub fn get(&self, id: impl Into<TicketId>) -> Result<Ticket, AppError> {
+        ticket::get_ticket(&self.conn, id.into().0)
+    }
+
+    /// The ticket with its dependencies, labels, notes and computed states.
+    pub fn export(&self, id: impl Into<TicketId>) -> Result<TicketExport, AppError> {
+        let t = self.get(id)?;
+        let flags = ticket::ticket_flags(&self.conn, t.id)?;
+        ticket::ticket_to_export(&self.conn, t, flags, &ticket::chrono_free_utc_now())
+    }
+
+    /// Apply `patch` and return the updated ticket. Status changes follow
+    /// the workflow's transition rules.
+    pub fn update(&self, id: impl Into<TicketId>, patch: &TicketPatch) -> Result<Ticket, AppError> {
+        let id = id.into().0;
+        ticket::update_ticket_with(&self.conn, id, &patch.as_update(), self.agent.as_deref())?;
+        ticket::get_ticket(&self.conn, id)
+    }
+
+    /// Delete a ticket, returning it as it was.
+    pub fn delete(
+        &self,
+        id: impl Into<TicketId>,
+        children: ChildPolicy,
+    ) -> Result<Ticket, AppError> {
+        let t = self.get(id)?;
+        ticket::delete_ticket_with(&self.conn, t.id, children, self.agent.as_deref())?;
+        Ok(t)
+    }
+
+    pub fn list(&self, filter: &ListFilter) -> Result<TicketPage, AppError> {
+        ticket::list_tickets_page(&self.conn, filter)
+    }
+
+    /// Like `list`, with each ticket's ready/waiting/blocking flags.
+    pub fn list_flagged(&self, filter: &ListFilter) -> Result<FlaggedPage, AppError> {
+        ticket::list_flagged_page(&self.conn, filter)
+    }
+
+    pub fn export_page(&self, filter: &ListFilter) -> Result<ExportPage, AppError> {
+        ticket::export_page(&self.conn, filter)
+    }
+
+    /// Stream matching tickets one at a time instead of collecting a page.
+    pub fn for_each_export(
+        &self,
+        filter: &ListFilter,
+        f: impl FnMut(TicketExport) -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        ticket::for_each_export(&self.conn, filter, f)
+    }
+
+    /// The ticket and its subtasks, recursively.
+    pub fn tree(&self, id: impl Into<TicketId>) -> Result<TreeNode, AppError> {
+        ticket::ticket_tree(&self.conn, id.into().0)
+    }
+
+    // ---- Claims ----
+
+    /// Claim a ticket for `lease_secs`. Returns warnings about unmet
+    /// dependencies and claims taken over.
+    pub fn claim(
+        &mut self,
+        id: impl Into<TicketId>,
+        lease_secs: u64,
+        force: bool,
+    ) -> Result<Vec<String>, AppError> {
+        let agent = self.require_agent()?;
+        let id = id.into().0;
+        ticket::claim_ticket_with_lease(&mut self.conn, id, &agent, force, lease_secs)
+    }
+
+    /// Claim the most urgent ready ticket, or `None` when there is none.
+    pub fn claim_next(&mut self, lease_secs: u64) -> Result<Option<Ticket>, AppError> {
+        let agent = self.require_agent()?;
+        ticket::claim_next(&mut self.conn, &agent, lease_secs)
+    }
+
+    /// Extend this agent's lease. Returns the new expiry.
+    pub fn heartbeat(
+        &mut self,
+        id: impl Into<TicketId>,
+        lease_secs: u64,
+    ) -> Result<String, AppError> {
+        let agent = self.require_agent()?;
+        let id = id.into().0;
+        ticket::heartbeat_ticket(&mut self.conn, id, &agent, lease_secs)
+    }
+
+    pub fn release(
+        &mut self,
+        id: impl Into<TicketId>,
+        force: bool,
+    ) -> Result<Vec<String>, AppError> {
+        let agent = self.require_agent()?;
+        let id = id.into().0;
+        ticket::release_ticket(&mut self.conn, id, &agent, force)
+    }
+
+    /// Clear every expired claim.
+    pub fn reap(&mut self) -> Result<Vec<ReapedTicket>, AppError> {
+        ticket::reap_expired(&mut self.conn, self.agent.as_deref())
+    }
+
+    pub fn block(&self, id: impl Into<TicketId>, reason: &str) -> Result<(), AppError> {
+        let id = id.into().0;
+        ticket::block_ticket(&self.conn, id, reason, self.agent.as_deref()).map(drop)
+    }
+
+    // ---- Notes and labels ----
+
+    /// Append a note authored by this store's agent. Returns the note id.
+    pub fn add_note(&self, id: impl Into<TicketId>, body: &str) -> Result<i64, AppError> {
+        let id = id.into().0;
+        ticket::add_note(&self.conn, id, self.agent.as_deref(), body)
+    }
+
+    /// Notes oldest first; with `limit`, only the most recent ones.
+    pub fn notes(
+        &self,
+        id: impl Into<TicketId>,
+        limit: Option<usize>,
+    ) -> Result<Vec<TicketNote>, AppError> {
+        ticket::list_notes(&self.conn, id.into().0, limit)
+    }
+
+    /// Every label in use with its ticket count.
+    pub fn labels(&self) -> Result<Vec<(String, i64)>, AppError> {
+        ticket::label_counts(&self.conn)
+    }
+
+    pub fn ticket_labels(&self, id: impl Into<TicketId>) -> Result<Vec<String>, AppError> {
+        ticket::list_labels(&self.conn, id.into().0)
+    }
+
+    /// Every labelled ticket's labels, keyed by ticket id.
+    pub fn labels_by_ticket(&self) -> Result<HashMap<i64, Vec<String>>, AppError> {
+        ticket::labels_by_ticket(&self.conn)
+    }
+
+    // ---- Dependencies ----
+
+    /// Record that `id` depends on `on`. Cycles are rejected.
+    pub fn add_dep(
+        &self,
+        id: impl Into<TicketId>,
+        on: impl Into<TicketId>,
+    ) -> Result<(), AppError> {
+        let (id, on) = (id.into().0, on.into().0);
+        ticket::add_dep(&self.conn, id, on, self.agent.as_deref())
+    }
+
+    pub fn remove_dep(
+        &self,
+        id: impl Into<TicketId>,
+        on: impl Into<TicketId>,
+    ) -> Result<(), AppError> {
+        let (id, on) = (id.into().0, on.into().0);
+        ticket::remove_dep(&self.conn, id, on, self.agent.as_deref())
+    }
+
+    pub fn deps(&self, id: impl Into<TicketId>) -> Result<DepInfo, AppError> {
+        ticket::list_deps(&self.conn, id.into().0)
+    }
+
+    /// How many tickets each ticket depends on, keyed by ticket id.
+    pub fn dep_counts(&self) -> Result<HashMap<i64, i64>, AppError> {
+        ticket::dep_counts(&self.conn)
+    }
+
+    /// Everything `id` depends on (or that depends on it), transitively.
+    pub fn dep_tree(
+        &self,
+        id: impl Into<TicketId>,
+        direction: Direction,
+    ) -> Result<DepNode, AppError> {
+        graph::dep_tree(&self.conn, id.into().0, direction)
+    }
+
+    /// Every ticket and dependency edge.
+    pub fn graph(&self) -> Result<DepGraph, AppError> {
+        graph::dep_graph(&self.conn)
+    }
+
+    /// Open tickets in waves that can be worked in parallel; with `to`, only
+    /// what that ticket needs.
+    pub fn plan(&self, to: Option<TicketId>) -> Result<Vec<PlanWave>, AppError> {
+        graph::plan(&self.conn, to.map(TicketId::get), &self.ids)
+    }
+
+    // ---- Search, history and import ----
+
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        ticket::search_tickets(&self.conn, query, limit)
+    }
+
+    /// History in order, optionally for one ticket and/or from `since`.
+    pub fn events(
+        &self,
+        id: Option<TicketId>,
+        since: Option<&str>,
+    ) -> Result<Vec<TicketEvent>, AppError> {
+        ticket::list_events(&self.conn, id.map(TicketId::get), since)
+    }
+
+    /// Events with ids after `after`, for following changes.
+    pub fn events_after(
+        &self,
+        after: i64,
+        id: Option<TicketId>,
+    ) -> Result<Vec<TicketEvent>, AppError> {
+        ticket::list_events_after(&self.conn, after, id.map(TicketId::get))
+    }
+
+    pub fn import(
+        &self,
+        records: &[ImportTicket],
+        opts: ImportOptions,
+    ) -> Result<ImportReport, AppError> {
+        import::import_tickets(&self.conn, records, opts, self.agent.as_deref())
+    }
+
+    // ---- Workflow ----
+
+    /// The statuses and transitions in force, from `.rtik.toml` beside the
+    /// database unless replaced with `set_workflow`.
+    pub fn workflow(&self) -> Result<Workflow, AppError> {
+        Workflow::of(&self.conn)
+    }
+
+    pub fn set_workflow(&self, workflow: &Workflow) -> Result<(), AppError> {
+        workflow.install(&self.conn)
+    }
+
+    // ---- Views ----
+
+    /// Create or replace a saved view. Returns true if one was replaced.
+    pub fn save_view(&self, name: &str, args: &[String]) -> Result<bool, AppError> {
+        view::save_view(&self.conn, name, args, self.agent.as_deref())
+    }
+
+    pub fn views(&self) -> Result<Vec<View>, AppError> {
+        view::list_views(&self.conn)
+    }
+
+    pub fn delete_view(&self, name: &str) -> Result<(), AppError> {
+        view::delete_view(&self.conn, name)
+    }
+}
+
+/// Changes to apply with `Store::update`; unset fields are left alone.
+///
+/// ```
+/// use rtik::{Status, TicketPatch};
+/// let patch = TicketPatch::new().status(Status::Blocked).add_label("infra");
+/// assert!(!patch.is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TicketPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub status: Option<Status>,
+    pub priority: Option<i64>,
+    pub add_labels: Vec<String>,
+    pub remove_labels: Vec<String>,
+}
+
+impl TicketPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn priority(mut self, priority: i64) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn add_label(mut self, label: impl Into<String>) -> Self {
+        self.add_labels.push(label.into());
+        self
+    }
+
+    pub fn remove_label(mut self, label: impl Into<String>) -> Self {
+        self.remove_labels.push(label.into());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_update().is_empty()
+    }
+
+    fn as_update(&self) -> TicketUpdate<'_> {
+        TicketUpdate {
+            name: self.name.as_deref(),
+            description: self.description.as_deref(),
+            status: self.status.clone(),
+            priority: self.priority,
+            add_labels: &self.add_labels,
+            remove_labels: &self.remove_labels,
+        }
+    }
+}
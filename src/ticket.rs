@@ -1,33 +1,114 @@
 use rusqlite::{Connection, TransactionBehavior};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
-#[derive(Debug)]
+use crate::model::{IdPrefix, Status, TicketId};
+use crate::query::{Expr, QueryError, State};
+use crate::workflow::Workflow;
+
+/// Statuses that satisfy dependencies, from the connection's workflow.
+macro_rules! done_statuses {
+    () => {
+        "(SELECT name FROM temp.workflow_statuses WHERE done)"
+    };
+}
+
+/// The status `claim` moves a ticket into, from the connection's workflow.
+macro_rules! claim_status {
+    () => {
+        "(SELECT name FROM temp.workflow_statuses WHERE claim)"
+    };
+}
+
+/// The status `release` returns a ticket to and `next` picks from.
+macro_rules! release_status {
+    () => {
+        "(SELECT name FROM temp.workflow_statuses WHERE release)"
+    };
+}
+
+#[derive(Debug, Clone)]
 pub struct Ticket {
     pub id: i64,
     pub name: String,
     pub description: String,
-    pub status: String,
+    pub status: Status,
+    pub priority: i64,
+    pub parent_id: Option<i64>,
     pub claimed_by: Option<String>,
+    pub claimed_at: Option<String>,
+    pub lease_expires_at: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// Reason given by the last `block`; kept after the ticket is unblocked.
+    pub block_reason: Option<String>,
+    /// Key from an external system, set by `import`.
+    pub external_key: Option<String>,
+}
+
+impl Ticket {
+    /// True when the ticket is claimed but its lease ran out before `now`.
+    pub fn lease_expired(&self, now: &str) -> bool {
+        self.claimed_by.is_some()
+            && self
+                .lease_expires_at
+                .as_deref()
+                .is_some_and(|exp| exp <= now)
+    }
+}
+
+/// Priority given to tickets created without one. P0 is the most urgent.
+pub const DEFAULT_PRIORITY: i64 = 2;
+
+/// Default claim lease: one hour.
+pub const DEFAULT_LEASE_SECS: u64 = 3600;
+
+/// Longest lease or duration accepted: 100 years, which keeps timestamps at
+/// four-digit years so they still sort as text.
+pub const MAX_DURATION_SECS: u64 = 36500 * 86400;
+
+const TICKET_COLUMNS: &str = "id, name, description, status, priority, parent_id, claimed_by,
+     lease_expires_at, created_at, updated_at, block_reason, claimed_at, external_key";
+
+fn ticket_from_row(row: &rusqlite::Row) -> rusqlite::Result<Ticket> {
+    Ok(Ticket {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        status: row.get(3)?,
+        priority: row.get(4)?,
+        parent_id: row.get(5)?,
+        claimed_by: row.get(6)?,
+        lease_expires_at: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        block_reason: row.get(10)?,
+        claimed_at: row.get(11)?,
+        external_key: row.get(12)?,
+    })
 }
 
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("ticket #{0} not found")]
     NotFound(i64),
-    #[error("no fields provided — use --name, --desc, or --status")]
+    #[error("no fields provided — use --name, --desc, --status, --priority, --label or --unlabel")]
     NoUpdateFields,
-    #[error("invalid status '{0}': must be one of todo, in-progress, blocked, done")]
-    InvalidStatus(String),
+    #[error("invalid status '{0}': {1}")]
+    InvalidStatus(String, String),
+    #[error("invalid priority {0}: must be P0-P4")]
+    InvalidPriority(i64),
     #[error("ticket #{0} already claimed by {1} since {2}")]
     AlreadyClaimed(i64, String, String),
     #[error("from {from}, valid transitions are: {valid}")]
     InvalidTransition { from: String, valid: String },
     #[error("block reason is required")]
     BlockReasonRequired,
+    #[error("note text is required")]
+    NoteRequired,
+    #[error("invalid label '{0}': labels must be non-empty with no spaces, commas or '#'")]
+    InvalidLabel(String),
     #[error("ticket #{0} not claimed by you ({1})")]
     NotOwner(i64, String),
     #[error("ticket #{0} is not currently claimed")]
@@ -38,25 +119,100 @@ pub enum AppError {
     CyclicDependency(String),
     #[error("dependency from #{0} to #{1} not found")]
     DepNotFound(i64, i64),
+    #[error("no ready tickets")]
+    NoReadyTicket,
+    #[error("ticket #{0} has {1} subtask(s) — use --children cascade or --children orphan")]
+    HasChildren(i64, i64),
+    #[error("view '{0}' not found")]
+    ViewNotFound(String),
+    #[error("invalid view name '{0}': use letters, digits, '-', '_' or '.'")]
+    InvalidViewName(String),
+    #[error("invalid view: {0}")]
+    InvalidViewArgs(String),
+    #[error("invalid query: {0}")]
+    InvalidQuery(#[from] QueryError),
+    #[error("invalid search query: {0}")]
+    InvalidSearch(String),
+    #[error("invalid import: {0}")]
+    InvalidImport(String),
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("unknown config key '{0}': see `rtik config list`")]
+    UnknownConfigKey(String),
+    #[error("cannot listen on {0}: {1}")]
+    Listen(String, String),
+    #[error("database migration failed: {0}")]
+    Migration(#[from] rusqlite_migration::Error),
+    /// Flags that cannot be combined.
+    #[error("{0}")]
+    Usage(String),
     #[error(transparent)]
     Db(#[from] rusqlite::Error),
 }
 
-pub fn validate_transition(from: &str, to: &str) -> Result<(), AppError> {
-    let allowed: &[&str] = match from {
-        "todo" => &["in-progress", "blocked"],
-        "in-progress" => &["done", "blocked", "todo"],
-        "blocked" => &["in-progress", "todo"],
-        "done" => &["in-progress"],
-        _ => &[],
-    };
-    if allowed.contains(&to) {
-        Ok(())
-    } else {
-        Err(AppError::InvalidTransition {
-            from: from.to_string(),
-            valid: allowed.join(", "),
-        })
+impl AppError {
+    /// Process exit code for this error. `next` with an empty queue exits 3 so
+    /// agent loops can tell "nothing to do" apart from a real failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::NoReadyTicket => 3,
+            _ => 1,
+        }
+    }
+
+    /// HTTP status for this error in `rtik serve` responses.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            AppError::NotFound(_)
+            | AppError::DepNotFound(..)
+            | AppError::ViewNotFound(_)
+            | AppError::NoReadyTicket => 404,
+            AppError::AlreadyClaimed(..)
+            | AppError::InvalidTransition { .. }
+            | AppError::NotClaimed(_)
+            | AppError::CyclicDependency(_)
+            | AppError::HasChildren(..) => 409,
+            AppError::NotOwner(..) => 403,
+            AppError::Listen(..)
+            | AppError::InvalidConfig(_)
+            | AppError::Migration(_)
+            | AppError::Db(_) => 500,
+            _ => 400,
+        }
+    }
+
+    /// Stable machine-readable name for this error, used by `--json`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::NoUpdateFields => "no_update_fields",
+            AppError::InvalidStatus(..) => "invalid_status",
+            AppError::InvalidPriority(_) => "invalid_priority",
+            AppError::AlreadyClaimed(..) => "already_claimed",
+            AppError::InvalidTransition { .. } => "invalid_transition",
+            AppError::BlockReasonRequired => "block_reason_required",
+            AppError::NoteRequired => "note_required",
+            AppError::InvalidLabel(_) => "invalid_label",
+            AppError::NotOwner(..) => "not_owner",
+            AppError::NotClaimed(_) => "not_claimed",
+            AppError::AgentNotSet => "agent_not_set",
+            AppError::CyclicDependency(_) => "cyclic_dependency",
+            AppError::DepNotFound(..) => "dep_not_found",
+            AppError::NoReadyTicket => "no_ready_ticket",
+            AppError::HasChildren(..) => "has_children",
+            AppError::ViewNotFound(_) => "view_not_found",
+            AppError::InvalidViewName(_) => "invalid_view_name",
+            AppError::InvalidViewArgs(_) => "invalid_view_args",
+            AppError::InvalidQuery(_) => "invalid_query",
+            AppError::InvalidSearch(_) => "invalid_search",
+            AppError::InvalidImport(_) => "invalid_import",
+            AppError::InvalidConfig(_) => "invalid_config",
+            AppError::UnknownConfigKey(_) => "unknown_config_key",
+            AppError::Listen(..) => "listen_failed",
+            AppError::Migration(_) => "migration",
+            AppError::Usage(_) => "usage",
+            AppError::Db(_) => "database",
+        }
     }
 }
 
@@ -65,70 +221,368 @@ pub fn claim_ticket(
     id: i64,
     agent: &str,
     force: bool,
-) -> Result<(), AppError> {
+) -> Result<Vec<String>, AppError> {
+    claim_ticket_with_lease(conn, id, agent, force, DEFAULT_LEASE_SECS)
+}
+
+/// Claim a ticket for `lease_secs` seconds. A claim whose lease has expired
+/// is treated as unclaimed and can be taken over without `force`. Returns
+/// warnings about unmet dependencies and claims taken over.
+pub fn claim_ticket_with_lease(
+    conn: &mut Connection,
+    id: i64,
+    agent: &str,
+    force: bool,
+    lease_secs: u64,
+) -> Result<Vec<String>, AppError> {
     let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut warnings = Vec::new();
 
     // Check unmet deps and warn.
     let unmet_count: i64 = tx.query_row(
-        "SELECT COUNT(*) FROM ticket_deps td
+        concat!(
+            "SELECT COUNT(*) FROM ticket_deps td
          JOIN tickets dep ON dep.id = td.depends_on
-         WHERE td.ticket_id = ?1 AND dep.status != 'done'",
+         WHERE td.ticket_id = ?1 AND dep.status NOT IN ",
+            done_statuses!()
+        ),
         rusqlite::params![id],
         |row| row.get(0),
     )?;
     if unmet_count > 0 {
-        eprintln!("Warning: {} dependencies not done", unmet_count);
+        warnings.push(format!("{} dependencies not done", unmet_count));
     }
 
     let now = chrono_free_utc_now();
-    if force {
-        let existing: Option<String> = tx
-            .query_row(
-                "SELECT claimed_by FROM tickets WHERE id = ?1",
-                rusqlite::params![id],
-                |row| row.get(0),
-            )
-            .map_err(|e| match e {
-                rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(id),
-                other => AppError::Db(other),
-            })?;
-        if let Some(ref owner) = existing {
-            if owner != agent {
-                eprintln!("Warning: overriding claim by {}", owner);
+    let expires = utc_now_plus(lease_secs);
+    let (existing, claimed_at, lease, status): (
+        Option<String>,
+        Option<String>,
+        Option<String>,
+        Status,
+    ) = tx
+        .query_row(
+            "SELECT claimed_by, claimed_at, lease_expires_at, status FROM tickets WHERE id = ?1",
+            rusqlite::params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(id),
+            other => AppError::Db(other),
+        })?;
+    if let Some(ref owner) = existing {
+        let expired = lease.as_deref().is_some_and(|exp| exp <= now.as_str());
+        if !force && !expired {
+            let at = claimed_at.unwrap_or_default();
+            return Err(AppError::AlreadyClaimed(id, owner.clone(), at));
+        }
+        if owner != agent {
+            if expired {
+                warnings.push(format!("taking over expired lease held by {}", owner));
+            } else {
+                warnings.push(format!("overriding claim by {}", owner));
             }
         }
-        tx.execute(
-            "UPDATE tickets SET claimed_by = ?1, claimed_at = ?2, status = 'in-progress', updated_at = ?2 WHERE id = ?3",
-            rusqlite::params![agent, now, id],
+    }
+
+    apply_claim(&tx, id, agent, existing.as_deref(), &status, &now, &expires)?;
+
+    tx.commit()?;
+    Ok(warnings)
+}
+
+// claim_prev_status remembers the status before the first claim so `reap`
+// can restore it; a takeover keeps the original value.
+fn apply_claim(
+    conn: &Connection,
+    id: i64,
+    agent: &str,
+    prev_owner: Option<&str>,
+    prev_status: &Status,
+    now: &str,
+    expires: &str,
+) -> Result<(), AppError> {
+    let workflow = Workflow::of(conn)?;
+    let claimed = workflow.claim();
+    conn.execute(
+        "UPDATE tickets SET claimed_by = ?1, claimed_at = ?2, lease_expires_at = ?3,
+         claim_prev_status = CASE WHEN claimed_by IS NULL THEN status ELSE claim_prev_status END,
+         status = ?4, updated_at = ?2
+         WHERE id = ?5",
+        rusqlite::params![agent, now, expires, claimed, id],
+    )?;
+    if prev_owner != Some(agent) {
+        record_event(conn, id, Some(agent), "claim", prev_owner, Some(agent), now)?;
+    }
+    if prev_status != claimed {
+        record_event(
+            conn,
+            id,
+            Some(agent),
+            "status",
+            Some(prev_status.as_str()),
+            Some(claimed.as_str()),
+            now,
         )?;
-    } else {
-        let affected = tx.execute(
-            "UPDATE tickets SET claimed_by = ?1, claimed_at = ?2, status = 'in-progress', updated_at = ?2 WHERE id = ?3 AND claimed_by IS NULL",
-            rusqlite::params![agent, now, id],
+    }
+    Ok(())
+}
+
+// Virtual-state conditions over the `tickets` table.
+const WAITING_SQL: &str = concat!(
+    "EXISTS (SELECT 1 FROM ticket_deps td
+     JOIN tickets dep ON dep.id = td.depends_on
+     WHERE td.ticket_id = tickets.id AND dep.status NOT IN ",
+    done_statuses!(),
+    ")"
+);
+const BLOCKING_SQL: &str = concat!(
+    "(tickets.status NOT IN ",
+    done_statuses!(),
+    " AND EXISTS (SELECT 1 FROM ticket_deps td
+     JOIN tickets dependent ON dependent.id = td.ticket_id
+     WHERE td.depends_on = tickets.id AND dependent.status NOT IN ",
+    done_statuses!(),
+    "))"
+);
+
+/// The ready condition, with `now` as the placeholder bound to the current
+/// timestamp (for lease expiry), e.g. `?1` or `?`.
+fn ready_sql(now: &str) -> String {
+    format!(
+        concat!(
+            "(tickets.status = ",
+            release_status!(),
+            "
+     AND (tickets.claimed_by IS NULL
+          OR (tickets.lease_expires_at IS NOT NULL AND tickets.lease_expires_at <= {now}))
+     AND NOT EXISTS (SELECT 1 FROM ticket_deps td
+         JOIN tickets dep ON dep.id = td.depends_on
+         WHERE td.ticket_id = tickets.id AND dep.status NOT IN ",
+            done_statuses!(),
+            "))"
+        ),
+        now = now
+    )
+}
+
+/// SQL condition for a computed state, pushing any bound values onto `params`.
+/// A claim whose lease has expired counts as unclaimed.
+pub(crate) fn state_sql(state: State, params: &mut Vec<Box<dyn rusqlite::types::ToSql>>) -> String {
+    match state {
+        State::Ready => {
+            params.push(Box::new(chrono_free_utc_now()));
+            ready_sql("?")
+        }
+        State::Waiting => WAITING_SQL.to_string(),
+        State::Blocking => BLOCKING_SQL.to_string(),
+        State::Claimed => {
+            params.push(Box::new(chrono_free_utc_now()));
+            "(tickets.claimed_by IS NOT NULL
+              AND (tickets.lease_expires_at IS NULL OR tickets.lease_expires_at > ?))"
+                .to_string()
+        }
+        State::Unclaimed => {
+            params.push(Box::new(chrono_free_utc_now()));
+            "(tickets.claimed_by IS NULL
+              OR (tickets.lease_expires_at IS NOT NULL AND tickets.lease_expires_at <= ?))"
+                .to_string()
+        }
+    }
+}
+
+/// Computed states derived from status, claim and dependencies.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TicketFlags {
+    /// todo, unclaimed (or lease expired), every dependency done
+    pub ready: bool,
+    /// at least one dependency is not done
+    pub waiting: bool,
+    /// not done, and some open ticket depends on it
+    pub blocking: bool,
+}
+
+pub fn ticket_flags(conn: &Connection, id: i64) -> Result<TicketFlags, AppError> {
+    let now = chrono_free_utc_now();
+    conn.query_row(
+        &format!(
+            "SELECT {}, {}, {} FROM tickets WHERE id = ?2",
+            ready_sql("?1"),
+            WAITING_SQL,
+            BLOCKING_SQL
+        ),
+        rusqlite::params![now, id],
+        |r| {
+            Ok(TicketFlags {
+                ready: r.get(0)?,
+                waiting: r.get(1)?,
+                blocking: r.get(2)?,
+            })
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(id),
+        other => AppError::Db(other),
+    })
+}
+
+/// Atomically pick the most urgent ready ticket (todo, unclaimed or lease
+/// expired, every dependency done; lowest id breaks ties) and claim it. Returns `None` if nothing is ready.
+pub fn claim_next(
+    conn: &mut Connection,
+    agent: &str,
+    lease_secs: u64,
+) -> Result<Option<Ticket>, AppError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let now = chrono_free_utc_now();
+    let expires = utc_now_plus(lease_secs);
+
+    let picked: Option<(i64, Option<String>, Status)> = match tx.query_row(
+        &format!(
+            "SELECT id, claimed_by, status FROM tickets WHERE {}
+             ORDER BY priority ASC, id ASC
+             LIMIT 1",
+            ready_sql("?1")
+        ),
+        rusqlite::params![now],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ) {
+        Ok(picked) => Some(picked),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(AppError::Db(e)),
+    };
+
+    let Some((id, prev_owner, prev_status)) = picked else {
+        return Ok(None);
+    };
+    apply_claim(
+        &tx,
+        id,
+        agent,
+        prev_owner.as_deref(),
+        &prev_status,
+        &now,
+        &expires,
+    )?;
+    let t = get_ticket(&tx, id)?;
+    tx.commit()?;
+    Ok(Some(t))
+}
+
+/// Extend the lease on a ticket the agent currently holds. Returns the new
+/// expiry timestamp.
+pub fn heartbeat_ticket(
+    conn: &mut Connection,
+    id: i64,
+    agent: &str,
+    lease_secs: u64,
+) -> Result<String, AppError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let result: rusqlite::Result<(Option<String>, Option<String>)> = tx.query_row(
+        "SELECT claimed_by, lease_expires_at FROM tickets WHERE id = ?1",
+        rusqlite::params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    );
+    let old_lease = match result {
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Err(AppError::NotFound(id)),
+        Err(e) => return Err(AppError::Db(e)),
+        Ok((None, _)) => return Err(AppError::NotClaimed(id)),
+        Ok((Some(owner), _)) if owner != agent => {
+            return Err(AppError::NotOwner(id, agent.to_string()))
+        }
+        Ok((Some(_), lease)) => lease,
+    };
+
+    let now = chrono_free_utc_now();
+    let expires = utc_now_plus(lease_secs);
+    tx.execute(
+        "UPDATE tickets SET lease_expires_at = ?1 WHERE id = ?2",
+        rusqlite::params![expires, id],
+    )?;
+    record_event(
+        &tx,
+        id,
+        Some(agent),
+        "lease",
+        old_lease.as_deref(),
+        Some(&expires),
+        &now,
+    )?;
+
+    tx.commit()?;
+    Ok(expires)
+}
+
+#[derive(Serialize)]
+pub struct ReapedTicket {
+    pub id: i64,
+    pub name: String,
+    pub agent: String,
+    pub lease_expired_at: String,
+    pub previous_status: Status,
+    pub restored_status: Status,
+}
+
+/// Clear every claim whose lease has expired. Tickets still in the
+/// workflow's claim status return to the status they had before they were
+/// claimed.
+pub fn reap_expired(
+    conn: &mut Connection,
+    actor: Option<&str>,
+) -> Result<Vec<ReapedTicket>, AppError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let now = chrono_free_utc_now();
+
+    let reaped = {
+        let mut stmt = tx.prepare(concat!(
+            "SELECT id, name, claimed_by, lease_expires_at, status,
+                    CASE WHEN status = ",
+            claim_status!(),
+            " THEN COALESCE(claim_prev_status, ",
+            release_status!(),
+            ")
+                         ELSE status END
+             FROM tickets
+             WHERE claimed_by IS NOT NULL AND lease_expires_at IS NOT NULL
+               AND lease_expires_at <= ?1
+             ORDER BY id"
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![now], |r| {
+            Ok(ReapedTicket {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                agent: r.get(2)?,
+                lease_expired_at: r.get(3)?,
+                previous_status: r.get(4)?,
+                restored_status: r.get(5)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+
+    for r in &reaped {
+        tx.execute(
+            "UPDATE tickets SET claimed_by = NULL, claimed_at = NULL, lease_expires_at = NULL,
+             claim_prev_status = NULL, status = ?1, updated_at = ?2 WHERE id = ?3",
+            rusqlite::params![r.restored_status, now, r.id],
         )?;
-        if affected == 0 {
-            let result: rusqlite::Result<(Option<String>, Option<String>)> = tx.query_row(
-                "SELECT claimed_by, claimed_at FROM tickets WHERE id = ?1",
-                rusqlite::params![id],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            );
-            match result {
-                Err(rusqlite::Error::QueryReturnedNoRows) => return Err(AppError::NotFound(id)),
-                Err(e) => return Err(AppError::Db(e)),
-                Ok((Some(owner), claimed_at)) => {
-                    let at = claimed_at.unwrap_or_default();
-                    return Err(AppError::AlreadyClaimed(id, owner, at));
-                }
-                Ok((None, _)) => {
-                    // Ticket exists but unclaimed — shouldn't happen, but handle gracefully.
-                    return Err(AppError::NotFound(id));
-                }
-            }
+        record_event(&tx, r.id, actor, "reaped", Some(&r.agent), None, &now)?;
+        if r.previous_status != r.restored_status {
+            record_event(
+                &tx,
+                r.id,
+                actor,
+                "status",
+                Some(r.previous_status.as_str()),
+                Some(r.restored_status.as_str()),
+                &now,
+            )?;
         }
     }
 
     tx.commit()?;
-    Ok(())
+    Ok(reaped)
 }
 
 pub fn release_ticket(
@@ -136,15 +590,16 @@ pub fn release_ticket(
     id: i64,
     agent: &str,
     force: bool,
-) -> Result<(), AppError> {
+) -> Result<Vec<String>, AppError> {
     let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut warnings = Vec::new();
 
-    let result: rusqlite::Result<Option<String>> = tx.query_row(
-        "SELECT claimed_by FROM tickets WHERE id = ?1",
+    let result: rusqlite::Result<(Option<String>, String)> = tx.query_row(
+        "SELECT claimed_by, status FROM tickets WHERE id = ?1",
         rusqlite::params![id],
-        |row| row.get(0),
+        |row| Ok((row.get(0)?, row.get(1)?)),
     );
-    let claimed_by = match result {
+    let (claimed_by, status) = match result {
         Err(rusqlite::Error::QueryReturnedNoRows) => return Err(AppError::NotFound(id)),
         Err(e) => return Err(AppError::Db(e)),
         Ok(val) => val,
@@ -153,7 +608,7 @@ pub fn release_ticket(
     if force {
         if let Some(ref owner) = claimed_by {
             if owner != agent {
-                eprintln!("Warning: overriding claim by {}", owner);
+                warnings.push(format!("overriding claim by {}", owner));
             }
         }
     } else {
@@ -165,62 +620,138 @@ pub fn release_ticket(
     }
 
     let now = chrono_free_utc_now();
+    let workflow = Workflow::of(&tx)?;
+    let released = workflow.release();
     tx.execute(
-        "UPDATE tickets SET claimed_by = NULL, claimed_at = NULL, status = 'todo', updated_at = ?1 WHERE id = ?2",
-        rusqlite::params![now, id],
+        "UPDATE tickets SET claimed_by = NULL, claimed_at = NULL, lease_expires_at = NULL,
+         claim_prev_status = NULL, status = ?1, updated_at = ?2 WHERE id = ?3",
+        rusqlite::params![released, now, id],
     )?;
+    if claimed_by.is_some() {
+        record_event(
+            &tx,
+            id,
+            Some(agent),
+            "claim",
+            claimed_by.as_deref(),
+            None,
+            &now,
+        )?;
+    }
+    if status != released.as_str() {
+        record_event(
+            &tx,
+            id,
+            Some(agent),
+            "status",
+            Some(&status),
+            Some(released.as_str()),
+            &now,
+        )?;
+    }
 
     tx.commit()?;
-    Ok(())
+    Ok(warnings)
 }
 
-pub fn block_ticket(conn: &Connection, id: i64, reason: &str) -> Result<String, AppError> {
-    let (current_status, name): (String, String) = conn
-        .query_row(
-            "SELECT status, name FROM tickets WHERE id = ?1",
-            rusqlite::params![id],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(id),
-            other => AppError::Db(other),
-        })?;
+pub fn block_ticket(
+    conn: &Connection,
+    id: i64,
+    reason: &str,
+    actor: Option<&str>,
+) -> Result<String, AppError> {
+    in_tx(conn, |conn| {
+        let (current_status, name): (Status, String) = conn
+            .query_row(
+                "SELECT status, name FROM tickets WHERE id = ?1",
+                rusqlite::params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(id),
+                other => AppError::Db(other),
+            })?;
 
-    validate_transition(&current_status, "blocked")?;
+        Workflow::of(conn)?.check_transition(&current_status, &Status::Blocked)?;
 
-    let now = chrono_free_utc_now();
-    conn.execute(
-        "UPDATE tickets SET status = 'blocked', block_reason = ?1, updated_at = ?2 WHERE id = ?3",
-        rusqlite::params![reason, now, id],
-    )?;
+        let now = chrono_free_utc_now();
+        conn.execute(
+            "UPDATE tickets SET status = 'blocked', block_reason = ?1, updated_at = ?2 WHERE id = ?3",
+            rusqlite::params![reason, now, id],
+        )?;
+        record_event(
+            conn,
+            id,
+            actor,
+            "status",
+            Some(current_status.as_str()),
+            Some("blocked"),
+            &now,
+        )?;
+        record_event(conn, id, actor, "block_reason", None, Some(reason), &now)?;
 
-    Ok(name)
+        Ok(name)
+    })
+}
+
+#[derive(Default)]
+pub struct NewTicket<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+    /// None = DEFAULT_PRIORITY
+    pub priority: Option<i64>,
+    pub labels: &'a [String],
+    pub parent: Option<i64>,
 }
 
 pub fn create_ticket(conn: &Connection, name: &str, desc: &str) -> Result<i64, AppError> {
-    conn.execute(
-        "INSERT INTO tickets (name, description) VALUES (?1, ?2)",
-        rusqlite::params![name, desc],
-    )?;
-    Ok(conn.last_insert_rowid())
+    create_ticket_with(
+        conn,
+        &NewTicket {
+            name,
+            description: desc,
+            ..Default::default()
+        },
+        None,
+    )
+}
+
+pub fn create_ticket_with(
+    conn: &Connection,
+    new: &NewTicket,
+    actor: Option<&str>,
+) -> Result<i64, AppError> {
+    let priority = new.priority.unwrap_or(DEFAULT_PRIORITY);
+    validate_priority(priority)?;
+    in_tx(conn, |conn| {
+        if let Some(parent) = new.parent {
+            get_ticket(conn, parent)?;
+        }
+        conn.execute(
+            "INSERT INTO tickets (name, description, priority, parent_id) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![new.name, new.description, priority, new.parent],
+        )?;
+        let id = conn.last_insert_rowid();
+        let now = chrono_free_utc_now();
+        record_event(conn, id, actor, "created", None, Some(new.name), &now)?;
+        apply_labels(conn, id, new.labels, &[], actor, &now)?;
+        Ok(id)
+    })
+}
+
+pub(crate) fn validate_priority(priority: i64) -> Result<(), AppError> {
+    if (0..=4).contains(&priority) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidPriority(priority))
+    }
 }
 
 pub fn get_ticket(conn: &Connection, id: i64) -> Result<Ticket, AppError> {
     conn.query_row(
-        "SELECT id, name, description, status, claimed_by, created_at, updated_at
-         FROM tickets WHERE id = ?1",
+        &format!("SELECT {} FROM tickets WHERE id = ?1", TICKET_COLUMNS),
         rusqlite::params![id],
-        |row| {
-            Ok(Ticket {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                status: row.get(3)?,
-                claimed_by: row.get(4)?,
-                created_at: row.get(5)?,
-                updated_at: row.get(6)?,
-            })
-        },
+        ticket_from_row,
     )
     .map_err(|e| match e {
         rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(id),
@@ -228,35 +759,266 @@ pub fn get_ticket(conn: &Connection, id: i64) -> Result<Ticket, AppError> {
     })
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Priority,
+    Created,
+    Updated,
+    Id,
+}
+
+impl SortField {
+    fn column(self) -> &'static str {
+        match self {
+            SortField::Priority => "priority",
+            SortField::Created => "created_at",
+            SortField::Updated => "updated_at",
+            SortField::Id => "id",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+#[derive(Default)]
 pub struct ListFilter {
-    pub status: Option<String>,
+    pub status: Option<Status>,
     /// None = no filter, Some(true) = claimed only, Some(false) = unclaimed only
     pub claimed: Option<bool>,
     pub claimer: Option<String>,
     /// Each term must appear in name OR description (AND-composed across terms, case-insensitive)
     pub search: Vec<String>,
+    /// Each inner group matches tickets with ANY of its labels; groups are AND-ed.
+    pub labels: Vec<Vec<String>>,
+    /// Tickets carrying any of these labels are excluded.
+    pub exclude_labels: Vec<String>,
+    /// Only direct subtasks of this ticket.
+    pub parent: Option<i64>,
+    /// Only tickets without a parent.
+    pub top_level: bool,
+    /// Only todo, unclaimed tickets whose dependencies are all done.
+    pub ready: bool,
+    /// Only tickets with at least one unmet dependency.
+    pub waiting: bool,
+    /// Only open tickets that other open tickets depend on.
+    pub blocking: bool,
+    /// Parsed `--query` expression, AND-ed with the other filters.
+    pub query: Option<Expr>,
+    /// Applied in order; empty = id ascending. Ties always fall back to id ascending.
+    pub sort: Vec<SortKey>,
+    /// Flip every sort key, including the id tiebreak.
+    pub reverse: bool,
+    /// Maximum number of tickets to return.
+    pub limit: Option<usize>,
+    /// Skip this many matching tickets first.
+    pub offset: usize,
+    /// Cursor: start right after this ticket in the sort order.
+    pub after: Option<i64>,
+}
+
+/// Builder-style setters; each returns the updated filter.
+///
+/// ```
+/// use rtik::{ListFilter, Status};
+/// let filter = ListFilter::new().status(Status::Todo).label("api").ready().limit(10);
+/// assert_eq!(filter.labels, vec![vec!["api".to_string()]]);
+/// ```
+impl ListFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Require this label; repeated calls require all of them.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.labels.push(vec![label.into()]);
+        self
+    }
+
+    /// Require at least one of these labels.
+    pub fn any_label<S: Into<String>>(mut self, labels: impl IntoIterator<Item = S>) -> Self {
+        self.labels
+            .push(labels.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn exclude_label(mut self, label: impl Into<String>) -> Self {
+        self.exclude_labels.push(label.into());
+        self
+    }
+
+    /// `true` for claimed tickets only, `false` for unclaimed only.
+    pub fn claimed(mut self, claimed: bool) -> Self {
+        self.claimed = Some(claimed);
+        self
+    }
+
+    pub fn claimer(mut self, agent: impl Into<String>) -> Self {
+        self.claimer = Some(agent.into());
+        self
+    }
+
+    /// Require this term in the name or description.
+    pub fn search(mut self, term: impl Into<String>) -> Self {
+        self.search.push(term.into());
+        self
+    }
+
+    pub fn parent(mut self, id: impl Into<TicketId>) -> Self {
+        self.parent = Some(id.into().0);
+        self
+    }
+
+    pub fn top_level(mut self) -> Self {
+        self.top_level = true;
+        self
+    }
+
+    pub fn ready(mut self) -> Self {
+        self.ready = true;
+        self
+    }
+
+    pub fn waiting(mut self) -> Self {
+        self.waiting = true;
+        self
+    }
+
+    pub fn blocking(mut self) -> Self {
+        self.blocking = true;
+        self
+    }
+
+    pub fn query(mut self, expr: Expr) -> Self {
+        self.query = Some(expr);
+        self
+    }
+
+    /// Add a sort key after any already given.
+    pub fn sort(mut self, field: SortField, descending: bool) -> Self {
+        self.sort.push(SortKey { field, descending });
+        self
+    }
+
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Start after this ticket: pass the previous page's `next_cursor`.
+    pub fn after(mut self, id: impl Into<TicketId>) -> Self {
+        self.after = Some(id.into().0);
+        self
+    }
+}
+
+/// One page of a filtered listing. `next_cursor` is the id to pass as
+/// `after` for the following page, or `None` on the last page.
+pub struct TicketPage {
+    pub tickets: Vec<Ticket>,
+    pub next_cursor: Option<i64>,
 }
 
 pub fn list_tickets_filtered(
     conn: &Connection,
     filter: &ListFilter,
 ) -> Result<Vec<Ticket>, AppError> {
+    list_tickets_page(conn, filter).map(|page| page.tickets)
+}
+
+type BoxedParams = Vec<Box<dyn rusqlite::types::ToSql>>;
+
+/// Build the SELECT for `filter`, returning at most `limit` rows (-1 = all).
+/// Each row holds the ticket columns followed by its ready, waiting and
+/// blocking flags (see `flagged_ticket_from_row`).
+fn list_query(
+    conn: &Connection,
+    filter: &ListFilter,
+    limit: i64,
+) -> Result<(String, BoxedParams), AppError> {
     let mut conditions: Vec<String> = Vec::new();
     let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
 
+    // Statuses the workflow lacks would silently match nothing.
+    let statuses: Vec<&Status> = filter
+        .status
+        .iter()
+        .chain(filter.query.iter().flat_map(Expr::statuses))
+        .collect();
+    if !statuses.is_empty() {
+        let workflow = Workflow::of(conn)?;
+        for s in statuses {
+            workflow.check(s)?;
+        }
+    }
     if let Some(ref s) = filter.status {
         conditions.push("status = ?".to_string());
         params.push(Box::new(s.clone()));
     }
     match filter.claimed {
-        Some(true) => conditions.push("claimed_by IS NOT NULL".to_string()),
-        Some(false) => conditions.push("claimed_by IS NULL".to_string()),
+        Some(true) => conditions.push(state_sql(State::Claimed, &mut params)),
+        Some(false) => conditions.push(state_sql(State::Unclaimed, &mut params)),
         None => {}
     }
     if let Some(ref c) = filter.claimer {
         conditions.push("claimed_by = ?".to_string());
         params.push(Box::new(c.clone()));
     }
+    if let Some(parent) = filter.parent {
+        conditions.push("parent_id = ?".to_string());
+        params.push(Box::new(parent));
+    }
+    if filter.top_level {
+        conditions.push("parent_id IS NULL".to_string());
+    }
+    if filter.ready {
+        conditions.push(state_sql(State::Ready, &mut params));
+    }
+    if filter.waiting {
+        conditions.push(state_sql(State::Waiting, &mut params));
+    }
+    if filter.blocking {
+        conditions.push(state_sql(State::Blocking, &mut params));
+    }
+    for group in filter.labels.iter().filter(|g| !g.is_empty()) {
+        conditions.push(format!(
+            "id IN (SELECT ticket_id FROM ticket_labels WHERE label IN ({}))",
+            vec!["?"; group.len()].join(", ")
+        ));
+        for label in group {
+            params.push(Box::new(normalize_label(label)));
+        }
+    }
+    if !filter.exclude_labels.is_empty() {
+        conditions.push(format!(
+            "id NOT IN (SELECT ticket_id FROM ticket_labels WHERE label IN ({}))",
+            vec!["?"; filter.exclude_labels.len()].join(", ")
+        ));
+        for label in &filter.exclude_labels {
+            params.push(Box::new(normalize_label(label)));
+        }
+    }
+    if let Some(ref query) = filter.query {
+        conditions.push(query.to_sql(&mut params));
+    }
     for term in &filter.search {
         conditions.push("(LOWER(name) LIKE ? OR LOWER(description) LIKE ?)".to_string());
         let pattern = format!("%{}%", term.to_lowercase());
@@ -264,44 +1026,120 @@ pub fn list_tickets_filtered(
         params.push(Box::new(pattern));
     }
 
+    // Effective ordering: the sort keys, then id as the tiebreak.
+    let mut keys: Vec<(&str, bool)> = filter
+        .sort
+        .iter()
+        .map(|k| (k.field.column(), k.descending != filter.reverse))
+        .collect();
+    if !filter.sort.iter().any(|k| k.field == SortField::Id) {
+        keys.push(("id", filter.reverse));
+    }
+
+    // Keyset condition: rows strictly after the cursor ticket in that order.
+    if let Some(after) = filter.after {
+        get_ticket(conn, after)?;
+        let cursor = |col: &str| format!("(SELECT {} FROM tickets c WHERE c.id = ?)", col);
+        let mut alternatives = Vec::new();
+        for (i, &(col, desc)) in keys.iter().enumerate() {
+            let mut parts = Vec::new();
+            for &(prev, _) in &keys[..i] {
+                parts.push(format!("tickets.{} = {}", prev, cursor(prev)));
+                params.push(Box::new(after));
+            }
+            let op = if desc { "<" } else { ">" };
+            parts.push(format!("tickets.{} {} {}", col, op, cursor(col)));
+            params.push(Box::new(after));
+            alternatives.push(format!("({})", parts.join(" AND ")));
+        }
+        conditions.push(format!("({})", alternatives.join(" OR ")));
+    }
+
     let where_clause = if conditions.is_empty() {
         String::new()
     } else {
         format!("WHERE {}", conditions.join(" AND "))
     };
 
+    let order: Vec<String> = keys
+        .iter()
+        .map(|&(col, desc)| format!("{} {}", col, if desc { "DESC" } else { "ASC" }))
+        .collect();
+
+    // The flag columns come before the WHERE clause, so bind theirs first.
+    let mut select_params: BoxedParams = Vec::new();
+    let flags = [State::Ready, State::Waiting, State::Blocking]
+        .map(|state| state_sql(state, &mut select_params))
+        .join(", ");
+    select_params.extend(params);
+
     let sql = format!(
-        "SELECT id, name, description, status, claimed_by, created_at, updated_at FROM tickets {} ORDER BY id ASC",
-        where_clause
+        "SELECT {}, {} FROM tickets {} ORDER BY {} LIMIT {} OFFSET {}",
+        TICKET_COLUMNS,
+        flags,
+        where_clause,
+        order.join(", "),
+        limit,
+        // SQLite reads anything past i64::MAX as a real, which OFFSET rejects.
+        i64::try_from(filter.offset).unwrap_or(i64::MAX)
     );
+    Ok((sql, select_params))
+}
+
+/// Read a `list_query` row: the ticket and its computed states.
+fn flagged_ticket_from_row(row: &rusqlite::Row) -> rusqlite::Result<(Ticket, TicketFlags)> {
+    let flags = TicketFlags {
+        ready: row.get(13)?,
+        waiting: row.get(14)?,
+        blocking: row.get(15)?,
+    };
+    Ok((ticket_from_row(row)?, flags))
+}
+
+/// A page of tickets with their computed states, as tables show them.
+pub struct FlaggedPage {
+    pub tickets: Vec<(Ticket, TicketFlags)>,
+    pub next_cursor: Option<i64>,
+}
 
+/// Like `list_tickets_page`, with each ticket's flags read in the same query.
+pub fn list_flagged_page(conn: &Connection, filter: &ListFilter) -> Result<FlaggedPage, AppError> {
+    // Fetch one extra row to learn whether another page follows. A limit
+    // too large for SQLite is no limit at all.
+    let limit = filter
+        .limit
+        .and_then(|l| i64::try_from(l).ok()?.checked_add(1))
+        .unwrap_or(-1);
+    let (sql, params) = list_query(conn, filter, limit)?;
     let param_refs: Vec<&dyn rusqlite::types::ToSql> = params.iter().map(|p| p.as_ref()).collect();
     let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt.query_map(param_refs.as_slice(), |row| {
-        Ok(Ticket {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            description: row.get(2)?,
-            status: row.get(3)?,
-            claimed_by: row.get(4)?,
-            created_at: row.get(5)?,
-            updated_at: row.get(6)?,
-        })
-    })?;
-    rows.collect::<rusqlite::Result<Vec<_>>>()
-        .map_err(AppError::Db)
+    let rows = stmt.query_map(param_refs.as_slice(), flagged_ticket_from_row)?;
+    let mut tickets = rows
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(AppError::Db)?;
+    let next_cursor = match filter.limit {
+        Some(l) if tickets.len() > l => {
+            tickets.truncate(l);
+            tickets.last().map(|(t, _)| t.id)
+        }
+        _ => None,
+    };
+    Ok(FlaggedPage {
+        tickets,
+        next_cursor,
+    })
+}
+
+pub fn list_tickets_page(conn: &Connection, filter: &ListFilter) -> Result<TicketPage, AppError> {
+    let page = list_flagged_page(conn, filter)?;
+    Ok(TicketPage {
+        tickets: page.tickets.into_iter().map(|(t, _)| t).collect(),
+        next_cursor: page.next_cursor,
+    })
 }
 
 pub fn list_tickets(conn: &Connection) -> Result<Vec<Ticket>, AppError> {
-    list_tickets_filtered(
-        conn,
-        &ListFilter {
-            status: None,
-            claimed: None,
-            claimer: None,
-            search: vec![],
-        },
-    )
+    list_tickets_filtered(conn, &ListFilter::default())
 }
 
 #[derive(Serialize)]
@@ -309,51 +1147,325 @@ pub struct TicketExport {
     pub id: i64,
     pub name: String,
     pub description: String,
-    pub status: String,
+    pub status: Status,
+    pub priority: i64,
+    pub parent_id: Option<i64>,
     pub claimed_by: Option<String>,
+    pub claimed_at: Option<String>,
+    pub lease_expires_at: Option<String>,
+    /// Only set while the ticket is blocked.
+    pub block_reason: Option<String>,
     pub dependencies: Vec<i64>,
+    pub labels: Vec<String>,
+    pub ready: bool,
+    pub waiting: bool,
+    pub blocking: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    pub notes: Vec<TicketNote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_key: Option<String>,
 }
 
 pub fn tickets_to_export(
     conn: &Connection,
     filter: &ListFilter,
 ) -> Result<Vec<TicketExport>, AppError> {
-    let tickets = list_tickets_filtered(conn, filter)?;
-    tickets
+    export_page(conn, filter).map(|page| page.tickets)
+}
+
+/// Paged JSON export: `{"tickets": [...], "next_cursor": 42}`.
+#[derive(Serialize)]
+pub struct ExportPage {
+    pub tickets: Vec<TicketExport>,
+    pub next_cursor: Option<i64>,
+}
+
+pub fn export_page(conn: &Connection, filter: &ListFilter) -> Result<ExportPage, AppError> {
+    let page = list_flagged_page(conn, filter)?;
+    let now = chrono_free_utc_now();
+    let tickets = page
+        .tickets
         .into_iter()
-        .map(|t| {
-            let deps = list_deps(conn, t.id)?;
-            Ok(TicketExport {
-                id: t.id,
-                name: t.name,
-                description: t.description,
-                status: t.status,
-                claimed_by: t.claimed_by,
-                dependencies: deps.forward,
-            })
-        })
-        .collect()
+        .map(|(t, flags)| ticket_to_export(conn, t, flags, &now))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ExportPage {
+        tickets,
+        next_cursor: page.next_cursor,
+    })
+}
+
+/// Call `f` with each matching ticket's export as its row is read, without
+/// collecting the result set. Pagination applies, but no cursor is reported.
+pub fn for_each_export(
+    conn: &Connection,
+    filter: &ListFilter,
+    mut f: impl FnMut(TicketExport) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let limit = filter
+        .limit
+        .and_then(|l| i64::try_from(l).ok())
+        .unwrap_or(-1);
+    let (sql, params) = list_query(conn, filter, limit)?;
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(param_refs.as_slice())?;
+    let now = chrono_free_utc_now();
+    while let Some(row) = rows.next()? {
+        let (t, flags) = flagged_ticket_from_row(row)?;
+        f(ticket_to_export(conn, t, flags, &now)?)?;
+    }
+    Ok(())
+}
+
+/// Build the export view of a single ticket with its computed `flags`.
+/// Claims with an expired lease (relative to `now`) are reported as unclaimed.
+pub fn ticket_to_export(
+    conn: &Connection,
+    t: Ticket,
+    flags: TicketFlags,
+    now: &str,
+) -> Result<TicketExport, AppError> {
+    let deps = list_deps(conn, t.id)?;
+    let notes = list_notes(conn, t.id, None)?;
+    let (claimed_by, claimed_at, lease_expires_at) = if t.lease_expired(now) {
+        (None, None, None)
+    } else {
+        (t.claimed_by, t.claimed_at, t.lease_expires_at)
+    };
+    let block_reason = if t.status == "blocked" {
+        t.block_reason
+    } else {
+        None
+    };
+    Ok(TicketExport {
+        id: t.id,
+        name: t.name,
+        description: t.description,
+        status: t.status,
+        priority: t.priority,
+        parent_id: t.parent_id,
+        claimed_by,
+        claimed_at,
+        lease_expires_at,
+        block_reason,
+        dependencies: deps.forward,
+        labels: list_labels(conn, t.id)?,
+        ready: flags.ready,
+        waiting: flags.waiting,
+        blocking: flags.blocking,
+        created_at: t.created_at,
+        updated_at: t.updated_at,
+        notes,
+        external_key: t.external_key,
+    })
 }
 
 pub fn format_export_text(t: &TicketExport) -> String {
+    format_export_text_with(t, &IdPrefix::default())
+}
+
+/// `format_export_text` with ids in the project's `id_prefix`.
+pub fn format_export_text_with(t: &TicketExport, ids: &IdPrefix) -> String {
     let deps_suffix = if t.dependencies.is_empty() {
         String::new()
     } else {
         let dep_ids = t
             .dependencies
             .iter()
-            .map(|d| format!("T-{}", d))
+            .map(|&d| ids.show_export(d))
             .collect::<Vec<_>>()
             .join(",");
         format!(" deps:{}", dep_ids)
     };
-    format!("T-{} [{}] {}{}", t.id, t.status, t.name, deps_suffix)
+    // Priority only appears when it differs from the default, to save tokens.
+    let priority = if t.priority == DEFAULT_PRIORITY {
+        String::new()
+    } else {
+        format!(" P{}", t.priority)
+    };
+    let parent_suffix = match t.parent_id {
+        Some(p) => format!(" parent:{}", ids.show_export(p)),
+        None => String::new(),
+    };
+    let labels_suffix: String = t.labels.iter().map(|l| format!(" #{}", l)).collect();
+    let flags = TicketFlags {
+        ready: t.ready,
+        waiting: t.waiting,
+        blocking: t.blocking,
+    };
+    format!(
+        "{} [{}]{} {}{}{}{}{}",
+        ids.show_export(t.id),
+        t.status,
+        priority,
+        t.name,
+        parent_suffix,
+        deps_suffix,
+        labels_suffix,
+        format_flags(flags)
+    )
+}
+
+/// Compact virtual-state marks, e.g. " +ready +blocking".
+pub fn format_flags(flags: TicketFlags) -> String {
+    let mut out = String::new();
+    if flags.ready {
+        out.push_str(" +ready");
+    }
+    if flags.waiting {
+        out.push_str(" +waiting");
+    }
+    if flags.blocking {
+        out.push_str(" +blocking");
+    }
+    out
+}
+
+/// What `delete_ticket_with` does when the ticket has subtasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChildPolicy {
+    /// Fail with `HasChildren`.
+    #[default]
+    Refuse,
+    /// Delete the whole subtree.
+    Cascade,
+    /// Detach the direct children, making them top-level tickets.
+    Orphan,
 }
 
 pub fn delete_ticket(conn: &Connection, id: i64) -> Result<(), AppError> {
-    match conn.execute("DELETE FROM tickets WHERE id = ?1", rusqlite::params![id])? {
-        0 => Err(AppError::NotFound(id)),
-        _ => Ok(()),
+    delete_ticket_with(conn, id, ChildPolicy::Refuse, None)
+}
+
+pub fn delete_ticket_with(
+    conn: &Connection,
+    id: i64,
+    policy: ChildPolicy,
+    actor: Option<&str>,
+) -> Result<(), AppError> {
+    in_tx(conn, |conn| {
+        let t = get_ticket(conn, id)?;
+        let children = child_ids(conn, id)?;
+        let now = chrono_free_utc_now();
+        if !children.is_empty() {
+            match policy {
+                ChildPolicy::Refuse => {
+                    return Err(AppError::HasChildren(id, children.len() as i64))
+                }
+                ChildPolicy::Cascade => {
+                    for child in children {
+                        delete_ticket_with(conn, child, ChildPolicy::Cascade, actor)?;
+                    }
+                }
+                ChildPolicy::Orphan => {
+                    conn.execute(
+                        "UPDATE tickets SET parent_id = NULL, updated_at = ?1 WHERE parent_id = ?2",
+                        rusqlite::params![now, id],
+                    )?;
+                    for child in children {
+                        record_event(
+                            conn,
+                            child,
+                            actor,
+                            "parent",
+                            Some(&id.to_string()),
+                            None,
+                            &now,
+                        )?;
+                    }
+                }
+            }
+        }
+        conn.execute("DELETE FROM tickets WHERE id = ?1", rusqlite::params![id])?;
+        record_event(conn, id, actor, "deleted", Some(&t.name), None, &now)?;
+        Ok(())
+    })
+}
+
+fn child_ids(conn: &Connection, parent: i64) -> Result<Vec<i64>, AppError> {
+    let mut stmt = conn.prepare("SELECT id FROM tickets WHERE parent_id = ?1 ORDER BY id")?;
+    let ids = stmt
+        .query_map(rusqlite::params![parent], |r| r.get(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(ids)
+}
+
+pub struct TreeNode {
+    pub ticket: Ticket,
+    pub children: Vec<TreeNode>,
+    /// Whether the ticket's status is done in the workflow it was loaded under.
+    pub done: bool,
+}
+
+impl TreeNode {
+    /// (done, total) over all descendants, not counting this node.
+    pub fn rollup(&self) -> (usize, usize) {
+        self.children.iter().fold((0, 0), |(done, total), c| {
+            let (d, t) = c.rollup();
+            let own = usize::from(c.done);
+            (done + d + own, total + t + 1)
+        })
+    }
+}
+
+/// Load the subtask hierarchy rooted at `id`.
+pub fn ticket_tree(conn: &Connection, id: i64) -> Result<TreeNode, AppError> {
+    let root = get_ticket(conn, id)?;
+    let all = list_tickets(conn)?;
+    let mut by_parent: HashMap<i64, Vec<Ticket>> = HashMap::new();
+    for t in all {
+        if let Some(p) = t.parent_id {
+            by_parent.entry(p).or_default().push(t);
+        }
+    }
+    let workflow = Workflow::of(conn)?;
+    let mut visited = HashSet::new();
+    Ok(build_tree(root, &workflow, &mut by_parent, &mut visited))
+}
+
+fn build_tree(
+    ticket: Ticket,
+    workflow: &Workflow,
+    by_parent: &mut HashMap<i64, Vec<Ticket>>,
+    visited: &mut HashSet<i64>,
+) -> TreeNode {
+    visited.insert(ticket.id);
+    let kids = by_parent.remove(&ticket.id).unwrap_or_default();
+    let mut children = Vec::new();
+    for kid in kids {
+        if !visited.contains(&kid.id) {
+            children.push(build_tree(kid, workflow, by_parent, visited));
+        }
+    }
+    let done = workflow.is_done(&ticket.status);
+    TreeNode {
+        ticket,
+        children,
+        done,
+    }
+}
+
+/// Fields to change in `update_ticket_with`; `None` leaves a field untouched.
+#[derive(Default)]
+pub struct TicketUpdate<'a> {
+    pub name: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub status: Option<Status>,
+    pub priority: Option<i64>,
+    pub add_labels: &'a [String],
+    pub remove_labels: &'a [String],
+}
+
+impl TicketUpdate<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.description.is_none()
+            && self.status.is_none()
+            && self.priority.is_none()
+            && self.add_labels.is_empty()
+            && self.remove_labels.is_empty()
     }
 }
 
@@ -364,61 +1476,341 @@ pub fn update_ticket(
     desc: Option<&str>,
     status: Option<&str>,
 ) -> Result<String, AppError> {
-    if name.is_none() && desc.is_none() && status.is_none() {
+    update_ticket_with(
+        conn,
+        id,
+        &TicketUpdate {
+            name,
+            description: desc,
+            status: status.map(str::parse).transpose()?,
+            ..Default::default()
+        },
+        None,
+    )
+}
+
+pub fn update_ticket_with(
+    conn: &Connection,
+    id: i64,
+    update: &TicketUpdate,
+    actor: Option<&str>,
+) -> Result<String, AppError> {
+    if update.is_empty() {
         return Err(AppError::NoUpdateFields);
     }
-    let now = chrono_free_utc_now();
-    // Normalize status outside any if-let so the String lives long enough for params.
-    let normalized_status: Option<String> = status.map(|s| s.to_lowercase());
-
-    // Validate transition before building SET clause.
-    if let Some(ref ns) = normalized_status {
-        let current_status: String = conn
-            .query_row(
-                "SELECT status FROM tickets WHERE id = ?1",
-                rusqlite::params![id],
-                |row| row.get(0),
-            )
-            .map_err(|e| match e {
-                rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(id),
-                other => AppError::Db(other),
-            })?;
-        validate_transition(&current_status, ns)?;
+    if let Some(p) = update.priority {
+        validate_priority(p)?;
     }
+    let name = update.name;
+    let desc = update.description;
+    in_tx(conn, |conn| {
+        let now = chrono_free_utc_now();
+        let current = get_ticket(conn, id)?;
+
+        // Validate transition before building SET clause.
+        let clears_claim = match update.status {
+            Some(ref ns) => {
+                let workflow = Workflow::of(conn)?;
+                workflow.check_transition(&current.status, ns)?;
+                workflow.check(ns)?.clears_claim
+            }
+            None => false,
+        };
+
+        let mut sets: Vec<&str> = Vec::new();
+        let mut params: Vec<(&str, &dyn rusqlite::types::ToSql)> = Vec::new();
+        if let Some(ref name) = name {
+            sets.push("name = :name");
+            params.push((":name", name));
+        }
+        if let Some(ref desc) = desc {
+            sets.push("description = :desc");
+            params.push((":desc", desc));
+        }
+        if let Some(ref priority) = update.priority {
+            sets.push("priority = :priority");
+            params.push((":priority", priority));
+        }
+        if let Some(ref ns) = update.status {
+            sets.push("status = :status");
+            params.push((":status", ns));
+            if clears_claim {
+                sets.push("claimed_by = :claimnil");
+                sets.push("claimed_at = :claimnil_at");
+                sets.push("lease_expires_at = :claimnil_at");
+                sets.push("claim_prev_status = :claimnil_at");
+                params.push((":claimnil", &rusqlite::types::Null));
+                params.push((":claimnil_at", &rusqlite::types::Null));
+            }
+        }
+        sets.push("updated_at = :now");
+        params.push((":now", &now));
+        params.push((":id", &id));
+
+        let sql = format!("UPDATE tickets SET {} WHERE id = :id", sets.join(", "));
+        conn.execute(&sql, params.as_slice())?;
 
-    let mut sets: Vec<&str> = Vec::new();
-    let mut params: Vec<(&str, &dyn rusqlite::types::ToSql)> = Vec::new();
-    if let Some(ref name) = name {
-        sets.push("name = :name");
-        params.push((":name", name));
+        if let Some(name) = name.filter(|n| *n != current.name) {
+            record_event(
+                conn,
+                id,
+                actor,
+                "name",
+                Some(&current.name),
+                Some(name),
+                &now,
+            )?;
+        }
+        if let Some(desc) = desc.filter(|d| *d != current.description) {
+            record_event(
+                conn,
+                id,
+                actor,
+                "description",
+                Some(&current.description),
+                Some(desc),
+                &now,
+            )?;
+        }
+        if let Some(ref ns) = update.status {
+            record_event(
+                conn,
+                id,
+                actor,
+                "status",
+                Some(current.status.as_str()),
+                Some(ns.as_str()),
+                &now,
+            )?;
+            if clears_claim && current.claimed_by.is_some() {
+                record_event(
+                    conn,
+                    id,
+                    actor,
+                    "claim",
+                    current.claimed_by.as_deref(),
+                    None,
+                    &now,
+                )?;
+            }
+        }
+
+        apply_labels(
+            conn,
+            id,
+            update.add_labels,
+            update.remove_labels,
+            actor,
+            &now,
+        )?;
+
+        Ok(name.map(str::to_string).unwrap_or(current.name))
+    })
+}
+
+/// Labels are stored lowercase so `Frontend` and `frontend` are one label.
+pub(crate) fn normalize_label(label: &str) -> String {
+    label.trim().to_lowercase()
+}
+
+fn validate_label(label: &str) -> Result<String, AppError> {
+    let normalized = normalize_label(label);
+    if normalized.is_empty()
+        || normalized
+            .chars()
+            .any(|c| c.is_whitespace() || c == ',' || c == '#')
+    {
+        return Err(AppError::InvalidLabel(label.to_string()));
     }
-    if let Some(ref desc) = desc {
-        sets.push("description = :desc");
-        params.push((":desc", desc));
+    Ok(normalized)
+}
+
+fn apply_labels(
+    conn: &Connection,
+    ticket_id: i64,
+    add: &[String],
+    remove: &[String],
+    actor: Option<&str>,
+    now: &str,
+) -> Result<(), AppError> {
+    for label in add {
+        let label = validate_label(label)?;
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO ticket_labels (ticket_id, label) VALUES (?1, ?2)",
+            rusqlite::params![ticket_id, label],
+        )?;
+        if inserted > 0 {
+            record_event(
+                conn,
+                ticket_id,
+                actor,
+                "label_added",
+                None,
+                Some(&label),
+                now,
+            )?;
+        }
     }
-    if let Some(ref ns) = normalized_status {
-        sets.push("status = :status");
-        params.push((":status", ns));
-        if ns == "done" {
-            sets.push("claimed_by = :claimnil");
-            sets.push("claimed_at = :claimnil_at");
-            params.push((":claimnil", &rusqlite::types::Null));
-            params.push((":claimnil_at", &rusqlite::types::Null));
+    for label in remove {
+        let label = normalize_label(label);
+        let deleted = conn.execute(
+            "DELETE FROM ticket_labels WHERE ticket_id = ?1 AND label = ?2",
+            rusqlite::params![ticket_id, label],
+        )?;
+        if deleted > 0 {
+            record_event(
+                conn,
+                ticket_id,
+                actor,
+                "label_removed",
+                Some(&label),
+                None,
+                now,
+            )?;
         }
     }
-    sets.push("updated_at = :now");
-    params.push((":now", &now));
-    params.push((":id", &id));
+    Ok(())
+}
 
-    let sql = format!("UPDATE tickets SET {} WHERE id = :id", sets.join(", "));
-    let affected = conn.execute(&sql, params.as_slice())?;
-    match affected {
-        0 => Err(AppError::NotFound(id)),
-        _ => {
-            let t = get_ticket(conn, id)?;
-            Ok(t.name)
-        }
+pub fn list_labels(conn: &Connection, ticket_id: i64) -> Result<Vec<String>, AppError> {
+    let mut stmt =
+        conn.prepare("SELECT label FROM ticket_labels WHERE ticket_id = ?1 ORDER BY label")?;
+    let labels = stmt
+        .query_map(rusqlite::params![ticket_id], |r| r.get(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(labels)
+}
+
+/// Every ticket's labels, alphabetically; unlabelled tickets are absent.
+pub fn labels_by_ticket(conn: &Connection) -> Result<HashMap<i64, Vec<String>>, AppError> {
+    let mut stmt = conn.prepare("SELECT ticket_id, label FROM ticket_labels ORDER BY label")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+    let mut map: HashMap<i64, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (id, label) = row?;
+        map.entry(id).or_default().push(label);
+    }
+    Ok(map)
+}
+
+/// Every label in use with the number of tickets carrying it, alphabetically.
+pub fn label_counts(conn: &Connection) -> Result<Vec<(String, i64)>, AppError> {
+    let mut stmt =
+        conn.prepare("SELECT label, COUNT(*) FROM ticket_labels GROUP BY label ORDER BY label")?;
+    let counts = stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(counts)
+}
+
+#[derive(Debug, Serialize)]
+pub struct TicketNote {
+    pub author: Option<String>,
+    pub body: String,
+    pub created_at: String,
+}
+
+/// Append a work note to a ticket. Returns the note id.
+pub fn add_note(
+    conn: &Connection,
+    ticket_id: i64,
+    author: Option<&str>,
+    body: &str,
+) -> Result<i64, AppError> {
+    if body.trim().is_empty() {
+        return Err(AppError::NoteRequired);
     }
+    in_tx(conn, |conn| {
+        get_ticket(conn, ticket_id)?;
+        let now = chrono_free_utc_now();
+        conn.execute(
+            "INSERT INTO ticket_notes (ticket_id, author, body, created_at) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![ticket_id, author, body, now],
+        )?;
+        let note_id = conn.last_insert_rowid();
+        record_event(conn, ticket_id, author, "note", None, Some(body), &now)?;
+        Ok(note_id)
+    })
+}
+
+/// Notes on a ticket, oldest first. With `limit`, only the most recent
+/// `limit` notes are returned (still oldest first).
+pub fn list_notes(
+    conn: &Connection,
+    ticket_id: i64,
+    limit: Option<usize>,
+) -> Result<Vec<TicketNote>, AppError> {
+    let limit = limit.map(|l| l as i64).unwrap_or(-1);
+    let mut stmt = conn.prepare(
+        "SELECT author, body, created_at FROM (
+             SELECT id, author, body, created_at FROM ticket_notes
+             WHERE ticket_id = ?1 ORDER BY id DESC LIMIT ?2
+         ) ORDER BY id ASC",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![ticket_id, limit], |r| {
+        Ok(TicketNote {
+            author: r.get(0)?,
+            body: r.get(1)?,
+            created_at: r.get(2)?,
+        })
+    })?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(AppError::Db)
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub id: i64,
+    pub name: String,
+    pub status: Status,
+    /// bm25 score; lower is more relevant.
+    pub rank: f64,
+    /// Best-matching fragment with hits wrapped in `[` `]`.
+    pub snippet: String,
+}
+
+/// Full-text search over name, description and notes using FTS5 query syntax
+/// (`"exact phrase"`, `prefix*`, `a NOT b`, `name:term`). Name hits weigh
+/// more than description hits, which weigh more than note hits.
+pub fn search_tickets(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchHit>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.name, t.status,
+                bm25(ticket_fts, 10.0, 4.0, 1.0) AS rank,
+                snippet(ticket_fts, -1, '[', ']', '…', 12)
+         FROM ticket_fts JOIN tickets t ON t.id = ticket_fts.rowid
+         WHERE ticket_fts MATCH ?1
+         ORDER BY rank, t.id
+         LIMIT ?2",
+    )?;
+    // Malformed MATCH expressions only fail once the statement runs, as a
+    // plain SQLITE_ERROR; busy or locked databases are still database errors.
+    let as_query_error = |e: rusqlite::Error| match e {
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::Unknown,
+                ..
+            },
+            Some(msg),
+        ) => AppError::InvalidSearch(msg.trim_start_matches("fts5: ").to_string()),
+        other => AppError::Db(other),
+    };
+    let rows = stmt
+        .query_map(rusqlite::params![query, limit as i64], |r| {
+            Ok(SearchHit {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                status: r.get(2)?,
+                rank: r.get(3)?,
+                snippet: r.get(4)?,
+            })
+        })
+        .map_err(as_query_error)?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(as_query_error)
 }
 
 pub struct DepInfo {
@@ -426,18 +1818,35 @@ pub struct DepInfo {
     pub reverse: Vec<i64>,
 }
 
-pub fn would_create_cycle(
-    conn: &Connection,
-    ticket_id: i64,
-    new_dep: i64,
-) -> Result<Option<Vec<i64>>, AppError> {
+/// Forward adjacency of `ticket_deps`: ticket -> the tickets it depends on,
+/// each list in ascending id order.
+pub fn load_dep_graph(conn: &Connection) -> Result<HashMap<i64, Vec<i64>>, AppError> {
     let mut adj: HashMap<i64, Vec<i64>> = HashMap::new();
-    let mut stmt = conn.prepare("SELECT ticket_id, depends_on FROM ticket_deps")?;
+    let mut stmt = conn
+        .prepare("SELECT ticket_id, depends_on FROM ticket_deps ORDER BY ticket_id, depends_on")?;
     let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, i64>(1)?)))?;
     for row in rows {
         let (from, to) = row?;
         adj.entry(from).or_default().push(to);
     }
+    Ok(adj)
+}
+
+/// How many tickets each ticket depends on; tickets with none are absent.
+pub fn dep_counts(conn: &Connection) -> Result<HashMap<i64, i64>, AppError> {
+    let mut stmt =
+        conn.prepare("SELECT ticket_id, COUNT(*) FROM ticket_deps GROUP BY ticket_id")?;
+    let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+    rows.collect::<rusqlite::Result<HashMap<_, _>>>()
+        .map_err(AppError::Db)
+}
+
+pub fn would_create_cycle(
+    conn: &Connection,
+    ticket_id: i64,
+    new_dep: i64,
+) -> Result<Option<Vec<i64>>, AppError> {
+    let mut adj = load_dep_graph(conn)?;
     adj.entry(ticket_id).or_default().push(new_dep);
 
     let mut visited: HashSet<i64> = HashSet::new();
@@ -474,7 +1883,12 @@ fn dfs_finds_target(
     false
 }
 
-pub fn add_dep(conn: &Connection, ticket_id: i64, depends_on: i64) -> Result<(), AppError> {
+pub fn add_dep(
+    conn: &Connection,
+    ticket_id: i64,
+    depends_on: i64,
+    actor: Option<&str>,
+) -> Result<(), AppError> {
     get_ticket(conn, ticket_id)?;
     get_ticket(conn, depends_on)?;
     if ticket_id == depends_on {
@@ -491,22 +1905,51 @@ pub fn add_dep(conn: &Connection, ticket_id: i64, depends_on: i64) -> Result<(),
             .join(" → ");
         return Err(AppError::CyclicDependency(cycle_str));
     }
-    conn.execute(
-        "INSERT INTO ticket_deps (ticket_id, depends_on) VALUES (?1, ?2)",
-        rusqlite::params![ticket_id, depends_on],
-    )?;
-    Ok(())
+    in_tx(conn, |conn| {
+        conn.execute(
+            "INSERT INTO ticket_deps (ticket_id, depends_on) VALUES (?1, ?2)",
+            rusqlite::params![ticket_id, depends_on],
+        )?;
+        let now = chrono_free_utc_now();
+        record_event(
+            conn,
+            ticket_id,
+            actor,
+            "dep_added",
+            None,
+            Some(&depends_on.to_string()),
+            &now,
+        )?;
+        Ok(())
+    })
 }
 
-pub fn remove_dep(conn: &Connection, ticket_id: i64, depends_on: i64) -> Result<(), AppError> {
-    conn.execute(
-        "DELETE FROM ticket_deps WHERE ticket_id=?1 AND depends_on=?2",
-        rusqlite::params![ticket_id, depends_on],
-    )?;
-    if conn.changes() == 0 {
-        return Err(AppError::DepNotFound(ticket_id, depends_on));
-    }
-    Ok(())
+pub fn remove_dep(
+    conn: &Connection,
+    ticket_id: i64,
+    depends_on: i64,
+    actor: Option<&str>,
+) -> Result<(), AppError> {
+    in_tx(conn, |conn| {
+        conn.execute(
+            "DELETE FROM ticket_deps WHERE ticket_id=?1 AND depends_on=?2",
+            rusqlite::params![ticket_id, depends_on],
+        )?;
+        if conn.changes() == 0 {
+            return Err(AppError::DepNotFound(ticket_id, depends_on));
+        }
+        let now = chrono_free_utc_now();
+        record_event(
+            conn,
+            ticket_id,
+            actor,
+            "dep_removed",
+            Some(&depends_on.to_string()),
+            None,
+            &now,
+        )?;
+        Ok(())
+    })
 }
 
 pub fn list_deps(conn: &Connection, ticket_id: i64) -> Result<DepInfo, AppError> {
@@ -525,12 +1968,119 @@ pub fn list_deps(conn: &Connection, ticket_id: i64) -> Result<DepInfo, AppError>
     Ok(DepInfo { forward, reverse })
 }
 
-fn chrono_free_utc_now() -> String {
+/// One row of the append-only `ticket_events` history.
+#[derive(Serialize)]
+pub struct TicketEvent {
+    pub id: i64,
+    pub ticket_id: i64,
+    pub actor: Option<String>,
+    pub kind: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub created_at: String,
+}
+
+fn event_from_row(r: &rusqlite::Row) -> rusqlite::Result<TicketEvent> {
+    Ok(TicketEvent {
+        id: r.get(0)?,
+        ticket_id: r.get(1)?,
+        actor: r.get(2)?,
+        kind: r.get(3)?,
+        old_value: r.get(4)?,
+        new_value: r.get(5)?,
+        created_at: r.get(6)?,
+    })
+}
+
+/// Run `f` inside an IMMEDIATE transaction, or inside the caller's transaction
+/// if one is already open, so a mutation and its events commit together.
+pub(crate) fn in_tx<T>(
+    conn: &Connection,
+    f: impl FnOnce(&Connection) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    if !conn.is_autocommit() {
+        return f(conn);
+    }
+    let tx = rusqlite::Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let out = f(&tx)?;
+    tx.commit()?;
+    Ok(out)
+}
+
+pub(crate) fn record_event(
+    conn: &Connection,
+    ticket_id: i64,
+    actor: Option<&str>,
+    kind: &str,
+    old_value: Option<&str>,
+    new_value: Option<&str>,
+    at: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO ticket_events (ticket_id, actor, kind, old_value, new_value, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![ticket_id, actor, kind, old_value, new_value, at],
+    )?;
+    Ok(())
+}
+
+/// Events in chronological order, optionally limited to one ticket and/or to
+/// events at or after `since` (an ISO 8601 timestamp or date prefix).
+pub fn list_events(
+    conn: &Connection,
+    ticket_id: Option<i64>,
+    since: Option<&str>,
+) -> Result<Vec<TicketEvent>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, ticket_id, actor, kind, old_value, new_value, created_at
+         FROM ticket_events
+         WHERE (?1 IS NULL OR ticket_id = ?1) AND (?2 IS NULL OR created_at >= ?2)
+         ORDER BY id ASC",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![ticket_id, since], event_from_row)?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(AppError::Db)
+}
+
+/// Events with an id greater than `after_id`, oldest first, optionally for
+/// one ticket. Event ids only grow, so the last id seen works as a cursor.
+pub fn list_events_after(
+    conn: &Connection,
+    after_id: i64,
+    ticket_id: Option<i64>,
+) -> Result<Vec<TicketEvent>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, ticket_id, actor, kind, old_value, new_value, created_at
+         FROM ticket_events
+         WHERE id > ?1 AND (?2 IS NULL OR ticket_id = ?2)
+         ORDER BY id ASC",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![after_id, ticket_id], event_from_row)?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(AppError::Db)
+}
+
+pub(crate) fn chrono_free_utc_now() -> String {
+    utc_now_plus(0)
+}
+
+fn utc_now_plus(offset_secs: u64) -> String {
+    format_utc(unix_now() + offset_secs.min(MAX_DURATION_SECS))
+}
+
+pub(crate) fn utc_now_minus(offset_secs: u64) -> String {
+    format_utc(unix_now().saturating_sub(offset_secs))
+}
+
+fn unix_now() -> u64 {
     use std::time::{SystemTime, UNIX_EPOCH};
-    let secs = SystemTime::now()
+    SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("time before epoch")
-        .as_secs();
+        .as_secs()
+}
+
+fn format_utc(secs: u64) -> String {
     let (y, mo, d, h, mi, s) = seconds_to_datetime(secs);
     format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s)
 }
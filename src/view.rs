@@ -0,0 +1,110 @@
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::ticket::{chrono_free_utc_now, AppError};
+
+/// A named set of `list`/`export` flags, stored as the literal arguments so a
+/// view can hold anything those commands accept (filters, sort, columns,
+/// format).
+#[derive(Debug, Serialize)]
+pub struct View {
+    pub name: String,
+    pub args: Vec<String>,
+    pub created_by: Option<String>,
+    pub updated_at: String,
+}
+
+fn validate_view_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InvalidViewName(name.to_string()))
+    }
+}
+
+/// Create or replace a view, created by `actor`. Returns true if an
+/// existing view was replaced.
+pub fn save_view(
+    conn: &Connection,
+    name: &str,
+    args: &[String],
+    actor: Option<&str>,
+) -> Result<bool, AppError> {
+    validate_view_name(name)?;
+    let now = chrono_free_utc_now();
+    let json = serde_json::to_string(args).expect("serialize");
+    let existed = get_view(conn, name).is_ok();
+    conn.execute(
+        "INSERT INTO views (name, args, created_by, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT(name) DO UPDATE SET args = excluded.args, updated_at = excluded.updated_at",
+        rusqlite::params![name, json, actor, now],
+    )?;
+    Ok(existed)
+}
+
+fn view_from_row(r: &rusqlite::Row) -> rusqlite::Result<View> {
+    let json: String = r.get(1)?;
+    Ok(View {
+        name: r.get(0)?,
+        args: serde_json::from_str(&json).unwrap_or_default(),
+        created_by: r.get(2)?,
+        updated_at: r.get(3)?,
+    })
+}
+
+pub fn get_view(conn: &Connection, name: &str) -> Result<View, AppError> {
+    conn.query_row(
+        "SELECT name, args, created_by, updated_at FROM views WHERE name = ?1",
+        rusqlite::params![name],
+        view_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::ViewNotFound(name.to_string()),
+        other => AppError::Db(other),
+    })
+}
+
+pub fn list_views(conn: &Connection) -> Result<Vec<View>, AppError> {
+    let mut stmt =
+        conn.prepare("SELECT name, args, created_by, updated_at FROM views ORDER BY name")?;
+    let rows = stmt.query_map([], view_from_row)?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(AppError::Db)
+}
+
+pub fn delete_view(conn: &Connection, name: &str) -> Result<(), AppError> {
+    let n = conn.execute("DELETE FROM views WHERE name = ?1", rusqlite::params![name])?;
+    if n == 0 {
+        return Err(AppError::ViewNotFound(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Position of an `@name` view reference in a command line: the argument
+/// directly after `list`/`ls`/`export`/`dump`.
+pub fn view_ref(args: &[String]) -> Option<usize> {
+    let cmd = args.iter().skip(1).position(|a| !a.starts_with('-'))? + 1;
+    if !matches!(args[cmd].as_str(), "list" | "ls" | "export" | "dump") {
+        return None;
+    }
+    match args.get(cmd + 1) {
+        Some(a) if a.starts_with('@') => Some(cmd + 1),
+        _ => None,
+    }
+}
+
+/// Replace an `@name` reference with the view's saved arguments. Flags given
+/// after the reference follow the saved ones, so they refine or override it.
+pub fn expand_view(conn: &Connection, mut args: Vec<String>) -> Result<Vec<String>, AppError> {
+    if let Some(pos) = view_ref(&args) {
+        let view = get_view(conn, &args[pos][1..])?;
+        args.splice(pos..=pos, view.args);
+    }
+    Ok(args)
+}
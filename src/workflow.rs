@@ -0,0 +1,317 @@
+//! The status workflow: which statuses exist, where each may move next, and
+//! how a status treats claims and dependencies. The default is the built-in
+//! graph; a project adds statuses or changes transitions in `.rtik.toml`:
+//!
+//! ```toml
+//! [workflow]
+//! claim = "in-progress"   # where claim and next move a ticket
+//! release = "todo"        # where release and reap return it; next picks from here
+//!
+//! [[workflow.status]]
+//! name = "in-progress"
+//! next = ["review", "blocked", "todo"]
+//!
+//! [[workflow.status]]
+//! name = "review"
+//! next = ["done", "in-progress"]
+//! clears_claim = true
+//! ```
+//!
+//! `db::open_connection` installs the workflow on the connection as a temp
+//! table, and every check reads it from there: `Workflow::of` in Rust and
+//! `temp.workflow_statuses` in SQL.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::model::Status;
+use crate::ticket::AppError;
+
+/// One status and its rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusDef {
+    pub status: Status,
+    /// Statuses a ticket may move to from this one with `update`.
+    pub next: Vec<Status>,
+    /// Moving into this status releases the ticket's claim.
+    pub clears_claim: bool,
+    /// Tickets in this status satisfy the dependencies on them.
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workflow {
+    statuses: Vec<StatusDef>,
+    claim: Status,
+    release: Status,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        use Status::*;
+        let def = |status, next: &[Status], done| StatusDef {
+            status,
+            next: next.to_vec(),
+            clears_claim: done,
+            done,
+        };
+        Workflow {
+            statuses: vec![
+                def(Todo, &[InProgress, Blocked], false),
+                def(InProgress, &[Done, Blocked, Todo], false),
+                def(Blocked, &[InProgress, Todo], false),
+                def(Done, &[InProgress], true),
+            ],
+            claim: InProgress,
+            release: Todo,
+        }
+    }
+}
+
+/// The `[workflow]` section of `.rtik.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowConfig {
+    pub claim: Option<String>,
+    pub release: Option<String>,
+    #[serde(default)]
+    pub status: Vec<StatusConfig>,
+}
+
+/// One `[[workflow.status]]` entry. Unset fields keep the built-in value,
+/// or default to no transitions and no flags for a new status.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusConfig {
+    pub name: String,
+    pub next: Option<Vec<String>>,
+    pub clears_claim: Option<bool>,
+    pub done: Option<bool>,
+}
+
+fn parse_name(raw: &str) -> Result<Status, AppError> {
+    raw.parse()
+        .map_err(|e: AppError| AppError::InvalidConfig(format!("workflow: {}", e)))
+}
+
+impl Workflow {
+    /// The default workflow with `config` applied. An entry naming a
+    /// built-in status changes it; any other name adds a status, listed
+    /// before `done`.
+    pub fn from_config(config: &WorkflowConfig) -> Result<Workflow, AppError> {
+        let mut wf = Workflow::default();
+        let mut seen = Vec::new();
+        for entry in &config.status {
+            let status = parse_name(&entry.name)?;
+            if seen.contains(&status) {
+                return Err(AppError::InvalidConfig(format!(
+                    "workflow: status '{}' is listed twice",
+                    status
+                )));
+            }
+            seen.push(status.clone());
+            let idx = match wf.statuses.iter().position(|d| d.status == status) {
+                Some(idx) => idx,
+                None => {
+                    let before_done = wf.statuses.len() - 1;
+                    wf.statuses.insert(
+                        before_done,
+                        StatusDef {
+                            status,
+                            next: Vec::new(),
+                            clears_claim: false,
+                            done: false,
+                        },
+                    );
+                    before_done
+                }
+            };
+            let def = &mut wf.statuses[idx];
+            if let Some(next) = &entry.next {
+                def.next = next
+                    .iter()
+                    .map(|n| parse_name(n))
+                    .collect::<Result<_, _>>()?;
+            }
+            if let Some(clears_claim) = entry.clears_claim {
+                def.clears_claim = clears_claim;
+            }
+            if let Some(done) = entry.done {
+                def.done = done;
+            }
+        }
+        for def in &wf.statuses {
+            if let Some(unknown) = def.next.iter().find(|n| wf.get(n).is_none()) {
+                return Err(AppError::InvalidConfig(format!(
+                    "workflow: status '{}' lists unknown next status '{}'",
+                    def.status, unknown
+                )));
+            }
+        }
+        let pick = |key: &str, raw: &Option<String>, default: &Status| match raw {
+            None => Ok(default.clone()),
+            Some(raw) => {
+                let status = parse_name(raw)?;
+                match wf.get(&status) {
+                    Some(_) => Ok(status),
+                    None => Err(AppError::InvalidConfig(format!(
+                        "workflow: {} status '{}' is not in the workflow",
+                        key, status
+                    ))),
+                }
+            }
+        };
+        let claim = pick("claim", &config.claim, &wf.claim)?;
+        let release = pick("release", &config.release, &wf.release)?;
+        wf.claim = claim;
+        wf.release = release;
+        Ok(wf)
+    }
+
+    /// The status `claim` and `next` move a ticket into.
+    pub fn claim(&self) -> &Status {
+        &self.claim
+    }
+
+    /// The status `release` and `reap` return a ticket to; `next` and the
+    /// ready state pick tickets in it.
+    pub fn release(&self) -> &Status {
+        &self.release
+    }
+
+    /// Every status, in workflow order.
+    pub fn statuses(&self) -> &[StatusDef] {
+        &self.statuses
+    }
+
+    pub fn get(&self, status: &Status) -> Option<&StatusDef> {
+        self.statuses.iter().find(|d| d.status == *status)
+    }
+
+    /// `todo, in-progress, ...`, for error messages.
+    pub fn names(&self) -> String {
+        self.statuses
+            .iter()
+            .map(|d| d.status.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The rules for `status`, or `InvalidStatus` if the workflow lacks it.
+    pub fn check(&self, status: &Status) -> Result<&StatusDef, AppError> {
+        self.get(status).ok_or_else(|| {
+            AppError::InvalidStatus(
+                status.to_string(),
+                format!("must be one of {}", self.names()),
+            )
+        })
+    }
+
+    /// Whether `update` may move a ticket from `from` to `to`. A ticket left
+    /// in a status the workflow no longer has may move anywhere.
+    pub fn check_transition(&self, from: &Status, to: &Status) -> Result<(), AppError> {
+        self.check(to)?;
+        let Some(def) = self.get(from) else {
+            return Ok(());
+        };
+        if def.next.contains(to) {
+            Ok(())
+        } else {
+            Err(AppError::InvalidTransition {
+                from: from.to_string(),
+                valid: def
+                    .next
+                    .iter()
+                    .map(Status::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            })
+        }
+    }
+
+    /// Whether tickets in `status` satisfy the dependencies on them.
+    pub fn is_done(&self, status: &Status) -> bool {
+        self.get(status).is_some_and(|d| d.done)
+    }
+
+    /// Make this the workflow of `conn`, replacing any installed before.
+    pub fn install(&self, conn: &Connection) -> Result<(), AppError> {
+        conn.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS workflow_statuses (
+                name         TEXT PRIMARY KEY,
+                position     INTEGER NOT NULL,
+                next         TEXT NOT NULL,
+                clears_claim INTEGER NOT NULL,
+                done         INTEGER NOT NULL,
+                claim        INTEGER NOT NULL,
+                release      INTEGER NOT NULL
+            );
+            DELETE FROM temp.workflow_statuses;",
+        )?;
+        let mut stmt = conn.prepare(
+            "INSERT INTO temp.workflow_statuses
+                 (name, position, next, clears_claim, done, claim, release)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for (position, def) in self.statuses.iter().enumerate() {
+            let next = def
+                .next
+                .iter()
+                .map(Status::as_str)
+                .collect::<Vec<_>>()
+                .join(",");
+            stmt.execute(rusqlite::params![
+                def.status,
+                position as i64,
+                next,
+                def.clears_claim,
+                def.done,
+                def.status == self.claim,
+                def.status == self.release
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// The workflow installed on `conn`.
+    pub fn of(conn: &Connection) -> Result<Workflow, AppError> {
+        let mut stmt = conn.prepare(
+            "SELECT name, next, clears_claim, done, claim, release
+             FROM temp.workflow_statuses ORDER BY position",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            let next: String = r.get(1)?;
+            Ok((
+                r.get::<_, Status>(0)?,
+                next,
+                r.get(2)?,
+                r.get(3)?,
+                r.get::<_, bool>(4)?,
+                r.get::<_, bool>(5)?,
+            ))
+        })?;
+        let mut wf = Workflow::default();
+        let mut statuses = Vec::new();
+        for row in rows {
+            let (status, next, clears_claim, done, claim, release) = row?;
+            if claim {
+                wf.claim = status.clone();
+            }
+            if release {
+                wf.release = status.clone();
+            }
+            statuses.push(StatusDef {
+                status,
+                next: next
+                    .split(',')
+                    .filter(|n| !n.is_empty())
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?,
+                clears_claim,
+                done,
+            });
+        }
+        wf.statuses = statuses;
+        Ok(wf)
+    }
+}
@@ -0,0 +1,153 @@
+use std::path::Path;
+use std::process::Command;
+
+fn rtik(db: &Path, args: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_rtik"))
+        .args(args)
+        .env("RTIK_DB", db)
+        .env_remove("RTIK_AGENT")
+        .env_remove("RTIK_OUTPUT")
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn json(db: &Path, args: &[&str]) -> serde_json::Value {
+    serde_json::from_str(&rtik(db, args)).unwrap()
+}
+
+#[test]
+fn json_listing_is_an_array_unless_paged() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("tickets.db");
+    for name in ["a", "b", "c"] {
+        rtik(&db, &["create", "--name", name]);
+    }
+
+    for command in ["list", "export"] {
+        let limited = json(&db, &[command, "--json", "--limit", "2"]);
+        assert_eq!(limited.as_array().unwrap().len(), 2, "{}", command);
+
+        let paged = json(&db, &[command, "--json", "--limit", "2", "--paged"]);
+        assert_eq!(paged["tickets"].as_array().unwrap().len(), 2, "{}", command);
+        assert_eq!(paged["next_cursor"], 2);
+        let last = json(
+            &db,
+            &[command, "--json", "--limit", "2", "--paged", "--after", "2"],
+        );
+        assert_eq!(last["tickets"][0]["id"], 3);
+        assert_eq!(last["next_cursor"], serde_json::Value::Null);
+    }
+}
+
+#[test]
+fn views_accept_export_only_flags() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("tickets.db");
+    rtik(&db, &["create", "--name", "Ship"]);
+    rtik(&db, &["note", "1", "first"]);
+    rtik(&db, &["note", "1", "second"]);
+
+    rtik(&db, &["view", "save", "brief", "--latest-note", "--json"]);
+    let exported = json(&db, &["export", "@brief"]);
+    let notes = exported[0]["notes"].as_array().unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0]["body"], "second");
+
+    rtik(&db, &["view", "save", "report", "--template", "label"]);
+    rtik(
+        &db,
+        &["view", "save", "wide", "--timestamps", "--status", "todo"],
+    );
+
+    let out = Command::new(env!("CARGO_BIN_EXE_rtik"))
+        .args(["view", "save", "bad", "--colour", "red", "--json"])
+        .env("RTIK_DB", &db)
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let err: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    assert_eq!(err["code"], "invalid_view_args");
+}
+
+#[test]
+fn list_table_marks_computed_states() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("tickets.db");
+    rtik(&db, &["create", "--name", "Schema"]);
+    rtik(&db, &["create", "--name", "API"]);
+    rtik(&db, &["dep", "add", "2", "1"]);
+
+    let table = rtik(&db, &["list", "--columns", "id,name"]);
+    let rows: Vec<&str> = table.lines().skip(2).collect();
+    assert!(rows[0].ends_with(" +ready +blocking"), "{table}");
+    assert!(rows[1].ends_with(" +waiting"), "{table}");
+}
+
+#[test]
+fn create_deps_and_tree_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("tickets.db");
+    assert_eq!(
+        rtik(&db, &["create", "--name", "Schema"]),
+        "Created: #1 Schema\n"
+    );
+    rtik(&db, &["create", "--name", "API", "--parent", "1"]);
+    rtik(&db, &["create", "--name", "Docs"]);
+    rtik(&db, &["dep", "add", "3", "2"]);
+
+    assert_eq!(rtik(&db, &["deps", "3"]), "Depends on: #2\n");
+    let deps = json(&db, &["deps", "2", "--json"]);
+    assert_eq!(deps["required_by"], serde_json::json!([3]));
+    assert_eq!(
+        rtik(&db, &["deps", "3", "--tree"]),
+        "#3 Docs [todo]\nDepends on:\n└── #2 API [todo]\n"
+    );
+    let graph = json(&db, &["deps", "--all", "--json"]);
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), 3);
+    assert_eq!(graph["edges"], serde_json::json!([{"from": 2, "to": 3}]));
+    assert!(rtik(&db, &["deps", "--all"]).contains("    t2 -> t3;\n"));
+
+    assert_eq!(
+        rtik(&db, &["tree", "1"]),
+        "#1 Schema [todo] (0/1 done)\n└── #2 API [todo]\n"
+    );
+}
+
+#[test]
+fn huge_limits_and_offsets_list_everything_or_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("tickets.db");
+    rtik(&db, &["create", "--name", "a"]);
+    rtik(&db, &["create", "--name", "b"]);
+
+    for limit in ["9223372036854775807", "18446744073709551615"] {
+        for command in ["list", "export"] {
+            let all = json(&db, &[command, "--json", "--limit", limit]);
+            assert_eq!(all.as_array().unwrap().len(), 2, "{command} {limit}");
+        }
+        let table = rtik(&db, &["list", "--limit", limit]);
+        assert_eq!(table.lines().count(), 4, "{table}");
+        let csv = rtik(&db, &["export", "--format", "csv", "--limit", limit]);
+        assert_eq!(csv.lines().count(), 3, "{csv}");
+    }
+    let none = json(&db, &["list", "--json", "--offset", "18446744073709551615"]);
+    assert_eq!(none, serde_json::json!([]));
+}
+
+#[test]
+fn huge_budget_exports_everything() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("tickets.db");
+    rtik(&db, &["create", "--name", "Only"]);
+    assert_eq!(
+        rtik(&db, &["export", "--budget", "18446744073709551615"]),
+        "T-1 [todo] Only +ready\n"
+    );
+    assert_eq!(rtik::budget::Budget::Tokens(usize::MAX).chars(), usize::MAX);
+}
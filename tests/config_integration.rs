@@ -0,0 +1,354 @@
+use rtik::config::{self, Config, Source};
+use rtik::Store;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn rtik(dir: &Path, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_rtik"));
+    cmd.args(args)
+        .current_dir(dir)
+        .env_remove("RTIK_DB")
+        .env_remove("RTIK_AGENT")
+        .env_remove("RTIK_OUTPUT");
+    for (k, v) in env {
+        cmd.env(k, v);
+    }
+    cmd.output().unwrap()
+}
+
+fn stdout(out: &Output) -> String {
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout.clone()).unwrap()
+}
+
+#[test]
+fn config_parses_settings_and_falls_back_to_defaults() {
+    let empty = Config::parse("").unwrap();
+    assert_eq!(empty.id_prefix(), "#");
+    assert_eq!(empty.busy_timeout().as_secs(), 5);
+    assert_eq!(empty.claim_lease(), 3600);
+    assert_eq!(empty.name_width(), 40);
+    assert!(empty.list.columns.is_none());
+
+    let config = Config::parse(
+        r#"
+agent = "planner"
+id_prefix = "T-"
+busy_timeout = "10s"
+
+[lease]
+claim = "2h"
+
+[list]
+columns = ["id", "status", "name", "claimed_by"]
+name_width = 60
+filter = "status:todo"
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.id_prefix(), "T-");
+    assert_eq!(config.busy_timeout().as_secs(), 10);
+    assert_eq!(config.claim_lease(), 7200);
+    // The heartbeat lease follows the claim lease unless set.
+    assert_eq!(config.heartbeat_lease(), 7200);
+    assert_eq!(config.name_width(), 60);
+    assert_eq!(config.list.columns.as_ref().unwrap().len(), 4);
+    assert_eq!(config.list.filter.as_ref().unwrap().text, "status:todo");
+
+    let setting = config.setting("lease.heartbeat").unwrap();
+    assert_eq!(setting.value.as_deref(), Some("2h"));
+    assert_eq!(setting.source, Source::Default);
+    let setting = config.setting("list.columns").unwrap();
+    assert_eq!(setting.value.as_deref(), Some("id,status,name,claimer"));
+    assert_eq!(setting.source, Source::Config);
+    assert_eq!(config.settings().len(), config::KEYS.len());
+    assert_eq!(
+        config.setting("colour").unwrap_err().code(),
+        "unknown_config_key"
+    );
+
+    for bad in [
+        "output = \"yaml\"",
+        "busy_timeout = \"soon\"",
+        "id_prefix = \"T1\"",
+        "[lease]\nclaims = \"1h\"",
+        "[lease]\nclaim = \"99999999999999999h\"",
+        "[list]\ncolumns = [\"id\", \"colour\"]",
+        "[list]\nname_width = 3",
+        "[list]\nfilter = \"status:(todo\"",
+    ] {
+        let err = Config::parse(bad).unwrap_err();
+        assert_eq!(err.code(), "invalid_config", "{}", bad);
+    }
+}
+
+#[test]
+fn config_set_keeps_comments_and_rejects_bad_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(".rtik.toml");
+    std::fs::write(
+        &path,
+        "# shared settings\nid_prefix = \"T-\"\nagent = \"ci\"\n",
+    )
+    .unwrap();
+
+    config::set(&path, "lease.claim", Some("30m")).unwrap();
+    config::set(&path, "list.columns", Some("id, name")).unwrap();
+    config::set(&path, "list.name_width", Some("50")).unwrap();
+    config::set(&path, "agent", None).unwrap();
+    config::set(&path, "workflow.claim", Some("in-progress")).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("# shared settings\n"));
+    assert!(!text.contains("agent"));
+    let config = Config::load(&path).unwrap();
+    assert_eq!(config.claim_lease(), 1800);
+    assert_eq!(config.name_width(), 50);
+    let setting = config.setting("workflow.claim").unwrap();
+    assert_eq!(setting.value.as_deref(), Some("in-progress"));
+    assert_eq!(setting.source, Source::Config);
+    assert_eq!(
+        config.setting("workflow.release").unwrap().value.as_deref(),
+        Some("todo")
+    );
+
+    for (key, value) in [
+        ("lease.claim", "forever"),
+        ("list.name_width", "wide"),
+        ("list.columns", "id,colour"),
+        ("output", "yaml"),
+        ("workflow.release", "shipped"),
+    ] {
+        let err = config::set(&path, key, Some(value)).unwrap_err();
+        assert_eq!(err.code(), "invalid_config", "{} = {}", key, value);
+    }
+    assert_eq!(
+        config::set(&path, "colour", Some("red"))
+            .unwrap_err()
+            .code(),
+        "unknown_config_key"
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+}
+
+#[test]
+fn store_opens_with_config_beside_database() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(".rtik.toml"),
+        "[lease]\nclaim = \"45m\"\nheartbeat = \"5m\"\n",
+    )
+    .unwrap();
+    let store = Store::open(dir.path().join("tickets.db")).unwrap();
+    assert_eq!(store.config().claim_lease(), 2700);
+    assert_eq!(store.config().heartbeat_lease(), 300);
+
+    std::fs::write(dir.path().join(".rtik.toml"), "busy_timeout = 5\n").unwrap();
+    let err = Store::open(dir.path().join("tickets.db")).err().unwrap();
+    assert_eq!(err.code(), "invalid_config");
+}
+
+#[test]
+fn cli_discovers_config_in_parent_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let sub = dir.path().join("src/deep");
+    std::fs::create_dir_all(&sub).unwrap();
+    std::fs::write(dir.path().join(".rtik.toml"), "id_prefix = \"T-\"\n").unwrap();
+
+    let out = stdout(&rtik(&sub, &["create", "--name", "Found it"], &[]));
+    assert_eq!(out, "Created: T-1 Found it\n");
+    assert!(dir.path().join(".rtik.db").exists());
+    assert!(!sub.join(".rtik.db").exists());
+
+    // Ids are accepted with the prefix, with '#' or bare.
+    assert!(stdout(&rtik(&sub, &["get", "T-1"], &[])).starts_with("T-1 Found it"));
+    assert!(stdout(&rtik(&sub, &["get", "#1"], &[])).starts_with("T-1 Found it"));
+    let out = rtik(&sub, &["get", "X-1", "--json"], &[]);
+    let err: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    assert_eq!(err["code"], "usage");
+
+    // RTIK_DB wins over discovery, and brings its own config.
+    let other = tempfile::tempdir().unwrap();
+    let db = other.path().join("t.db");
+    let out = rtik(
+        &sub,
+        &["create", "--name", "Elsewhere"],
+        &[("RTIK_DB", db.to_str().unwrap())],
+    );
+    assert_eq!(stdout(&out), "Created: #1 Elsewhere\n");
+}
+
+#[test]
+fn id_prefix_applies_to_export_and_queries() {
+    let dir = tempfile::tempdir().unwrap();
+    let d = dir.path();
+    std::fs::write(
+        d.join(".rtik.toml"),
+        "id_prefix = \"T-\"\n\n[list]\nfilter = \"parent:T-1\"\n",
+    )
+    .unwrap();
+    stdout(&rtik(d, &["create", "--name", "Epic"], &[]));
+    stdout(&rtik(
+        d,
+        &["create", "--name", "Child", "--parent", "T-1"],
+        &[],
+    ));
+    stdout(&rtik(d, &["dep", "add", "T-2", "T-1"], &[]));
+
+    assert_eq!(
+        stdout(&rtik(d, &["export", "-q", "id:T-2"], &[])),
+        "T-2 [todo] Child parent:T-1 deps:T-1 +waiting\n"
+    );
+    let md = stdout(&rtik(d, &["export", "--format", "markdown"], &[]));
+    assert!(md.contains("- [ ] T-2 **Child** — parent T-1 · depends on T-1\n"));
+    let dot = stdout(&rtik(d, &["deps", "--all"], &[]));
+    assert!(dot.contains("label=\"T-1 Epic\\n[todo]\""), "{dot}");
+
+    // The configured list.filter reads the prefix too.
+    let listed = stdout(&rtik(d, &["list", "--columns", "id,name"], &[]));
+    assert_eq!(listed.lines().skip(2).count(), 1, "{listed}");
+
+    // Library users get it from the store.
+    let store = Store::open(d.join(".rtik.db")).unwrap();
+    assert_eq!(store.ids().show(2), "T-2");
+    let filter = rtik::ListFilter {
+        query: Some(rtik::query::parse_with("id:T-1", store.ids()).unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(store.list(&filter).unwrap().tickets[0].name, "Epic");
+}
+
+#[test]
+fn cli_precedence_is_flag_then_env_then_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let d = dir.path();
+    std::fs::write(
+        d.join(".rtik.toml"),
+        r#"
+agent = "from-config"
+output = "json"
+
+[lease]
+claim = "2h"
+
+[list]
+columns = ["id", "claimer", "name"]
+name_width = 12
+filter = "status:todo"
+"#,
+    )
+    .unwrap();
+    let created: serde_json::Value =
+        serde_json::from_str(&stdout(&rtik(d, &["create", "--name", "One"], &[]))).unwrap();
+    assert_eq!(created["id"], 1);
+    stdout(&rtik(
+        d,
+        &["create", "--name", "A rather long second ticket"],
+        &[],
+    ));
+
+    // agent: config, then RTIK_AGENT.
+    let claimed: serde_json::Value =
+        serde_json::from_str(&stdout(&rtik(d, &["claim", "1"], &[]))).unwrap();
+    assert_eq!(claimed["claimed_by"], "from-config");
+    let out = rtik(d, &["get", "1"], &[("RTIK_AGENT", "from-env")]);
+    let ticket: serde_json::Value = serde_json::from_str(&stdout(&out)).unwrap();
+    assert_eq!(ticket["claimed_by"], "from-config");
+    let out = rtik(d, &["claim", "2"], &[("RTIK_AGENT", "from-env")]);
+    let claimed: serde_json::Value = serde_json::from_str(&stdout(&out)).unwrap();
+    assert_eq!(claimed["claimed_by"], "from-env");
+
+    // lease: config, then --lease.
+    let lease = |v: &serde_json::Value| v["lease_expires_at"].as_str().unwrap().to_string();
+    let hb: serde_json::Value =
+        serde_json::from_str(&stdout(&rtik(d, &["heartbeat", "1"], &[]))).unwrap();
+    let short: serde_json::Value =
+        serde_json::from_str(&stdout(&rtik(d, &["heartbeat", "1", "--lease", "1m"], &[]))).unwrap();
+    assert!(lease(&short) < lease(&hb));
+
+    // output: config json, then RTIK_OUTPUT, then --format / --json.
+    let text = stdout(&rtik(d, &["release", "1"], &[("RTIK_OUTPUT", "text")]));
+    assert_eq!(text, "Released #1\n");
+    let out = rtik(d, &["list", "--format", "text", "--all"], &[]);
+    let table = stdout(&out);
+    // columns and name width from config; --all skips the default filter.
+    assert!(table.starts_with("  ID  CLAIMER       NAME"));
+    assert!(table.contains("A rather ..."));
+    assert!(table.contains("from-env"));
+    let out = rtik(
+        d,
+        &["list", "--json", "--columns", "id,name"],
+        &[("RTIK_OUTPUT", "text")],
+    );
+    let listed: serde_json::Value = serde_json::from_str(&stdout(&out)).unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    let out = rtik(
+        d,
+        &["list", "--columns", "id,name"],
+        &[("RTIK_OUTPUT", "text")],
+    );
+    assert!(stdout(&out).starts_with("  ID  NAME\n"));
+
+    // list.filter only applies when no filter flag is given.
+    let out = rtik(d, &["list", "--status", "in-progress"], &[]);
+    let listed: serde_json::Value = serde_json::from_str(&stdout(&out)).unwrap();
+    assert_eq!(listed[0]["id"], 2);
+
+    // config get reports the winning layer.
+    let out = rtik(
+        d,
+        &["config", "get", "agent"],
+        &[("RTIK_AGENT", "from-env")],
+    );
+    let setting: serde_json::Value = serde_json::from_str(&stdout(&out)).unwrap();
+    assert_eq!(setting["value"], "from-env");
+    assert_eq!(setting["source"], "env");
+    let out = rtik(d, &["config", "get", "output", "--json"], &[]);
+    let setting: serde_json::Value = serde_json::from_str(&stdout(&out)).unwrap();
+    assert_eq!(setting["source"], "config");
+    let out = rtik(
+        d,
+        &["config", "get", "busy_timeout"],
+        &[("RTIK_OUTPUT", "text")],
+    );
+    assert_eq!(stdout(&out), "5s\n");
+}
+
+#[test]
+fn cli_config_commands_fix_a_broken_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let d = dir.path();
+    std::fs::write(d.join(".rtik.toml"), "[list]\nname_width = 3\n").unwrap();
+
+    let out = rtik(d, &["list", "--json"], &[]);
+    assert!(!out.status.success());
+    let err: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    assert_eq!(err["code"], "invalid_config");
+    assert!(!d.join(".rtik.db").exists());
+
+    let out = rtik(d, &["config", "set", "list.name_width", "30"], &[]);
+    assert_eq!(
+        stdout(&out),
+        format!(
+            "Set list.name_width = 30 in {}\n",
+            d.join(".rtik.toml").display()
+        )
+    );
+    let out = rtik(d, &["config", "list", "--json"], &[]);
+    let settings: serde_json::Value = serde_json::from_str(&stdout(&out)).unwrap();
+    let width = settings
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["key"] == "list.name_width")
+        .unwrap();
+    assert_eq!(width["value"], "30");
+    assert_eq!(width["source"], "config");
+
+    let out = rtik(d, &["config", "set", "shade", "blue", "--json"], &[]);
+    let err: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    assert_eq!(err["code"], "unknown_config_key");
+}
@@ -1,4 +1,4 @@
-use rtik::{db, ticket};
+use rtik::{db, graph, ticket, IdPrefix};
 
 fn open_test_db() -> (rusqlite::Connection, tempfile::TempPath) {
     let tmp = tempfile::NamedTempFile::new().expect("tempfile");
@@ -179,10 +179,35 @@ fn claim_with_unmet_deps_warns_but_succeeds() {
     let (mut conn, _tmp) = open_test_db();
     let id1 = ticket::create_ticket(&conn, "Dep", "").unwrap();
     let id2 = ticket::create_ticket(&conn, "Task", "").unwrap();
-    ticket::add_dep(&conn, id2, id1).unwrap();
+    ticket::add_dep(&conn, id2, id1, None).unwrap();
     // id1 is still "todo" (not done) — claim of id2 should warn but succeed
-    let result = ticket::claim_ticket(&mut conn, id2, "agent-1", false);
-    assert!(result.is_ok());
+    let warnings = ticket::claim_ticket(&mut conn, id2, "agent-1", false).unwrap();
+    assert_eq!(warnings, vec!["1 dependencies not done"]);
+
+    let warnings = ticket::claim_ticket(&mut conn, id2, "agent-2", true).unwrap();
+    assert_eq!(
+        warnings,
+        vec!["1 dependencies not done", "overriding claim by agent-1"]
+    );
+    let warnings = ticket::release_ticket(&mut conn, id2, "agent-1", true).unwrap();
+    assert_eq!(warnings, vec!["overriding claim by agent-2"]);
+}
+
+#[test]
+fn error_codes_are_stable() {
+    let (conn, _tmp) = open_test_db();
+    let err = ticket::get_ticket(&conn, 42).unwrap_err();
+    assert_eq!(err.code(), "not_found");
+    assert_eq!(ticket::AppError::NoReadyTicket.code(), "no_ready_ticket");
+    assert_eq!(
+        ticket::AppError::CyclicDependency(String::new()).code(),
+        "cyclic_dependency"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&rtik::error_json(err.code(), &err.to_string())).unwrap();
+    assert_eq!(json["code"], "not_found");
+    assert_eq!(json["message"], "ticket #42 not found");
 }
 
 #[test]
@@ -260,7 +285,7 @@ fn done_to_in_progress_is_valid() {
 fn blocked_from_todo_is_valid() {
     let (conn, _tmp) = open_test_db();
     let id = ticket::create_ticket(&conn, "Task", "").unwrap();
-    let result = ticket::block_ticket(&conn, id, "waiting on something");
+    let result = ticket::block_ticket(&conn, id, "waiting on something", None);
     assert!(result.is_ok());
     let t = ticket::get_ticket(&conn, id).unwrap();
     assert_eq!(t.status, "blocked");
@@ -297,7 +322,7 @@ fn add_dep_success() {
     let (conn, _tmp) = open_test_db();
     let id1 = ticket::create_ticket(&conn, "A", "").unwrap();
     let id2 = ticket::create_ticket(&conn, "B", "").unwrap();
-    ticket::add_dep(&conn, id2, id1).unwrap();
+    ticket::add_dep(&conn, id2, id1, None).unwrap();
     let deps = ticket::list_deps(&conn, id2).unwrap();
     assert_eq!(deps.forward, vec![id1]);
     assert!(deps.reverse.is_empty());
@@ -308,8 +333,8 @@ fn remove_dep_success() {
     let (conn, _tmp) = open_test_db();
     let id1 = ticket::create_ticket(&conn, "A", "").unwrap();
     let id2 = ticket::create_ticket(&conn, "B", "").unwrap();
-    ticket::add_dep(&conn, id2, id1).unwrap();
-    ticket::remove_dep(&conn, id2, id1).unwrap();
+    ticket::add_dep(&conn, id2, id1, None).unwrap();
+    ticket::remove_dep(&conn, id2, id1, None).unwrap();
     let deps = ticket::list_deps(&conn, id2).unwrap();
     assert!(deps.forward.is_empty());
     assert!(deps.reverse.is_empty());
@@ -321,9 +346,9 @@ fn circular_dep_rejected() {
     let a = ticket::create_ticket(&conn, "A", "").unwrap();
     let b = ticket::create_ticket(&conn, "B", "").unwrap();
     let c = ticket::create_ticket(&conn, "C", "").unwrap();
-    ticket::add_dep(&conn, b, a).unwrap();
-    ticket::add_dep(&conn, c, b).unwrap();
-    let result = ticket::add_dep(&conn, a, c);
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    ticket::add_dep(&conn, c, b, None).unwrap();
+    let result = ticket::add_dep(&conn, a, c, None);
     assert!(matches!(
         result,
         Err(ticket::AppError::CyclicDependency(..))
@@ -334,7 +359,7 @@ fn circular_dep_rejected() {
 fn self_dep_rejected() {
     let (conn, _tmp) = open_test_db();
     let id = ticket::create_ticket(&conn, "A", "").unwrap();
-    let result = ticket::add_dep(&conn, id, id);
+    let result = ticket::add_dep(&conn, id, id, None);
     assert!(matches!(
         result,
         Err(ticket::AppError::CyclicDependency(..))
@@ -345,7 +370,7 @@ fn self_dep_rejected() {
 fn remove_nonexistent_dep() {
     let (conn, _tmp) = open_test_db();
     let id = ticket::create_ticket(&conn, "A", "").unwrap();
-    let result = ticket::remove_dep(&conn, id, 999);
+    let result = ticket::remove_dep(&conn, id, 999, None);
     assert!(matches!(result, Err(ticket::AppError::DepNotFound(..))));
 }
 
@@ -354,7 +379,7 @@ fn cascade_delete_removes_deps() {
     let (conn, _tmp) = open_test_db();
     let id1 = ticket::create_ticket(&conn, "A", "").unwrap();
     let id2 = ticket::create_ticket(&conn, "B", "").unwrap();
-    ticket::add_dep(&conn, id2, id1).unwrap();
+    ticket::add_dep(&conn, id2, id1, None).unwrap();
     ticket::delete_ticket(&conn, id1).unwrap();
     let deps = ticket::list_deps(&conn, id2).unwrap();
     assert!(deps.forward.is_empty());
@@ -365,7 +390,466 @@ fn reverse_deps_populated() {
     let (conn, _tmp) = open_test_db();
     let a = ticket::create_ticket(&conn, "A", "").unwrap();
     let b = ticket::create_ticket(&conn, "B", "").unwrap();
-    ticket::add_dep(&conn, b, a).unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
     let deps = ticket::list_deps(&conn, a).unwrap();
     assert_eq!(deps.reverse, vec![b]);
 }
+
+#[test]
+fn dep_tree_is_transitive_and_marks_repeats() {
+    let (conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    let c = ticket::create_ticket(&conn, "C", "").unwrap();
+    let d = ticket::create_ticket(&conn, "D", "").unwrap();
+    // d -> b -> a, d -> c -> a: a is reachable twice.
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    ticket::add_dep(&conn, c, a, None).unwrap();
+    ticket::add_dep(&conn, d, b, None).unwrap();
+    ticket::add_dep(&conn, d, c, None).unwrap();
+
+    let up = graph::dep_tree(&conn, d, graph::Direction::DependsOn).unwrap();
+    let ids: Vec<i64> = up.children.iter().map(|n| n.ticket.id).collect();
+    assert_eq!(ids, vec![b, c]);
+    assert_eq!(up.children[0].children[0].ticket.id, a);
+    assert!(!up.children[0].children[0].repeated);
+    assert!(up.children[1].children[0].repeated);
+
+    let down = graph::dep_tree(&conn, a, graph::Direction::RequiredBy).unwrap();
+    let ids: Vec<i64> = down.children.iter().map(|n| n.ticket.id).collect();
+    assert_eq!(ids, vec![b, c]);
+    assert_eq!(down.children[0].children[0].ticket.id, d);
+}
+
+#[test]
+fn graph_render_includes_edges_and_status_colors() {
+    let (mut conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "Schema", "").unwrap();
+    let b = ticket::create_ticket(&conn, "API \"v2\"", "").unwrap();
+    ticket::create_ticket(&conn, "Unrelated", "").unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    ticket::claim_ticket(&mut conn, a, "agent", false).unwrap();
+
+    let deps = graph::dep_graph(&conn).unwrap();
+    let dot = deps.render(graph::GraphFormat::Dot, &IdPrefix::new("TK-"));
+    assert!(dot.starts_with("digraph deps {"));
+    assert!(dot.contains("t1 -> t2;"));
+    assert!(dot.contains("TK-2 API \\\"v2\\\""));
+    assert!(!dot.contains("Unrelated"));
+    assert!(dot.contains("[in-progress]"));
+
+    let mermaid = deps.render(graph::GraphFormat::Mermaid, &IdPrefix::default());
+    assert!(mermaid.starts_with("flowchart LR"));
+    assert!(mermaid.contains("t1 --> t2"));
+    assert!(mermaid.contains("t1[\"T-1 Schema\"]"));
+    assert!(mermaid.contains(":::in_progress"));
+    assert!(mermaid.contains("classDef done"));
+}
+
+#[test]
+fn plan_groups_open_tickets_into_waves() {
+    let (conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    let c = ticket::create_ticket(&conn, "C", "").unwrap();
+    let d = ticket::create_ticket(&conn, "D", "").unwrap();
+    let done = ticket::create_ticket(&conn, "Done", "").unwrap();
+    ticket::update_ticket(&conn, done, None, None, Some("in-progress")).unwrap();
+    ticket::update_ticket(&conn, done, None, None, Some("done")).unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    ticket::add_dep(&conn, c, b, None).unwrap();
+    ticket::add_dep(&conn, d, done, None).unwrap();
+
+    let waves = graph::plan(&conn, None, &IdPrefix::default()).unwrap();
+    let ids: Vec<Vec<i64>> = waves
+        .iter()
+        .map(|w| w.tickets.iter().map(|t| t.id).collect())
+        .collect();
+    assert_eq!(ids, vec![vec![a, d], vec![b], vec![c]]);
+    assert!(waves[0].tickets[1].dependencies.is_empty());
+
+    let to_b = graph::plan(&conn, Some(b), &IdPrefix::default()).unwrap();
+    let ids: Vec<Vec<i64>> = to_b
+        .iter()
+        .map(|w| w.tickets.iter().map(|t| t.id).collect())
+        .collect();
+    assert_eq!(ids, vec![vec![a], vec![b]]);
+}
+
+#[test]
+fn plan_reports_cycles_in_stored_graph() {
+    let (conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    // Bypass add_dep's cycle check to simulate corrupted data.
+    conn.execute(
+        "INSERT INTO ticket_deps (ticket_id, depends_on) VALUES (?1, ?2)",
+        [a, b],
+    )
+    .unwrap();
+    let err = graph::plan(&conn, None, &IdPrefix::default()).unwrap_err();
+    match err {
+        ticket::AppError::CyclicDependency(path) => assert_eq!(path, "#1 → #2 → #1"),
+        other => panic!("expected CyclicDependency, got {other:?}"),
+    }
+    let err = graph::plan(&conn, None, &IdPrefix::new("T-")).unwrap_err();
+    assert_eq!(err.to_string().matches("T-").count(), 3, "{err}");
+}
+
+// ---- Leases ----
+
+#[test]
+fn claim_sets_lease_expiry() {
+    let (mut conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    ticket::claim_ticket(&mut conn, id, "agent-1", false).unwrap();
+    let t = ticket::get_ticket(&conn, id).unwrap();
+    assert_eq!(t.claimed_by.as_deref(), Some("agent-1"));
+    assert!(t.lease_expires_at.is_some());
+    assert!(!t.lease_expired("2000-01-01T00:00:00Z"));
+}
+
+#[test]
+fn expired_lease_can_be_claimed_without_force() {
+    let (mut conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    ticket::claim_ticket_with_lease(&mut conn, id, "agent-1", false, 0).unwrap();
+    ticket::claim_ticket(&mut conn, id, "agent-2", false).unwrap();
+    let t = ticket::get_ticket(&conn, id).unwrap();
+    assert_eq!(t.claimed_by.as_deref(), Some("agent-2"));
+}
+
+#[test]
+fn expired_lease_listed_as_unclaimed() {
+    let (mut conn, _tmp) = open_test_db();
+    let id1 = ticket::create_ticket(&conn, "Expired", "").unwrap();
+    let id2 = ticket::create_ticket(&conn, "Active", "").unwrap();
+    ticket::claim_ticket_with_lease(&mut conn, id1, "agent-1", false, 0).unwrap();
+    ticket::claim_ticket(&mut conn, id2, "agent-1", false).unwrap();
+
+    let unclaimed = ticket::list_tickets_filtered(
+        &conn,
+        &ticket::ListFilter {
+            claimed: Some(false),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(unclaimed.len(), 1);
+    assert_eq!(unclaimed[0].id, id1);
+
+    let exports = ticket::tickets_to_export(&conn, &ticket::ListFilter::default()).unwrap();
+    assert_eq!(exports[0].claimed_by, None);
+    assert_eq!(exports[1].claimed_by.as_deref(), Some("agent-1"));
+}
+
+#[test]
+fn heartbeat_extends_own_lease() {
+    let (mut conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    ticket::claim_ticket_with_lease(&mut conn, id, "agent-1", false, 0).unwrap();
+    ticket::heartbeat_ticket(&mut conn, id, "agent-1", 600).unwrap();
+    let result = ticket::claim_ticket(&mut conn, id, "agent-2", false);
+    assert!(matches!(result, Err(ticket::AppError::AlreadyClaimed(..))));
+}
+
+#[test]
+fn oversized_durations_are_rejected_not_overflowed() {
+    use rtik::cli::parse_duration;
+    assert_eq!(parse_duration("36500d"), Ok(36500 * 86400));
+    assert!(parse_duration("36501d").is_err());
+    assert!(parse_duration("99999999999999999h").is_err());
+    assert!(parse_duration("18446744073709551615m").is_err());
+
+    // Leases given directly as seconds are capped rather than overflowing.
+    let (mut conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    ticket::claim_ticket_with_lease(&mut conn, id, "agent-1", false, u64::MAX).unwrap();
+    let expires = ticket::heartbeat_ticket(&mut conn, id, "agent-1", u64::MAX).unwrap();
+    assert_eq!(expires.len(), "2126-01-01T00:00:00Z".len());
+}
+
+#[test]
+fn since_rejects_non_ascii_instead_of_panicking() {
+    use rtik::cli::parse_since;
+    assert_eq!(parse_since("2026-10-01").as_deref(), Ok("2026-10-01"));
+    for bad in ["200é", "é", "2é", "20"] {
+        assert!(parse_since(bad).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn heartbeat_requires_ownership() {
+    let (mut conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    let result = ticket::heartbeat_ticket(&mut conn, id, "agent-1", 600);
+    assert!(matches!(result, Err(ticket::AppError::NotClaimed(_))));
+    ticket::claim_ticket(&mut conn, id, "agent-1", false).unwrap();
+    let result = ticket::heartbeat_ticket(&mut conn, id, "agent-2", 600);
+    assert!(matches!(result, Err(ticket::AppError::NotOwner(..))));
+}
+
+#[test]
+fn reap_restores_prior_status() {
+    let (mut conn, _tmp) = open_test_db();
+    let id1 = ticket::create_ticket(&conn, "From todo", "").unwrap();
+    let id2 = ticket::create_ticket(&conn, "From done", "").unwrap();
+    let id3 = ticket::create_ticket(&conn, "Still leased", "").unwrap();
+    ticket::update_ticket(&conn, id2, None, None, Some("in-progress")).unwrap();
+    ticket::update_ticket(&conn, id2, None, None, Some("done")).unwrap();
+    ticket::claim_ticket_with_lease(&mut conn, id1, "agent-1", false, 0).unwrap();
+    ticket::claim_ticket_with_lease(&mut conn, id2, "agent-2", false, 0).unwrap();
+    ticket::claim_ticket(&mut conn, id3, "agent-3", false).unwrap();
+
+    let reaped = ticket::reap_expired(&mut conn, None).unwrap();
+    assert_eq!(reaped.len(), 2);
+    assert_eq!(reaped[0].agent, "agent-1");
+    assert_eq!(reaped[1].restored_status, "done");
+
+    let t1 = ticket::get_ticket(&conn, id1).unwrap();
+    assert_eq!(t1.status, "todo");
+    assert!(t1.claimed_by.is_none());
+    assert_eq!(ticket::get_ticket(&conn, id2).unwrap().status, "done");
+    assert_eq!(
+        ticket::get_ticket(&conn, id3)
+            .unwrap()
+            .claimed_by
+            .as_deref(),
+        Some("agent-3")
+    );
+}
+
+// ---- Next ----
+
+#[test]
+fn claim_next_picks_lowest_ready_id() {
+    let (mut conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    let c = ticket::create_ticket(&conn, "C", "").unwrap();
+    ticket::add_dep(&conn, a, c, None).unwrap();
+    let t = ticket::claim_next(&mut conn, "agent-1", 600)
+        .unwrap()
+        .unwrap();
+    assert_eq!(t.id, b);
+    assert_eq!(t.status, "in-progress");
+    assert_eq!(t.claimed_by.as_deref(), Some("agent-1"));
+}
+
+#[test]
+fn claim_next_skips_unmet_deps_and_claimed() {
+    let (mut conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    ticket::claim_ticket(&mut conn, a, "agent-1", false).unwrap();
+    assert!(ticket::claim_next(&mut conn, "agent-2", 600)
+        .unwrap()
+        .is_none());
+
+    ticket::update_ticket(&conn, a, None, None, Some("done")).unwrap();
+    let t = ticket::claim_next(&mut conn, "agent-2", 600)
+        .unwrap()
+        .unwrap();
+    assert_eq!(t.id, b);
+}
+
+#[test]
+fn claim_next_empty_queue_returns_none() {
+    let (mut conn, _tmp) = open_test_db();
+    assert!(ticket::claim_next(&mut conn, "agent-1", 600)
+        .unwrap()
+        .is_none());
+    assert_eq!(ticket::AppError::NoReadyTicket.exit_code(), 3);
+}
+
+// ---- Event history ----
+
+#[test]
+fn mutations_append_events() {
+    let (mut conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    ticket::update_ticket(&conn, a, Some("A2"), None, None).unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    ticket::claim_ticket(&mut conn, a, "agent-1", false).unwrap();
+    ticket::update_ticket(&conn, a, None, None, Some("done")).unwrap();
+
+    let events = ticket::list_events(&conn, Some(a), None).unwrap();
+    let kinds: Vec<&str> = events.iter().map(|e| e.kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        vec!["created", "name", "claim", "status", "status", "claim"]
+    );
+    assert_eq!(events[1].old_value.as_deref(), Some("A"));
+    assert_eq!(events[1].new_value.as_deref(), Some("A2"));
+    assert_eq!(events[2].actor.as_deref(), Some("agent-1"));
+    assert_eq!(events[4].new_value.as_deref(), Some("done"));
+    assert_eq!(events[5].old_value.as_deref(), Some("agent-1"));
+    assert!(events[5].new_value.is_none());
+
+    let dep_events = ticket::list_events(&conn, Some(b), None).unwrap();
+    assert_eq!(dep_events[1].kind, "dep_added");
+    assert_eq!(dep_events[1].new_value.as_deref(), Some("1"));
+}
+
+#[test]
+fn failed_mutation_records_no_event() {
+    let (conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    let result = ticket::update_ticket(&conn, id, None, None, Some("done"));
+    assert!(result.is_err());
+    let events = ticket::list_events(&conn, Some(id), None).unwrap();
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+fn events_survive_delete_and_filter_by_since() {
+    let (conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Temp", "").unwrap();
+    ticket::delete_ticket(&conn, id).unwrap();
+    let events = ticket::list_events(&conn, Some(id), None).unwrap();
+    assert_eq!(events.last().unwrap().kind, "deleted");
+    assert_eq!(events.last().unwrap().old_value.as_deref(), Some("Temp"));
+
+    let all = ticket::list_events(&conn, None, Some("2000-01-01")).unwrap();
+    assert_eq!(all.len(), 2);
+    let none = ticket::list_events(&conn, None, Some("9999-01-01")).unwrap();
+    assert!(none.is_empty());
+}
+
+// ---- Notes ----
+
+#[test]
+fn add_note_appends_in_order() {
+    let (conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    ticket::add_note(&conn, id, Some("agent-1"), "started parser").unwrap();
+    ticket::add_note(&conn, id, Some("agent-2"), "parser done, wiring CLI").unwrap();
+    ticket::add_note(&conn, id, None, "human check-in").unwrap();
+
+    let all = ticket::list_notes(&conn, id, None).unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[0].body, "started parser");
+    assert_eq!(all[1].author.as_deref(), Some("agent-2"));
+
+    let last_two = ticket::list_notes(&conn, id, Some(2)).unwrap();
+    assert_eq!(last_two.len(), 2);
+    assert_eq!(last_two[0].body, "parser done, wiring CLI");
+    assert_eq!(last_two[1].body, "human check-in");
+}
+
+#[test]
+fn add_note_validates_input() {
+    let (conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    assert!(matches!(
+        ticket::add_note(&conn, id, None, "  "),
+        Err(ticket::AppError::NoteRequired)
+    ));
+    assert!(matches!(
+        ticket::add_note(&conn, 999, None, "text"),
+        Err(ticket::AppError::NotFound(999))
+    ));
+}
+
+#[test]
+fn claim_next_prefers_urgent_priority() {
+    let (mut conn, _tmp) = open_test_db();
+    ticket::create_ticket(&conn, "Normal", "").unwrap();
+    let urgent = ticket::create_ticket_with(
+        &conn,
+        &ticket::NewTicket {
+            name: "Urgent",
+            priority: Some(0),
+            ..Default::default()
+        },
+        None,
+    )
+    .unwrap();
+    let t = ticket::claim_next(&mut conn, "agent-1", 600)
+        .unwrap()
+        .unwrap();
+    assert_eq!(t.id, urgent);
+}
+
+// ---- Subtasks ----
+
+fn create_child(conn: &rusqlite::Connection, name: &str, parent: i64) -> i64 {
+    ticket::create_ticket_with(
+        conn,
+        &ticket::NewTicket {
+            name,
+            parent: Some(parent),
+            ..Default::default()
+        },
+        None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn subtasks_roll_up_done_counts() {
+    let (mut conn, _tmp) = open_test_db();
+    let epic = ticket::create_ticket(&conn, "Epic", "").unwrap();
+    let a = create_child(&conn, "A", epic);
+    let b = create_child(&conn, "B", epic);
+    let b1 = create_child(&conn, "B1", b);
+    create_child(&conn, "B2", b);
+    ticket::claim_ticket(&mut conn, a, "agent-1", false).unwrap();
+    ticket::update_ticket(&conn, a, None, None, Some("done")).unwrap();
+    ticket::claim_ticket(&mut conn, b1, "agent-1", false).unwrap();
+    ticket::update_ticket(&conn, b1, None, None, Some("done")).unwrap();
+
+    let tree = ticket::ticket_tree(&conn, epic).unwrap();
+    assert_eq!(tree.children.len(), 2);
+    assert_eq!(tree.rollup(), (2, 4));
+    assert_eq!(tree.children[1].rollup(), (1, 2));
+    assert_eq!(ticket::get_ticket(&conn, b1).unwrap().parent_id, Some(b));
+}
+
+#[test]
+fn create_with_missing_parent_fails() {
+    let (conn, _tmp) = open_test_db();
+    let result = ticket::create_ticket_with(
+        &conn,
+        &ticket::NewTicket {
+            name: "Orphan",
+            parent: Some(42),
+            ..Default::default()
+        },
+        None,
+    );
+    assert!(matches!(result, Err(ticket::AppError::NotFound(42))));
+}
+
+#[test]
+fn delete_parent_policies() {
+    let (conn, _tmp) = open_test_db();
+    let epic = ticket::create_ticket(&conn, "Epic", "").unwrap();
+    let child = create_child(&conn, "Child", epic);
+    let grandchild = create_child(&conn, "Grandchild", child);
+
+    assert!(matches!(
+        ticket::delete_ticket(&conn, epic),
+        Err(ticket::AppError::HasChildren(_, 1))
+    ));
+
+    ticket::delete_ticket_with(&conn, child, ticket::ChildPolicy::Orphan, None).unwrap();
+    assert_eq!(
+        ticket::get_ticket(&conn, grandchild).unwrap().parent_id,
+        None
+    );
+
+    let child2 = create_child(&conn, "Child 2", epic);
+    let grandchild2 = create_child(&conn, "Grandchild 2", child2);
+    ticket::delete_ticket_with(&conn, epic, ticket::ChildPolicy::Cascade, None).unwrap();
+    for id in [epic, child2, grandchild2] {
+        assert!(matches!(
+            ticket::get_ticket(&conn, id),
+            Err(ticket::AppError::NotFound(_))
+        ));
+    }
+    assert!(ticket::get_ticket(&conn, grandchild).is_ok());
+}
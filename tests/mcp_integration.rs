@@ -0,0 +1,178 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Pipe `requests` (one JSON-RPC message each) into `rtik mcp` and return
+/// the parsed response lines.
+fn run_mcp(db: &std::path::Path, requests: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rtik"))
+        .arg("mcp")
+        .env("RTIK_DB", db)
+        .env("RTIK_AGENT", "bot")
+        .env_remove("RTIK_OUTPUT")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("spawn rtik mcp");
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        for r in requests {
+            writeln!(stdin, "{}", r).unwrap();
+        }
+    }
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("response is JSON"))
+        .collect()
+}
+
+fn call(id: i64, tool: &str, args: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": tool, "arguments": args },
+    })
+}
+
+#[test]
+fn mcp_session_end_to_end() {
+    use serde_json::json;
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let requests = vec![
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+               "params": {"protocolVersion": "2025-06-18", "capabilities": {},
+                          "clientInfo": {"name": "test", "version": "0"}}}),
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+        call(3, "create_ticket", json!({"name": "Schema", "priority": 1})),
+        call(
+            4,
+            "create_ticket",
+            json!({"name": "API", "labels": ["api"]}),
+        ),
+        call(
+            5,
+            "add_dependency",
+            json!({"ticket_id": 2, "depends_on": 1}),
+        ),
+        call(
+            6,
+            "add_dependency",
+            json!({"ticket_id": 1, "depends_on": 2}),
+        ),
+        json!({"jsonrpc": "2.0", "id": 7, "method": "resources/read",
+               "params": {"uri": "rtik://tickets/ready"}}),
+        call(8, "claim_ticket", json!({})),
+        call(9, "claim_ticket", json!({"id": 2})),
+        json!({"jsonrpc": "2.0", "id": 10, "method": "resources/read",
+               "params": {"uri": "rtik://tickets/claimed"}}),
+        call(11, "update_ticket", json!({"id": 1, "status": "done"})),
+        call(12, "list_tickets", json!({"status": "done"})),
+        call(13, "get_ticket", json!({"id": "one"})),
+        json!({"jsonrpc": "2.0", "id": 14, "method": "no/such/method"}),
+    ];
+    let responses = run_mcp(tmp.path(), &requests);
+
+    // The notification gets no response.
+    assert_eq!(responses.len(), requests.len() - 1);
+    let by_id = |id: i64| {
+        responses
+            .iter()
+            .find(|r| r["id"] == id)
+            .unwrap_or_else(|| panic!("no response {id}"))
+    };
+
+    assert_eq!(by_id(1)["result"]["serverInfo"]["name"], "rtik");
+    let tools: Vec<&str> = by_id(2)["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    for name in [
+        "create_ticket",
+        "get_ticket",
+        "update_ticket",
+        "claim_ticket",
+        "release_ticket",
+        "block_ticket",
+        "add_dependency",
+        "remove_dependency",
+        "list_tickets",
+        "export_tickets",
+    ] {
+        assert!(tools.contains(&name), "missing tool {name}");
+    }
+    for t in by_id(2)["result"]["tools"].as_array().unwrap() {
+        assert_eq!(t["inputSchema"]["type"], "object");
+    }
+
+    assert_eq!(by_id(3)["result"]["structuredContent"]["id"], 1);
+    assert_eq!(
+        by_id(4)["result"]["structuredContent"]["labels"],
+        json!(["api"])
+    );
+    assert_eq!(
+        by_id(5)["result"]["structuredContent"]["dependencies"],
+        json!([1])
+    );
+
+    let cycle = &by_id(6)["result"];
+    assert_eq!(cycle["isError"], true);
+    assert_eq!(cycle["structuredContent"]["code"], "cyclic_dependency");
+
+    let ready: serde_json::Value =
+        serde_json::from_str(by_id(7)["result"]["contents"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(ready.as_array().unwrap().len(), 1);
+    assert_eq!(ready[0]["id"], 1);
+
+    let next = &by_id(8)["result"]["structuredContent"];
+    assert_eq!(next["id"], 1);
+    assert_eq!(next["claimed_by"], "bot");
+
+    let warned = &by_id(9)["result"]["structuredContent"];
+    assert_eq!(warned["warnings"], json!(["1 dependencies not done"]));
+
+    let claimed: serde_json::Value =
+        serde_json::from_str(by_id(10)["result"]["contents"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(claimed.as_array().unwrap().len(), 2);
+
+    assert_eq!(by_id(11)["result"]["structuredContent"]["status"], "done");
+    let listed = &by_id(12)["result"]["structuredContent"]["tickets"];
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["name"], "Schema");
+
+    assert_eq!(by_id(13)["error"]["code"], -32602);
+    assert_eq!(by_id(14)["error"]["code"], -32601);
+}
+
+#[test]
+fn mcp_reports_parse_errors_and_keeps_going() {
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rtik"))
+        .arg("mcp")
+        .env("RTIK_DB", tmp.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"not json\n{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n")
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    let lines: Vec<serde_json::Value> = String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["error"]["code"], -32700);
+    assert_eq!(lines[0]["id"], serde_json::Value::Null);
+    assert_eq!(lines[1]["result"], serde_json::json!({}));
+}
@@ -1,4 +1,5 @@
-use rtik::{db, ticket};
+use rtik::workflow::Workflow;
+use rtik::{db, ticket, IdPrefix, Status};
 use ticket::{ListFilter, TicketExport};
 
 fn open_test_db() -> (rusqlite::Connection, tempfile::TempPath) {
@@ -9,12 +10,7 @@ fn open_test_db() -> (rusqlite::Connection, tempfile::TempPath) {
 }
 
 fn empty_filter() -> ListFilter {
-    ListFilter {
-        status: None,
-        claimed: None,
-        claimer: None,
-        search: vec![],
-    }
+    ListFilter::default()
 }
 
 // ---- Filter: status ----
@@ -27,7 +23,7 @@ fn test_filter_by_status_returns_matching() {
     ticket::update_ticket(&conn, id2, None, None, Some("in-progress")).unwrap();
 
     let filter = ListFilter {
-        status: Some("in-progress".to_string()),
+        status: Some(Status::InProgress),
         ..empty_filter()
     };
     let tickets = ticket::list_tickets_filtered(&conn, &filter).unwrap();
@@ -41,7 +37,7 @@ fn test_filter_by_status_returns_empty_for_unknown() {
     ticket::create_ticket(&conn, "Todo ticket", "").unwrap();
 
     let filter = ListFilter {
-        status: Some("done".to_string()),
+        status: Some(Status::Done),
         ..empty_filter()
     };
     let tickets = ticket::list_tickets_filtered(&conn, &filter).unwrap();
@@ -187,7 +183,7 @@ fn test_filter_compose_status_and_search() {
     ticket::update_ticket(&conn, id1, None, None, Some("in-progress")).unwrap();
 
     let filter = ListFilter {
-        status: Some("todo".to_string()),
+        status: Some(Status::Todo),
         search: vec!["fix".to_string()],
         ..empty_filter()
     };
@@ -205,9 +201,22 @@ fn test_export_plain_text_no_deps() {
         id: 7,
         name: "Deploy service".to_string(),
         description: "".to_string(),
-        status: "todo".to_string(),
+        status: Status::Todo,
+        priority: 2,
+        parent_id: None,
         claimed_by: None,
+        claimed_at: None,
+        lease_expires_at: None,
+        block_reason: None,
         dependencies: vec![],
+        labels: vec![],
+        ready: false,
+        waiting: false,
+        blocking: false,
+        created_at: "2026-10-01T00:00:00Z".to_string(),
+        updated_at: "2026-10-01T00:00:00Z".to_string(),
+        notes: vec![],
+        external_key: None,
     };
     let text = ticket::format_export_text(&export);
     assert_eq!(text, "T-7 [todo] Deploy service");
@@ -219,9 +228,22 @@ fn test_export_plain_text_with_deps() {
         id: 3,
         name: "Integrate payments".to_string(),
         description: "".to_string(),
-        status: "in-progress".to_string(),
+        status: Status::InProgress,
+        priority: 2,
+        parent_id: None,
         claimed_by: None,
+        claimed_at: None,
+        lease_expires_at: None,
+        block_reason: None,
         dependencies: vec![1, 2],
+        labels: vec![],
+        ready: false,
+        waiting: false,
+        blocking: false,
+        created_at: "2026-10-01T00:00:00Z".to_string(),
+        updated_at: "2026-10-01T00:00:00Z".to_string(),
+        notes: vec![],
+        external_key: None,
     };
     let text = ticket::format_export_text(&export);
     assert_eq!(text, "T-3 [in-progress] Integrate payments deps:T-1,T-2");
@@ -234,7 +256,7 @@ fn test_export_json_structure() {
     let (conn, _tmp) = open_test_db();
     let id1 = ticket::create_ticket(&conn, "Alpha", "first").unwrap();
     let id2 = ticket::create_ticket(&conn, "Beta", "second").unwrap();
-    ticket::add_dep(&conn, id2, id1).unwrap();
+    ticket::add_dep(&conn, id2, id1, None).unwrap();
 
     let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
     let json = serde_json::to_string_pretty(&exports).unwrap();
@@ -266,3 +288,1082 @@ fn test_empty_filter_returns_all() {
     let tickets = ticket::list_tickets_filtered(&conn, &empty_filter()).unwrap();
     assert_eq!(tickets.len(), 3);
 }
+
+// ---- Export: notes ----
+
+#[test]
+fn test_export_json_includes_notes() {
+    let (conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Alpha", "").unwrap();
+    ticket::add_note(&conn, id, Some("agent-1"), "halfway through").unwrap();
+
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    let parsed = serde_json::to_value(&exports).unwrap();
+    assert_eq!(parsed[0]["notes"][0]["body"], "halfway through");
+    assert_eq!(parsed[0]["notes"][0]["author"], "agent-1");
+}
+
+// ---- Priority and sorting ----
+
+#[test]
+fn test_priority_defaults_and_updates() {
+    let (conn, _tmp) = open_test_db();
+    let id = ticket::create_ticket(&conn, "Task", "").unwrap();
+    assert_eq!(ticket::get_ticket(&conn, id).unwrap().priority, 2);
+    let update = ticket::TicketUpdate {
+        priority: Some(0),
+        ..Default::default()
+    };
+    ticket::update_ticket_with(&conn, id, &update, None).unwrap();
+    assert_eq!(ticket::get_ticket(&conn, id).unwrap().priority, 0);
+
+    let bad = ticket::NewTicket {
+        name: "Bad",
+        priority: Some(9),
+        ..Default::default()
+    };
+    assert!(matches!(
+        ticket::create_ticket_with(&conn, &bad, None),
+        Err(ticket::AppError::InvalidPriority(9))
+    ));
+}
+
+#[test]
+fn test_sort_by_priority_then_id() {
+    let (conn, _tmp) = open_test_db();
+    for (name, priority) in [("Low", 4), ("Urgent", 0), ("Normal", 2), ("Urgent 2", 0)] {
+        let new = ticket::NewTicket {
+            name,
+            priority: Some(priority),
+            ..Default::default()
+        };
+        ticket::create_ticket_with(&conn, &new, None).unwrap();
+    }
+    let filter = ListFilter {
+        sort: vec![ticket::SortKey {
+            field: ticket::SortField::Priority,
+            descending: false,
+        }],
+        ..empty_filter()
+    };
+    let names: Vec<String> = ticket::list_tickets_filtered(&conn, &filter)
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(names, vec!["Urgent", "Urgent 2", "Normal", "Low"]);
+
+    let filter = ListFilter {
+        sort: vec![ticket::SortKey {
+            field: ticket::SortField::Id,
+            descending: true,
+        }],
+        ..empty_filter()
+    };
+    let tickets = ticket::list_tickets_filtered(&conn, &filter).unwrap();
+    assert_eq!(tickets[0].name, "Urgent 2");
+}
+
+#[test]
+fn test_export_text_shows_non_default_priority() {
+    let export = TicketExport {
+        id: 9,
+        name: "Hotfix".to_string(),
+        description: "".to_string(),
+        status: Status::Todo,
+        priority: 0,
+        parent_id: None,
+        claimed_by: None,
+        claimed_at: None,
+        lease_expires_at: None,
+        block_reason: None,
+        dependencies: vec![],
+        labels: vec![],
+        ready: false,
+        waiting: false,
+        blocking: false,
+        created_at: "2026-10-01T00:00:00Z".to_string(),
+        updated_at: "2026-10-01T00:00:00Z".to_string(),
+        notes: vec![],
+        external_key: None,
+    };
+    assert_eq!(ticket::format_export_text(&export), "T-9 [todo] P0 Hotfix");
+}
+
+#[test]
+fn test_parse_sort_keys() {
+    let key = rtik::cli::parse_sort_key("created:desc").unwrap();
+    assert_eq!(key.field, ticket::SortField::Created);
+    assert!(key.descending);
+    assert!(rtik::cli::parse_sort_key("name").is_err());
+    assert_eq!(rtik::cli::parse_priority("P1"), Ok(1));
+    assert!(rtik::cli::parse_priority("5").is_err());
+}
+
+// ---- Labels ----
+
+fn labels(names: &[&str]) -> Vec<String> {
+    names.iter().map(|s| s.to_string()).collect()
+}
+
+fn create_labeled(conn: &rusqlite::Connection, name: &str, names: &[&str]) -> i64 {
+    let labels = labels(names);
+    let new = ticket::NewTicket {
+        name,
+        labels: &labels,
+        ..Default::default()
+    };
+    ticket::create_ticket_with(conn, &new, None).unwrap()
+}
+
+#[test]
+fn test_labels_added_normalized_and_removed() {
+    let (conn, _tmp) = open_test_db();
+    let id = create_labeled(&conn, "Task", &["Frontend", "db"]);
+    assert_eq!(
+        ticket::list_labels(&conn, id).unwrap(),
+        labels(&["db", "frontend"])
+    );
+
+    let remove = labels(&["db"]);
+    let add = labels(&["docs"]);
+    let update = ticket::TicketUpdate {
+        add_labels: &add,
+        remove_labels: &remove,
+        ..Default::default()
+    };
+    ticket::update_ticket_with(&conn, id, &update, None).unwrap();
+    assert_eq!(
+        ticket::list_labels(&conn, id).unwrap(),
+        labels(&["docs", "frontend"])
+    );
+
+    let bad = labels(&["has space"]);
+    let update = ticket::TicketUpdate {
+        add_labels: &bad,
+        ..Default::default()
+    };
+    assert!(matches!(
+        ticket::update_ticket_with(&conn, id, &update, None),
+        Err(ticket::AppError::InvalidLabel(_))
+    ));
+}
+
+#[test]
+fn test_filter_labels_and_or_not() {
+    let (conn, _tmp) = open_test_db();
+    create_labeled(&conn, "UI", &["frontend"]);
+    create_labeled(&conn, "Schema", &["db"]);
+    create_labeled(&conn, "Flaky UI", &["frontend", "flaky-test"]);
+    create_labeled(&conn, "Plain", &[]);
+
+    let names = |filter: &ListFilter| -> Vec<String> {
+        ticket::list_tickets_filtered(&conn, filter)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
+    };
+
+    let any = ListFilter {
+        labels: vec![labels(&["frontend", "db"])],
+        ..empty_filter()
+    };
+    assert_eq!(names(&any), vec!["UI", "Schema", "Flaky UI"]);
+
+    let all = ListFilter {
+        labels: vec![labels(&["frontend"]), labels(&["flaky-test"])],
+        ..empty_filter()
+    };
+    assert_eq!(names(&all), vec!["Flaky UI"]);
+
+    let not = ListFilter {
+        labels: vec![labels(&["frontend"])],
+        exclude_labels: labels(&["flaky-test"]),
+        ..empty_filter()
+    };
+    assert_eq!(names(&not), vec!["UI"]);
+
+    let counts = ticket::label_counts(&conn).unwrap();
+    assert_eq!(counts[0], ("db".to_string(), 1));
+    assert_eq!(counts[2], ("frontend".to_string(), 2));
+}
+
+#[test]
+fn test_export_labels_text_and_json() {
+    let (conn, _tmp) = open_test_db();
+    create_labeled(&conn, "Fix build", &["ci", "flaky-test"]);
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    assert_eq!(
+        ticket::format_export_text(&exports[0]),
+        "T-1 [todo] Fix build #ci #flaky-test +ready"
+    );
+    let parsed = serde_json::to_value(&exports).unwrap();
+    assert_eq!(parsed[0]["labels"], serde_json::json!(["ci", "flaky-test"]));
+}
+
+// ---- Filter: parent ----
+
+#[test]
+fn test_filter_parent_and_top_level() {
+    let (conn, _tmp) = open_test_db();
+    let epic = ticket::create_ticket(&conn, "Epic", "").unwrap();
+    let new = ticket::NewTicket {
+        name: "Sub",
+        parent: Some(epic),
+        ..Default::default()
+    };
+    ticket::create_ticket_with(&conn, &new, None).unwrap();
+    ticket::create_ticket(&conn, "Loose", "").unwrap();
+
+    let children = ListFilter {
+        parent: Some(epic),
+        ..empty_filter()
+    };
+    let tickets = ticket::list_tickets_filtered(&conn, &children).unwrap();
+    assert_eq!(tickets.len(), 1);
+    assert_eq!(tickets[0].name, "Sub");
+
+    let top = ListFilter {
+        top_level: true,
+        ..empty_filter()
+    };
+    let names: Vec<String> = ticket::list_tickets_filtered(&conn, &top)
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(names, vec!["Epic", "Loose"]);
+
+    let exports = ticket::tickets_to_export(&conn, &children).unwrap();
+    assert_eq!(
+        ticket::format_export_text(&exports[0]),
+        "T-2 [todo] Sub parent:T-1 +ready"
+    );
+}
+
+// ---- Virtual states ----
+
+#[test]
+fn test_filter_ready_waiting_blocking() {
+    let (mut conn, _tmp) = open_test_db();
+    let base = ticket::create_ticket(&conn, "Base", "").unwrap();
+    let top = ticket::create_ticket(&conn, "Top", "").unwrap();
+    let solo = ticket::create_ticket(&conn, "Solo", "").unwrap();
+    let taken = ticket::create_ticket(&conn, "Taken", "").unwrap();
+    ticket::add_dep(&conn, top, base, None).unwrap();
+    ticket::claim_ticket(&mut conn, taken, "agent-1", false).unwrap();
+
+    let ids = |conn: &rusqlite::Connection, filter: &ListFilter| -> Vec<i64> {
+        ticket::list_tickets_filtered(conn, filter)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect()
+    };
+    let ready = ListFilter {
+        ready: true,
+        ..empty_filter()
+    };
+    let waiting = ListFilter {
+        waiting: true,
+        ..empty_filter()
+    };
+    let blocking = ListFilter {
+        blocking: true,
+        ..empty_filter()
+    };
+    assert_eq!(ids(&conn, &ready), vec![base, solo]);
+    assert_eq!(ids(&conn, &waiting), vec![top]);
+    assert_eq!(ids(&conn, &blocking), vec![base]);
+
+    let flags = ticket::ticket_flags(&conn, base).unwrap();
+    assert!(flags.ready && flags.blocking && !flags.waiting);
+
+    ticket::claim_ticket(&mut conn, base, "agent-1", false).unwrap();
+    ticket::update_ticket(&conn, base, None, None, Some("done")).unwrap();
+    assert_eq!(ids(&conn, &ready), vec![top, solo]);
+    assert!(ids(&conn, &waiting).is_empty());
+    assert!(ids(&conn, &blocking).is_empty());
+}
+
+#[test]
+fn test_export_flags_text_and_json() {
+    let (conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    assert_eq!(
+        ticket::format_export_text(&exports[0]),
+        "T-1 [todo] A +ready +blocking"
+    );
+    assert_eq!(
+        ticket::format_export_text(&exports[1]),
+        "T-2 [todo] B deps:T-1 +waiting"
+    );
+    let parsed = serde_json::to_value(&exports).unwrap();
+    assert_eq!(parsed[0]["ready"], true);
+    assert_eq!(parsed[0]["blocking"], true);
+    assert_eq!(parsed[1]["waiting"], true);
+    assert_eq!(parsed[1]["ready"], false);
+}
+
+// ---- Full-text search ----
+
+fn search_ids(conn: &rusqlite::Connection, query: &str) -> Vec<i64> {
+    ticket::search_tickets(conn, query, 20)
+        .unwrap()
+        .iter()
+        .map(|h| h.id)
+        .collect()
+}
+
+#[test]
+fn test_search_ranks_name_hits_first_and_tracks_edits() {
+    let (conn, _tmp) = open_test_db();
+    let desc_hit = ticket::create_ticket(&conn, "Cleanup", "parser leaks memory").unwrap();
+    let name_hit = ticket::create_ticket(&conn, "Parser rewrite", "").unwrap();
+    ticket::create_ticket(&conn, "Unrelated", "").unwrap();
+    assert_eq!(search_ids(&conn, "parser"), vec![name_hit, desc_hit]);
+
+    ticket::update_ticket(&conn, desc_hit, None, Some("nothing here"), None).unwrap();
+    assert_eq!(search_ids(&conn, "parser"), vec![name_hit]);
+
+    ticket::delete_ticket(&conn, name_hit).unwrap();
+    assert!(search_ids(&conn, "parser").is_empty());
+}
+
+#[test]
+fn test_search_covers_notes_and_query_syntax() {
+    let (conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "Deploy", "roll out the flaky service").unwrap();
+    let b = ticket::create_ticket(&conn, "Monitor", "").unwrap();
+    ticket::add_note(&conn, b, None, "saw flakiness in staging").unwrap();
+
+    assert_eq!(search_ids(&conn, "flak*"), vec![a, b]);
+    assert_eq!(search_ids(&conn, "staging"), vec![b]);
+    assert_eq!(search_ids(&conn, "\"flaky service\""), vec![a]);
+    assert_eq!(search_ids(&conn, "flak* NOT staging"), vec![a]);
+
+    let hits = ticket::search_tickets(&conn, "staging", 20).unwrap();
+    assert_eq!(hits[0].snippet, "saw flakiness in [staging]");
+}
+
+#[test]
+fn test_search_rejects_malformed_query() {
+    let (conn, _tmp) = open_test_db();
+    ticket::create_ticket(&conn, "A", "").unwrap();
+    let err = ticket::search_tickets(&conn, "\"unclosed", 20).unwrap_err();
+    assert!(matches!(err, ticket::AppError::InvalidSearch(_)));
+}
+
+#[test]
+fn test_search_reports_a_locked_database_as_a_database_error() {
+    let (conn, tmp) = open_test_db();
+    ticket::create_ticket(&conn, "A", "").unwrap();
+    drop(conn);
+    // A rollback journal, so one writer's lock shuts out readers.
+    let holder = rusqlite::Connection::open(&tmp).unwrap();
+    holder
+        .query_row("PRAGMA journal_mode = DELETE", [], |_| Ok(()))
+        .unwrap();
+    let conn = rusqlite::Connection::open(&tmp).unwrap();
+    conn.busy_timeout(std::time::Duration::ZERO).unwrap();
+    assert_eq!(search_ids(&conn, "a").len(), 1);
+
+    holder.execute_batch("BEGIN EXCLUSIVE").unwrap();
+    let err = ticket::search_tickets(&conn, "a", 20).unwrap_err();
+    assert_eq!(err.code(), "database", "{}", err);
+}
+
+// ---- Query language ----
+
+fn query_ids(conn: &rusqlite::Connection, q: &str) -> Vec<i64> {
+    let filter = ListFilter {
+        query: Some(rtik::query::parse(q).unwrap()),
+        ..ListFilter::default()
+    };
+    ticket::list_tickets_filtered(conn, &filter)
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect()
+}
+
+#[test]
+fn test_query_parses_into_typed_ast() {
+    use rtik::query::{parse, CmpOp, Expr, Predicate};
+    let expr = parse("status:(todo,blocked) AND NOT text:\"flaky test\" OR priority<=1").unwrap();
+    let expected = Expr::Or(
+        Box::new(Expr::And(
+            Box::new(Expr::Pred(Predicate::Status(vec![
+                Status::Todo,
+                Status::Blocked,
+            ]))),
+            Box::new(Expr::Not(Box::new(Expr::Pred(Predicate::Text(vec![
+                "flaky test".into(),
+            ]))))),
+        )),
+        Box::new(Expr::Pred(Predicate::Priority(CmpOp::Le, vec![1]))),
+    );
+    assert_eq!(expr, expected);
+    // Juxtaposition is AND; bare words are text searches.
+    assert_eq!(
+        parse("label:db deploy").unwrap(),
+        Expr::And(
+            Box::new(Expr::Pred(Predicate::Label(vec!["db".into()]))),
+            Box::new(Expr::Pred(Predicate::Text(vec!["deploy".into()]))),
+        )
+    );
+}
+
+#[test]
+fn test_query_errors_report_column() {
+    use rtik::query::parse;
+    // Unknown names are checked against the workflow when the query runs.
+    let err = parse("status:(todo,no.pe)").unwrap_err();
+    assert_eq!(err.column, 14);
+    assert!(err.message.contains("invalid status 'no.pe'"));
+    assert_eq!(parse("status:(todo").unwrap_err().column, 13);
+    assert_eq!(parse("label:a AND").unwrap_err().column, 12);
+    assert_eq!(parse("bogus:1").unwrap_err().column, 1);
+    assert_eq!(parse("label>a").unwrap_err().column, 6);
+    assert_eq!(parse("text:\"open").unwrap_err().column, 6);
+    let err = parse("created>200é").unwrap_err();
+    assert_eq!(err.column, 9);
+    assert!(
+        err.message.contains("invalid time '200é'"),
+        "{}",
+        err.message
+    );
+    assert_eq!(parse("updated<é").unwrap_err().column, 9);
+}
+
+#[test]
+fn test_query_status_claimer_glob_and_not() {
+    let (mut conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "flaky on CI").unwrap();
+    let c = ticket::create_ticket(&conn, "C", "").unwrap();
+    let d = ticket::create_ticket(&conn, "D", "").unwrap();
+    ticket::claim_ticket(&mut conn, a, "bot-1", false).unwrap();
+    ticket::claim_ticket(&mut conn, b, "bot-2", false).unwrap();
+    ticket::claim_ticket(&mut conn, c, "human", false).unwrap();
+    ticket::block_ticket(&conn, c, "waiting on review", None).unwrap();
+
+    assert_eq!(query_ids(&conn, "claimer:bot-*"), vec![a, b]);
+    assert_eq!(
+        query_ids(&conn, "claimer:bot-* AND NOT text:\"flaky\""),
+        vec![a]
+    );
+    // Unclaimed tickets still satisfy a negated claimer test.
+    assert_eq!(query_ids(&conn, "NOT claimer:bot-*"), vec![c, d]);
+    assert_eq!(
+        query_ids(&conn, "status:(todo,blocked) OR claimer:bot-1"),
+        vec![a, c, d]
+    );
+    assert_eq!(query_ids(&conn, "is:unclaimed"), vec![d]);
+}
+
+#[test]
+fn test_query_dates_priority_and_ids() {
+    let (conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    let c = ticket::create_ticket(&conn, "C", "").unwrap();
+    conn.execute(
+        "UPDATE tickets SET updated_at = '2026-09-30T23:00:00Z' WHERE id = ?1",
+        [a],
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE tickets SET updated_at = '2026-10-01T08:00:00Z', priority = 0 WHERE id = ?1",
+        [b],
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE tickets SET updated_at = '2026-10-02T00:00:00Z' WHERE id = ?1",
+        [c],
+    )
+    .unwrap();
+
+    // A bare date covers the whole day.
+    assert_eq!(query_ids(&conn, "updated:2026-10-01"), vec![b]);
+    assert_eq!(query_ids(&conn, "updated>2026-10-01"), vec![c]);
+    assert_eq!(query_ids(&conn, "updated>=2026-10-01"), vec![b, c]);
+    assert_eq!(query_ids(&conn, "updated<2026-10"), vec![a]);
+    assert_eq!(query_ids(&conn, "priority:P0"), vec![b]);
+    assert_eq!(query_ids(&conn, "priority>0"), vec![a, c]);
+    assert_eq!(query_ids(&conn, "id:(1,3)"), vec![a, c]);
+}
+
+// ---- Saved views ----
+
+fn argv(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_view_save_list_replace_and_delete() {
+    use rtik::view;
+    let (conn, _tmp) = open_test_db();
+    let replaced = view::save_view(&conn, "mine", &argv(&["--claimer", "me"]), None).unwrap();
+    assert!(!replaced);
+    let replaced = view::save_view(
+        &conn,
+        "mine",
+        &argv(&["--claimer", "me", "--sort", "priority"]),
+        None,
+    )
+    .unwrap();
+    assert!(replaced);
+    view::save_view(&conn, "blocked", &argv(&["--status", "blocked"]), None).unwrap();
+
+    let views = view::list_views(&conn).unwrap();
+    let names: Vec<&str> = views.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, vec!["blocked", "mine"]);
+    assert_eq!(
+        views[1].args,
+        argv(&["--claimer", "me", "--sort", "priority"])
+    );
+
+    view::delete_view(&conn, "mine").unwrap();
+    assert!(matches!(
+        view::get_view(&conn, "mine"),
+        Err(ticket::AppError::ViewNotFound(_))
+    ));
+    assert!(matches!(
+        view::save_view(&conn, "@bad name", &[], None),
+        Err(ticket::AppError::InvalidViewName(_))
+    ));
+}
+
+#[test]
+fn test_view_expansion_splices_saved_args() {
+    use rtik::view;
+    let (conn, _tmp) = open_test_db();
+    view::save_view(&conn, "todo", &argv(&["--status", "todo"]), None).unwrap();
+
+    let expanded =
+        view::expand_view(&conn, argv(&["rtik", "ls", "@todo", "--label", "db"])).unwrap();
+    assert_eq!(
+        expanded,
+        argv(&["rtik", "ls", "--status", "todo", "--label", "db"])
+    );
+    // Only the argument right after list/export is a view reference.
+    let untouched = argv(&["rtik", "create", "-n", "@todo"]);
+    assert_eq!(view::view_ref(&untouched), None);
+    assert!(matches!(
+        view::expand_view(&conn, argv(&["rtik", "export", "@missing"])),
+        Err(ticket::AppError::ViewNotFound(_))
+    ));
+}
+
+// ---- Pagination ----
+
+fn page_ids(page: &ticket::TicketPage) -> Vec<i64> {
+    page.tickets.iter().map(|t| t.id).collect()
+}
+
+#[test]
+fn test_limit_and_cursor_walk_every_ticket_once() {
+    let (conn, _tmp) = open_test_db();
+    for (name, pri) in [("a", 2), ("b", 0), ("c", 2), ("d", 1), ("e", 0)] {
+        ticket::create_ticket_with(
+            &conn,
+            &ticket::NewTicket {
+                name,
+                priority: Some(pri),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+    }
+    let mut filter = ListFilter {
+        sort: vec![ticket::SortKey {
+            field: ticket::SortField::Priority,
+            descending: false,
+        }],
+        limit: Some(2),
+        ..ListFilter::default()
+    };
+    let mut seen = Vec::new();
+    loop {
+        let page = ticket::list_tickets_page(&conn, &filter).unwrap();
+        seen.extend(page_ids(&page));
+        match page.next_cursor {
+            Some(cursor) => filter.after = Some(cursor),
+            None => break,
+        }
+    }
+    assert_eq!(seen, vec![2, 5, 4, 1, 3]);
+
+    filter.after = Some(4);
+    filter.reverse = true;
+    let page = ticket::list_tickets_page(&conn, &filter).unwrap();
+    assert_eq!(page_ids(&page), vec![5, 2]);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn test_offset_and_exact_final_page() {
+    let (conn, _tmp) = open_test_db();
+    for name in ["a", "b", "c", "d"] {
+        ticket::create_ticket(&conn, name, "").unwrap();
+    }
+    let filter = ListFilter {
+        limit: Some(2),
+        offset: 2,
+        ..ListFilter::default()
+    };
+    let page = ticket::list_tickets_page(&conn, &filter).unwrap();
+    assert_eq!(page_ids(&page), vec![3, 4]);
+    // No ticket follows, so there is no cursor even though the page is full.
+    assert_eq!(page.next_cursor, None);
+
+    let exported = ticket::export_page(
+        &conn,
+        &ListFilter {
+            limit: Some(1),
+            ..ListFilter::default()
+        },
+    )
+    .unwrap();
+    let json = serde_json::to_value(&exported).unwrap();
+    assert_eq!(json["next_cursor"], 1);
+    assert_eq!(json["tickets"].as_array().unwrap().len(), 1);
+}
+
+// ---- Budgeted export ----
+
+#[test]
+fn test_truncate_description_prefers_sentence_then_word() {
+    use rtik::budget::truncate_description;
+    let desc = "First sentence here. Second sentence runs on for a while longer.";
+    assert_eq!(truncate_description(desc, 100), desc);
+    assert_eq!(truncate_description(desc, 30), "First sentence here.");
+    assert_eq!(
+        truncate_description("alpha beta gamma delta", 13),
+        "alpha beta…"
+    );
+    assert_eq!(
+        truncate_description("multi\n\nline   text", 50),
+        "multi line text"
+    );
+}
+
+#[test]
+fn test_budget_orders_by_relevance_and_summarizes_rest() {
+    let (mut conn, _tmp) = open_test_db();
+    let done = ticket::create_ticket(&conn, "Shipped", "").unwrap();
+    ticket::update_ticket(&conn, done, None, None, Some("in-progress")).unwrap();
+    ticket::update_ticket(&conn, done, None, None, Some("done")).unwrap();
+    let blocked = ticket::create_ticket(&conn, "Stuck", "").unwrap();
+    ticket::block_ticket(&conn, blocked, "vendor", None).unwrap();
+    let waiting = ticket::create_ticket(&conn, "Later", "").unwrap();
+    let ready = ticket::create_ticket(&conn, "Ready one", "Do the thing.").unwrap();
+    ticket::add_dep(&conn, waiting, ready, None).unwrap();
+    let theirs = ticket::create_ticket(&conn, "Theirs", "").unwrap();
+    ticket::claim_ticket(&mut conn, theirs, "other", false).unwrap();
+    let mine = ticket::create_ticket(&conn, "Mine", "").unwrap();
+    ticket::claim_ticket(&mut conn, mine, "me", false).unwrap();
+
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    let all = rtik::budget::render_budgeted(
+        &exports,
+        Some("me"),
+        10_000,
+        &Workflow::default(),
+        &IdPrefix::default(),
+    );
+    let order: Vec<&str> = all
+        .lines()
+        .filter(|l| l.starts_with("T-"))
+        .map(|l| l.split(' ').next().unwrap())
+        .collect();
+    assert_eq!(order, vec!["T-6", "T-5", "T-4", "T-2", "T-1", "T-3"]);
+    assert!(all.contains("T-4 [todo] Ready one +ready +blocking\n  Do the thing.\n"));
+    assert!(
+        !all.contains(" more "),
+        "nothing omitted, so no summary: {all}"
+    );
+
+    let small = rtik::budget::render_budgeted(
+        &exports,
+        Some("me"),
+        80,
+        &Workflow::default(),
+        &IdPrefix::default(),
+    );
+    assert!(small.chars().count() <= 80, "{small}");
+    assert_eq!(
+        small,
+        "T-6 [in-progress] Mine\nT-5 [in-progress] Theirs\n+2 more todo, 1 blocked, 1 done\n"
+    );
+}
+
+// ---- Markdown export ----
+
+#[test]
+fn test_markdown_groups_by_status_with_checkboxes() {
+    use rtik::markdown::{render_markdown, Grouping};
+    let (mut conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "Schema", "Two tables.\n\nSee notes.").unwrap();
+    let b = ticket::create_ticket(&conn, "API", "").unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    ticket::claim_ticket(&mut conn, a, "bot-1", false).unwrap();
+    ticket::update_ticket(&conn, a, None, None, Some("done")).unwrap();
+    let c = ticket::create_ticket(&conn, "Deploy", "").unwrap();
+    ticket::block_ticket(&conn, c, "no credentials", None).unwrap();
+
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    let md = render_markdown(
+        &exports,
+        Grouping::Status,
+        &Workflow::default(),
+        &IdPrefix::default(),
+    );
+    assert_eq!(
+        md,
+        "# Tickets\n\
+         \n## Todo (1)\n\n\
+         - [ ] #2 **API** — depends on #1\n\
+         \n## Blocked (1)\n\n\
+         - [ ] #3 **Deploy**\n  > Blocked: no credentials\n\
+         \n## Done (1)\n\n\
+         - [x] #1 **Schema**\n  Two tables.\n\n  See notes.\n"
+    );
+}
+
+#[test]
+fn test_markdown_groups_by_claimer_and_label() {
+    use rtik::markdown::{render_markdown, Grouping};
+    let (mut conn, _tmp) = open_test_db();
+    let a = create_labeled(&conn, "A", &["db", "api"]);
+    create_labeled(&conn, "B", &[]);
+    ticket::claim_ticket(&mut conn, a, "bot-1", false).unwrap();
+
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    let by_claimer = render_markdown(
+        &exports,
+        Grouping::Claimer,
+        &Workflow::default(),
+        &IdPrefix::default(),
+    );
+    assert!(by_claimer.contains("## @bot-1 (1)\n\n- [ ] #1 **A** — `in-progress` · @bot-1"));
+    assert!(by_claimer.contains("## Unclaimed (1)\n\n- [ ] #2 **B** — `todo`\n"));
+
+    let by_label = render_markdown(
+        &exports,
+        Grouping::Label,
+        &Workflow::default(),
+        &IdPrefix::default(),
+    );
+    let headings: Vec<&str> = by_label.lines().filter(|l| l.starts_with("## ")).collect();
+    assert_eq!(
+        headings,
+        vec!["## #api (1)", "## #db (1)", "## Unlabelled (1)"]
+    );
+}
+
+// ---- JSON Lines and CSV ----
+
+#[test]
+fn test_for_each_export_streams_every_field() {
+    let (mut conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    ticket::claim_ticket(&mut conn, a, "bot", false).unwrap();
+    ticket::block_ticket(&conn, b, "needs keys", None).unwrap();
+
+    let mut lines = Vec::new();
+    ticket::for_each_export(&conn, &empty_filter(), |e| {
+        lines.push(serde_json::to_string(&e).unwrap());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(lines.len(), 2);
+    let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(first["claimed_by"], "bot");
+    assert!(first["claimed_at"].is_string());
+    assert!(first["lease_expires_at"].is_string());
+    assert!(first["created_at"].is_string());
+    assert!(first["updated_at"].is_string());
+    let second: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(second["block_reason"], "needs keys");
+
+    let mut count = 0;
+    let filter = ListFilter {
+        limit: Some(1),
+        ..ListFilter::default()
+    };
+    ticket::for_each_export(&conn, &filter, |_| {
+        count += 1;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_csv_header_columns_and_quoting() {
+    use rtik::cli::Column;
+    let (conn, _tmp) = open_test_db();
+    let a = create_labeled(&conn, "Plain", &["db", "api"]);
+    let b = ticket::create_ticket(&conn, "Say \"hi\", then leave", "line one\nline two").unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    import(
+        &conn,
+        r#"{"name": "Keyed", "external_key": "JIRA-7"}"#,
+        false,
+        false,
+    )
+    .unwrap();
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+
+    let header = rtik::csv::header(&rtik::csv::ALL_COLUMNS);
+    assert!(header.starts_with(
+        "id,name,description,status,priority,parent_id,claimed_by,claimed_at,lease_expires_at,block_reason"
+    ));
+    assert!(header.ends_with(",created_at,updated_at,external_key"));
+    let keyed = rtik::csv::record(&exports[2], &rtik::csv::ALL_COLUMNS);
+    assert!(
+        keyed.starts_with("3,Keyed,") && keyed.ends_with(",JIRA-7"),
+        "{keyed}"
+    );
+    assert!(rtik::csv::record(&exports[0], &rtik::csv::ALL_COLUMNS).ends_with(','));
+    assert_eq!(
+        rtik::csv::record(&exports[2], &[Column::Id, Column::ExternalKey]),
+        "3,JIRA-7"
+    );
+    let columns = [
+        Column::Id,
+        Column::Name,
+        Column::Description,
+        Column::Labels,
+        Column::Dependencies,
+    ];
+    assert_eq!(
+        rtik::csv::header(&columns),
+        "id,name,description,labels,dependencies"
+    );
+    assert_eq!(
+        rtik::csv::record(&exports[0], &columns),
+        "1,Plain,,\"api,db\","
+    );
+    assert_eq!(
+        rtik::csv::record(&exports[1], &columns),
+        "2,\"Say \"\"hi\"\", then leave\",\"line one\nline two\",,1"
+    );
+}
+
+// ---- Import ----
+
+fn import(
+    conn: &rusqlite::Connection,
+    input: &str,
+    upsert: bool,
+    dry_run: bool,
+) -> Result<rtik::import::ImportReport, ticket::AppError> {
+    let records = rtik::import::parse_import(input, None)?;
+    rtik::import::import_tickets(
+        conn,
+        &records,
+        rtik::import::ImportOptions { upsert, dry_run },
+        None,
+    )
+}
+
+#[test]
+fn test_import_remaps_ids_and_resolves_deps() {
+    let (conn, _tmp) = open_test_db();
+    ticket::create_ticket(&conn, "Existing", "").unwrap();
+
+    let input = r#"[
+        {"id": 1, "name": "Schema", "labels": ["db"], "status": "done"},
+        {"id": "api", "name": "API", "parent_id": 1, "dependencies": [1],
+         "notes": [{"author": "planner", "body": "start here"}]},
+        {"id": 3, "name": "Waiting", "status": "blocked", "block_reason": "vendor",
+         "dependencies": ["api", 1]}
+    ]"#;
+    let report = import(&conn, input, false, false).unwrap();
+    let ids: Vec<i64> = report.tickets.iter().map(|t| t.id).collect();
+    assert_eq!(ids, vec![2, 3, 4]);
+    assert_eq!(report.tickets[1].source_id.as_deref(), Some("api"));
+    assert_eq!(report.dependencies, 3);
+
+    let schema = ticket::get_ticket(&conn, 2).unwrap();
+    assert_eq!(schema.status, "done");
+    assert_eq!(ticket::list_labels(&conn, 2).unwrap(), vec!["db"]);
+    let api = ticket::get_ticket(&conn, 3).unwrap();
+    assert_eq!(api.parent_id, Some(2));
+    assert_eq!(ticket::list_deps(&conn, 3).unwrap().forward, vec![2]);
+    assert_eq!(
+        ticket::list_notes(&conn, 3, None).unwrap()[0].body,
+        "start here"
+    );
+    let waiting = ticket::get_ticket(&conn, 4).unwrap();
+    assert_eq!(waiting.status, "blocked");
+    assert_eq!(waiting.block_reason.as_deref(), Some("vendor"));
+    assert_eq!(ticket::list_deps(&conn, 4).unwrap().forward, vec![2, 3]);
+}
+
+#[test]
+fn test_import_round_trips_export_and_jsonl() {
+    let (conn, _tmp) = open_test_db();
+    let a = ticket::create_ticket(&conn, "A", "desc").unwrap();
+    let b = ticket::create_ticket(&conn, "B", "").unwrap();
+    ticket::add_dep(&conn, b, a, None).unwrap();
+    let exported = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    let jsonl: String = exported
+        .iter()
+        .map(|e| serde_json::to_string(e).unwrap() + "\n")
+        .collect();
+
+    let (other, _tmp2) = open_test_db();
+    let report = import(&other, &jsonl, false, false).unwrap();
+    assert_eq!(report.tickets.len(), 2);
+    let again = ticket::tickets_to_export(&other, &empty_filter()).unwrap();
+    assert_eq!(again[0].description, "desc");
+    assert_eq!(again[1].dependencies, vec![again[0].id]);
+
+    let err = rtik::import::parse_import("{\"name\": \"ok\"}\nnot json\n", None).unwrap_err();
+    assert!(err.to_string().contains("line 2"), "got: {err}");
+}
+
+#[test]
+fn test_import_rejects_cycles_and_unknown_refs_atomically() {
+    let (conn, _tmp) = open_test_db();
+    let input = r#"[
+        {"id": "x", "name": "X", "dependencies": ["y"]},
+        {"id": "y", "name": "Y", "dependencies": ["x"]}
+    ]"#;
+    let err = import(&conn, input, false, false).unwrap_err();
+    assert!(matches!(err, ticket::AppError::CyclicDependency(_)));
+    assert!(err.to_string().contains("'x'"), "got: {err}");
+
+    let input = r#"[{"id": "x", "name": "X", "dependencies": ["nope"]}]"#;
+    let err = import(&conn, input, false, false).unwrap_err();
+    assert!(matches!(err, ticket::AppError::InvalidImport(_)));
+
+    let input = r#"[{"id": "x", "name": "X", "parent_id": "y"}, {"id": "y", "name": "Y", "parent_id": "x"}]"#;
+    assert!(import(&conn, input, false, false).is_err());
+
+    assert!(ticket::list_tickets(&conn).unwrap().is_empty());
+}
+
+#[test]
+fn test_import_dry_run_writes_nothing() {
+    let (conn, _tmp) = open_test_db();
+    let input = r#"[{"id": "a", "name": "A"}, {"id": "b", "name": "B", "dependencies": ["a"]}]"#;
+    let report = import(&conn, input, false, true).unwrap();
+    assert!(report.dry_run);
+    assert_eq!(report.tickets.len(), 2);
+    assert_eq!(report.dependencies, 1);
+    assert!(ticket::list_tickets(&conn).unwrap().is_empty());
+    assert!(ticket::list_events(&conn, None, None).unwrap().is_empty());
+}
+
+#[test]
+fn test_import_upsert_matches_external_key() {
+    use rtik::import::ImportAction;
+    let (conn, _tmp) = open_test_db();
+    let first = r#"[
+        {"id": "a", "key": "JIRA-1", "name": "Schema"},
+        {"id": "b", "key": "JIRA-2", "name": "API"}
+    ]"#;
+    import(&conn, first, true, false).unwrap();
+
+    let err = import(&conn, first, false, false).unwrap_err();
+    assert!(err.to_string().contains("--upsert"), "got: {err}");
+
+    let second = r#"[
+        {"id": "a", "key": "JIRA-1", "name": "Schema"},
+        {"id": "b", "key": "JIRA-2", "name": "API v2", "dependencies": ["a"]},
+        {"id": "c", "key": "JIRA-3", "name": "Docs", "priority": 0}
+    ]"#;
+    let report = import(&conn, second, true, false).unwrap();
+    let actions: Vec<ImportAction> = report.tickets.iter().map(|t| t.action).collect();
+    assert_eq!(
+        actions,
+        vec![
+            ImportAction::Unchanged,
+            ImportAction::Updated,
+            ImportAction::Created
+        ]
+    );
+    assert_eq!(report.tickets[1].id, 2);
+    assert_eq!(ticket::get_ticket(&conn, 2).unwrap().name, "API v2");
+    assert_eq!(ticket::list_deps(&conn, 2).unwrap().forward, vec![1]);
+    assert_eq!(ticket::list_tickets(&conn).unwrap().len(), 3);
+
+    let exports = ticket::tickets_to_export(&conn, &empty_filter()).unwrap();
+    assert_eq!(exports[2].external_key.as_deref(), Some("JIRA-3"));
+}
+
+#[test]
+fn test_parse_markdown_checklist() {
+    use rtik::import::{parse_markdown, Nesting};
+    let plan = "# Plan\n\
+                \n\
+                Intro prose.\n\
+                \n\
+                - [ ] Storage\n  \
+                  Pick the schema.\n\
+                \n  \
+                  Keep it boring.\n  \
+                  - [x] Draft schema\n  \
+                  - [ ] Migrations\n    \
+                    Use rusqlite_migration.\n\
+                * [ ] API\n\
+                - plain bullet\n\
+                1. [X] Docs\n";
+    let records = parse_markdown(plan, Nesting::Parent);
+    let names: Vec<&str> = records.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["Storage", "Draft schema", "Migrations", "API", "Docs"]
+    );
+    assert_eq!(records[0].id.as_deref(), Some("L5"));
+    assert_eq!(
+        records[0].description,
+        "Pick the schema.\n\nKeep it boring."
+    );
+    assert_eq!(records[1].status.as_deref(), Some("done"));
+    assert_eq!(records[1].parent_id.as_deref(), Some("L5"));
+    assert_eq!(records[2].description, "Use rusqlite_migration.");
+    assert_eq!(records[3].parent_id, None);
+    assert_eq!(records[4].status.as_deref(), Some("done"));
+
+    let records = parse_markdown(plan, Nesting::Deps);
+    assert_eq!(records[0].dependencies, vec!["L9", "L10"]);
+    assert_eq!(records[1].parent_id, None);
+}
+
+#[test]
+fn test_import_markdown_plan() {
+    use rtik::import::{import_tickets, parse_markdown, ImportOptions, Nesting};
+    let (conn, _tmp) = open_test_db();
+    let plan = "- [ ] Ship\n  - [ ] Build\n  - [x] Test\n";
+
+    let records = parse_markdown(plan, Nesting::Deps);
+    let report = import_tickets(&conn, &records, ImportOptions::default(), None).unwrap();
+    let mapping: Vec<(Option<&str>, i64)> = report
+        .tickets
+        .iter()
+        .map(|t| (t.source_id.as_deref(), t.id))
+        .collect();
+    assert_eq!(
+        mapping,
+        vec![(Some("L1"), 1), (Some("L2"), 2), (Some("L3"), 3)]
+    );
+    assert_eq!(ticket::list_deps(&conn, 1).unwrap().forward, vec![2, 3]);
+    assert_eq!(ticket::get_ticket(&conn, 3).unwrap().status, "done");
+
+    let records = parse_markdown(plan, Nesting::Parent);
+    import_tickets(&conn, &records, ImportOptions::default(), None).unwrap();
+    assert_eq!(ticket::get_ticket(&conn, 5).unwrap().parent_id, Some(4));
+    assert!(ticket::list_deps(&conn, 4).unwrap().forward.is_empty());
+}
@@ -0,0 +1,305 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// A running `rtik serve`, killed on drop.
+struct Server {
+    child: Child,
+    url: String,
+    _db: tempfile::NamedTempFile,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn start(listen: &str) -> Server {
+    let db = tempfile::NamedTempFile::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rtik"))
+        .args(["serve", "--listen", listen])
+        .env("RTIK_DB", db.path())
+        .env("RTIK_AGENT", "server")
+        .env_remove("RTIK_OUTPUT")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("spawn rtik serve");
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let url = line
+        .trim()
+        .strip_prefix("Listening on ")
+        .unwrap_or_else(|| panic!("unexpected banner {line:?}"))
+        .to_string();
+    Server {
+        child,
+        url,
+        _db: db,
+    }
+}
+
+fn exchange(
+    mut stream: impl Read + Write,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+) -> (u16, Value) {
+    let body = body.unwrap_or("");
+    // Host and, with a body, Content-Type get defaults unless given.
+    let given = |name: &str| headers.iter().any(|(k, _)| k.eq_ignore_ascii_case(name));
+    let mut defaults = Vec::new();
+    if !given("Host") {
+        defaults.push(("Host", "localhost"));
+    }
+    if !body.is_empty() && !given("Content-Type") {
+        defaults.push(("Content-Type", "application/json"));
+    }
+    let headers = headers.iter().copied().chain(defaults);
+    let mut req = format!(
+        "{method} {path} HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (k, v) in headers.filter(|(_, v)| !v.is_empty()) {
+        req.push_str(&format!("{k}: {v}\r\n"));
+    }
+    req.push_str("\r\n");
+    req.push_str(body);
+    stream.write_all(req.as_bytes()).unwrap();
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).unwrap();
+    let (head, payload) = raw.split_once("\r\n\r\n").expect("complete response");
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    let value = serde_json::from_str(payload).unwrap_or(Value::String(payload.to_string()));
+    (status, value)
+}
+
+impl Server {
+    fn request_with(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let addr = self.url.strip_prefix("http://").unwrap();
+        let stream = std::net::TcpStream::connect(addr).unwrap();
+        let body = body.map(|b| b.to_string());
+        exchange(stream, method, path, headers, body.as_deref())
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        self.request_with(method, path, &[], body)
+    }
+}
+
+#[test]
+fn http_api_crud_and_error_statuses() {
+    let server = start("127.0.0.1:0");
+
+    let (status, a) = server.request(
+        "POST",
+        "/tickets",
+        Some(json!({"name": "Schema", "labels": ["db"]})),
+    );
+    assert_eq!(status, 201);
+    assert_eq!(a["id"], 1);
+    let (_, _) = server.request(
+        "POST",
+        "/tickets",
+        Some(json!({"name": "API", "priority": 1})),
+    );
+
+    let (status, page) = server.request("GET", "/tickets?label=db", None);
+    assert_eq!(status, 200);
+    assert_eq!(page["tickets"].as_array().unwrap().len(), 1);
+    assert_eq!(page["tickets"][0]["name"], "Schema");
+
+    let (status, patched) = server.request(
+        "PATCH",
+        "/tickets/2",
+        Some(json!({"description": "REST layer"})),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(patched["description"], "REST layer");
+
+    let (status, dep) = server.request("POST", "/tickets/2/deps", Some(json!({"depends_on": 1})));
+    assert_eq!(status, 200);
+    assert_eq!(dep["dependencies"], json!([1]));
+    let (status, err) = server.request("POST", "/tickets/1/deps", Some(json!({"depends_on": 2})));
+    assert_eq!(status, 409);
+    assert_eq!(err["code"], "cyclic_dependency");
+
+    // The header names the agent; the server's RTIK_AGENT is only a default.
+    let (status, claimed) =
+        server.request_with("POST", "/tickets/next", &[("X-Rtik-Agent", "bot")], None);
+    assert_eq!(status, 200);
+    assert_eq!(claimed["id"], 1);
+    assert_eq!(claimed["claimed_by"], "bot");
+    assert_eq!(claimed["warnings"], json!([]));
+
+    let (status, err) = server.request("POST", "/tickets/1/release", None);
+    assert_eq!(status, 403);
+    assert_eq!(err["code"], "not_owner");
+
+    let (status, err) = server.request("GET", "/tickets/99", None);
+    assert_eq!(status, 404);
+    assert_eq!(err["code"], "not_found");
+
+    let (status, err) = server.request("POST", "/tickets/1/block", Some(json!({"reason": ""})));
+    assert_eq!(status, 400);
+    assert_eq!(err["code"], "block_reason_required");
+
+    let (status, err) = server.request("PATCH", "/tickets/1", Some(json!({"priority": "high"})));
+    assert_eq!(status, 400);
+    assert_eq!(err["code"], "invalid_params");
+
+    let (status, err) = server.request("PUT", "/tickets/1", None);
+    assert_eq!(status, 405);
+    assert_eq!(err["code"], "method_not_allowed");
+
+    let (status, _) = server.request("GET", "/nowhere", None);
+    assert_eq!(status, 404);
+
+    let (status, text) = server.request("GET", "/export?format=markdown", None);
+    assert_eq!(status, 200);
+    assert!(text.as_str().unwrap().contains("**Schema**"));
+
+    let (status, deleted) = server.request("DELETE", "/tickets/2", None);
+    assert_eq!(status, 200);
+    assert_eq!(deleted["deleted"], true);
+}
+
+#[test]
+fn rejects_hosts_other_than_this_server() {
+    let server = start("127.0.0.1:0");
+    let addr = server.url.strip_prefix("http://").unwrap().to_string();
+
+    for host in ["localhost:7777", addr.as_str(), "127.0.0.2", "[::1]:80"] {
+        let (status, _) = server.request_with("GET", "/labels", &[("Host", host)], None);
+        assert_eq!(status, 200, "{host}");
+    }
+    // An empty value drops the header altogether.
+    for host in ["evil.example", "evil.example:7777", "10.0.0.1", ""] {
+        let (status, err) = server.request_with(
+            "POST",
+            "/tickets",
+            &[("Host", host)],
+            Some(json!({"name": "Sneaky"})),
+        );
+        assert_eq!(status, 403, "{host}");
+        assert_eq!(err["code"], "invalid_host");
+    }
+    let (_, page) = server.request("GET", "/tickets", None);
+    assert_eq!(page["tickets"], json!([]));
+}
+
+#[test]
+fn rejects_bodies_not_sent_as_json() {
+    let server = start("127.0.0.1:0");
+    let body = Some(json!({"name": "Form post"}));
+
+    // An empty value drops the header altogether.
+    for content_type in ["text/plain", "application/x-www-form-urlencoded", ""] {
+        let (status, err) = server.request_with(
+            "POST",
+            "/tickets",
+            &[("Content-Type", content_type)],
+            body.clone(),
+        );
+        assert_eq!(status, 415, "{content_type}");
+        assert_eq!(err["code"], "unsupported_media_type");
+    }
+    let (_, page) = server.request("GET", "/tickets", None);
+    assert_eq!(page["tickets"], json!([]));
+
+    let (status, _) = server.request_with(
+        "POST",
+        "/tickets",
+        &[("Content-Type", "Application/JSON; charset=utf-8")],
+        body,
+    );
+    assert_eq!(status, 201);
+}
+
+#[test]
+fn refuses_bodies_over_the_limit() {
+    let server = start("127.0.0.1:0");
+    let name = "x".repeat(rtik::server::MAX_BODY as usize);
+    let (status, err) = server.request("POST", "/tickets", Some(json!({ "name": name })));
+    assert_eq!(status, 413);
+    assert_eq!(err["code"], "body_too_large");
+    let (_, page) = server.request("GET", "/tickets", None);
+    assert_eq!(page["tickets"], json!([]));
+}
+
+#[test]
+fn events_long_poll_wakes_on_change() {
+    let server = start("127.0.0.1:0");
+    server.request("POST", "/tickets", Some(json!({"name": "Watch me"})));
+
+    let (_, events) = server.request("GET", "/events", None);
+    let cursor = events["cursor"].as_i64().unwrap();
+    assert!(cursor > 0);
+
+    // Nothing new: the request is held for the whole wait.
+    let started = Instant::now();
+    let (_, empty) = server.request("GET", &format!("/events?after={cursor}&wait=1"), None);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(empty["events"], json!([]));
+    assert_eq!(empty["cursor"], cursor);
+
+    let url = server.url.clone();
+    let waiter = std::thread::spawn(move || {
+        let addr = url.strip_prefix("http://").unwrap();
+        let stream = std::net::TcpStream::connect(addr).unwrap();
+        let started = Instant::now();
+        let resp = exchange(
+            stream,
+            "GET",
+            &format!("/events?after={cursor}&wait=30"),
+            &[],
+            None,
+        );
+        (resp, started.elapsed())
+    });
+    std::thread::sleep(Duration::from_millis(300));
+    server.request("POST", "/tickets/1/notes", Some(json!({"body": "looking"})));
+
+    let ((status, events), elapsed) = waiter.join().unwrap();
+    assert_eq!(status, 200);
+    assert!(elapsed < Duration::from_secs(10), "long poll did not wake");
+    assert_eq!(events["events"][0]["kind"], "note");
+    assert_eq!(events["events"][0]["actor"], "server");
+    assert!(events["cursor"].as_i64().unwrap() > cursor);
+}
+
+#[cfg(unix)]
+#[test]
+fn serves_on_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let sock = dir.path().join("rtik.sock");
+    let server = start(sock.to_str().unwrap());
+    assert_eq!(server.url, format!("unix:{}", sock.display()));
+
+    let stream = std::os::unix::net::UnixStream::connect(&sock).unwrap();
+    let (status, created) = exchange(stream, "POST", "/tickets", &[], Some(r#"{"name":"Local"}"#));
+    assert_eq!(status, 201);
+    assert_eq!(created["name"], "Local");
+}
+
+#[test]
+fn refuses_non_loopback_addresses() {
+    let out = Command::new(env!("CARGO_BIN_EXE_rtik"))
+        .args(["serve", "--listen", "0.0.0.0:0"])
+        .env("RTIK_DB", tempfile::NamedTempFile::new().unwrap().path())
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("only loopback"));
+}
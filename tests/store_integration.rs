@@ -0,0 +1,181 @@
+use rtik::ticket::{ChildPolicy, NewTicket, SortField, DEFAULT_LEASE_SECS};
+use rtik::{AppError, ListFilter, Status, Store, TicketId, TicketPatch};
+
+fn open_store() -> (Store, tempfile::TempPath) {
+    let tmp = tempfile::NamedTempFile::new().expect("tempfile");
+    let store = Store::open(tmp.path()).expect("open store");
+    (store, tmp.into_temp_path())
+}
+
+fn create(store: &Store, name: &str) -> TicketId {
+    store
+        .create(&NewTicket {
+            name,
+            ..Default::default()
+        })
+        .unwrap()
+}
+
+#[test]
+fn store_ticket_lifecycle() {
+    let (store, _tmp) = open_store();
+    let mut store = store.with_agent("worker");
+    let id = create(&store, "Write schema");
+    assert_eq!(id, TicketId(1));
+
+    let t = store
+        .update(
+            id,
+            &TicketPatch::new()
+                .description("tables and indexes")
+                .priority(1)
+                .add_label("db"),
+        )
+        .unwrap();
+    assert_eq!(t.description, "tables and indexes");
+    assert_eq!(t.priority, 1);
+    assert_eq!(t.status, Status::Todo);
+
+    let warnings = store.claim(id, DEFAULT_LEASE_SECS, false).unwrap();
+    assert!(warnings.is_empty());
+    let t = store.get(id).unwrap();
+    assert_eq!(t.status, Status::InProgress);
+    assert_eq!(t.claimed_by.as_deref(), Some("worker"));
+
+    store
+        .update(id, &TicketPatch::new().status(Status::Done))
+        .unwrap();
+    let e = store.export(id).unwrap();
+    assert_eq!(e.status, Status::Done);
+    assert_eq!(e.labels, vec!["db".to_string()]);
+
+    let removed = store.delete(id, ChildPolicy::Refuse).unwrap();
+    assert_eq!(removed.name, "Write schema");
+    assert!(matches!(store.get(id), Err(AppError::NotFound(1))));
+}
+
+#[test]
+fn store_invalid_transition_is_an_error_not_an_exit() {
+    let (store, _tmp) = open_store();
+    let id = create(&store, "Skip ahead");
+    let err = store
+        .update(id, &TicketPatch::new().status(Status::Done))
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_transition");
+    assert_eq!(store.get(id).unwrap().status, Status::Todo);
+}
+
+#[test]
+fn store_claims_need_an_agent() {
+    let (mut store, _tmp) = open_store();
+    let id = create(&store, "Unowned");
+    assert!(matches!(
+        store.claim(id, DEFAULT_LEASE_SECS, false),
+        Err(AppError::AgentNotSet)
+    ));
+    assert!(store.claim_next(DEFAULT_LEASE_SECS).is_err());
+
+    assert_eq!(store.set_agent(Some("late".into())), None);
+    let t = store.claim_next(DEFAULT_LEASE_SECS).unwrap().unwrap();
+    assert_eq!(t.id, 1);
+    assert!(store.claim_next(DEFAULT_LEASE_SECS).unwrap().is_none());
+}
+
+#[test]
+fn store_records_its_agent_as_the_actor() {
+    let (store, _tmp) = open_store();
+    let mut store = store.with_agent("planner");
+    let id = create(&store, "Tracked");
+    store.add_note(id, "first pass").unwrap();
+    store.set_agent(Some("reviewer".into()));
+    store.claim(id, DEFAULT_LEASE_SECS, false).unwrap();
+
+    let events = store.events(Some(id), None).unwrap();
+    let actors: Vec<(&str, Option<&str>)> = events
+        .iter()
+        .map(|e| (e.kind.as_str(), e.actor.as_deref()))
+        .collect();
+    assert!(actors.contains(&("created", Some("planner"))));
+    assert!(actors.contains(&("note", Some("planner"))));
+    assert!(actors.contains(&("claim", Some("reviewer"))));
+
+    let notes = store.notes(id, None).unwrap();
+    assert_eq!(notes[0].author.as_deref(), Some("planner"));
+}
+
+#[test]
+fn store_wraps_a_plain_connection() {
+    let tmp = tempfile::NamedTempFile::new().expect("tempfile");
+    drop(Store::open(tmp.path()).unwrap());
+
+    // A connection opened without `db::open_connection` has no workflow yet.
+    let conn = rusqlite::Connection::open(tmp.path()).unwrap();
+    let store = Store::from_connection(conn).unwrap().with_agent("lib");
+    let id = create(&store, "Plain");
+    store.block(id, "waiting").unwrap();
+    store
+        .save_view("mine", &["--claimer".into(), "lib".into()])
+        .unwrap();
+
+    let events = store.events(Some(id), None).unwrap();
+    assert!(events.iter().all(|e| e.actor.as_deref() == Some("lib")));
+    assert_eq!(store.views().unwrap()[0].created_by.as_deref(), Some("lib"));
+}
+
+#[test]
+fn store_list_with_filter_builder() {
+    let (store, _tmp) = open_store();
+    let a = create(&store, "Alpha");
+    let b = create(&store, "Beta");
+    let c = create(&store, "Gamma");
+    store
+        .update(a, &TicketPatch::new().add_label("api"))
+        .unwrap();
+    store
+        .update(b, &TicketPatch::new().add_label("api").priority(0))
+        .unwrap();
+    store.add_dep(c, b).unwrap();
+
+    let page = store
+        .list(
+            &ListFilter::new()
+                .label("api")
+                .sort(SortField::Priority, false),
+        )
+        .unwrap();
+    let names: Vec<&str> = page.tickets.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["Beta", "Alpha"]);
+
+    let ready = store.list(&ListFilter::new().ready()).unwrap();
+    assert!(ready.tickets.iter().all(|t| c != t.id));
+
+    let page = store.list(&ListFilter::new().limit(1)).unwrap();
+    assert_eq!(page.tickets.len(), 1);
+    assert!(page.next_cursor.is_some());
+
+    assert_eq!(store.deps(c).unwrap().forward, vec![b.get()]);
+}
+
+#[test]
+fn status_and_ticket_id_parse_and_display() {
+    assert_eq!("in_progress".parse::<Status>().unwrap(), Status::InProgress);
+    assert_eq!("DONE".parse::<Status>().unwrap(), Status::Done);
+    assert_eq!(
+        "Shipped".parse::<Status>().unwrap(),
+        Status::Custom("shipped".into())
+    );
+    assert!(matches!(
+        "ship it!".parse::<Status>(),
+        Err(AppError::InvalidStatus(s, _)) if s == "ship it!"
+    ));
+    assert_eq!(Status::InProgress.to_string(), "in-progress");
+    assert_eq!(
+        serde_json::to_value(Status::Blocked).unwrap(),
+        serde_json::json!("blocked")
+    );
+
+    assert_eq!("#12".parse::<TicketId>().unwrap(), TicketId(12));
+    assert_eq!("7".parse::<TicketId>().unwrap(), 7);
+    assert_eq!(TicketId(3).to_string(), "#3");
+    assert!(TicketPatch::new().is_empty());
+}
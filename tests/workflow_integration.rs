@@ -0,0 +1,294 @@
+use rtik::config::Config;
+use rtik::ticket::{NewTicket, DEFAULT_LEASE_SECS};
+use rtik::workflow::Workflow;
+use rtik::{AppError, ListFilter, Status, Store, TicketPatch};
+use std::process::Command;
+
+const REVIEW_FLOW: &str = r#"
+[[workflow.status]]
+name = "in-progress"
+next = ["review", "blocked", "todo"]
+
+[[workflow.status]]
+name = "review"
+next = ["qa", "in-progress"]
+clears_claim = true
+
+[[workflow.status]]
+name = "qa"
+next = ["done", "in-progress"]
+done = true
+"#;
+
+fn review() -> Status {
+    Status::Custom("review".into())
+}
+
+fn qa() -> Status {
+    Status::Custom("qa".into())
+}
+
+fn workflow(toml: &str) -> Result<Workflow, AppError> {
+    Workflow::from_config(&Config::parse(toml)?.workflow)
+}
+
+fn open_store(toml: &str) -> (Store, tempfile::TempPath) {
+    let tmp = tempfile::NamedTempFile::new().expect("tempfile");
+    let store = Store::open(tmp.path()).unwrap().with_agent("dev");
+    store.set_workflow(&workflow(toml).unwrap()).unwrap();
+    (store, tmp.into_temp_path())
+}
+
+fn create(store: &Store, name: &str) -> i64 {
+    store
+        .create(&NewTicket {
+            name,
+            ..Default::default()
+        })
+        .unwrap()
+        .get()
+}
+
+#[test]
+fn default_workflow_is_the_builtin_graph() {
+    let wf = workflow("").unwrap();
+    assert_eq!(wf, Workflow::default());
+    let names: Vec<&str> = wf.statuses().iter().map(|d| d.status.as_str()).collect();
+    assert_eq!(names, ["todo", "in-progress", "blocked", "done"]);
+    assert!(wf
+        .check_transition(&Status::Todo, &Status::InProgress)
+        .is_ok());
+    assert!(wf.check_transition(&Status::Todo, &Status::Done).is_err());
+    assert!(wf.is_done(&Status::Done));
+    assert!(wf.get(&Status::Done).unwrap().clears_claim);
+
+    let (store, _tmp) = open_store("");
+    assert_eq!(store.workflow().unwrap(), wf);
+    let id = create(&store, "Plain");
+    let err = store
+        .update(id, &TicketPatch::new().status(review()))
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_status");
+    assert!(err
+        .to_string()
+        .contains("must be one of todo, in-progress, blocked, done"));
+}
+
+#[test]
+fn custom_statuses_follow_configured_transitions_and_flags() {
+    let (mut store, _tmp) = open_store(REVIEW_FLOW);
+    let names: Vec<String> = store
+        .workflow()
+        .unwrap()
+        .statuses()
+        .iter()
+        .map(|d| d.status.to_string())
+        .collect();
+    assert_eq!(
+        names,
+        ["todo", "in-progress", "blocked", "review", "qa", "done"]
+    );
+
+    let api = create(&store, "API");
+    let docs = create(&store, "Docs");
+    store.add_dep(docs, api).unwrap();
+
+    store.claim(api, DEFAULT_LEASE_SECS, false).unwrap();
+    // done is no longer reachable straight from in-progress.
+    let err = store
+        .update(api, &TicketPatch::new().status(Status::Done))
+        .unwrap_err();
+    assert!(err.to_string().contains("review, blocked, todo"));
+
+    // review clears the claim.
+    let t = store
+        .update(api, &TicketPatch::new().status(review()))
+        .unwrap();
+    assert_eq!(t.status, review());
+    assert_eq!(t.claimed_by, None);
+    assert!(!store.export(docs).unwrap().ready);
+
+    // qa counts as done, so the dependent becomes ready.
+    store.update(api, &TicketPatch::new().status(qa())).unwrap();
+    let docs_export = store.export(docs).unwrap();
+    assert!(docs_export.ready);
+    assert!(!docs_export.waiting);
+    assert!(store.plan(None).unwrap()[0]
+        .tickets
+        .iter()
+        .all(|t| t.id != api));
+
+    let page = store.list(&ListFilter::new().status(qa())).unwrap();
+    assert_eq!(page.tickets.len(), 1);
+    assert!(matches!(
+        store.list(&ListFilter::new().status(Status::Custom("staging".into()))),
+        Err(AppError::InvalidStatus(..))
+    ));
+    let query = rtik::query::parse("status:(review,staging)").unwrap();
+    assert!(store.list(&ListFilter::new().query(query)).is_err());
+}
+
+#[test]
+fn markdown_and_budget_follow_workflow_order() {
+    use rtik::markdown::{render_markdown, Grouping};
+    let (mut store, _tmp) = open_store(REVIEW_FLOW);
+    let api = create(&store, "API");
+    let docs = create(&store, "Docs");
+    create(&store, "Ops");
+    for (id, statuses) in [(api, vec![review(), qa()]), (docs, vec![review()])] {
+        store.claim(id, DEFAULT_LEASE_SECS, false).unwrap();
+        for status in statuses {
+            store
+                .update(id, &TicketPatch::new().status(status))
+                .unwrap();
+        }
+    }
+    let wf = store.workflow().unwrap();
+    let exports = store.export_page(&ListFilter::new()).unwrap().tickets;
+
+    let md = render_markdown(&exports, Grouping::Status, &wf, store.ids());
+    let headings: Vec<&str> = md.lines().filter(|l| l.starts_with("## ")).collect();
+    assert_eq!(headings, ["## Todo (1)", "## Review (1)", "## Qa (1)"]);
+    // qa is a done status, so its tickets are checked off.
+    assert!(md.contains("- [x] #1 **API**\n"));
+    assert!(md.contains("- [ ] #2 **Docs**\n"));
+
+    let budgeted = rtik::budget::render_budgeted(&exports, None, 20, &wf, store.ids());
+    assert!(
+        budgeted.ends_with("+1 more todo, 1 review, 1 qa\n"),
+        "{budgeted}"
+    );
+}
+
+#[test]
+fn claims_move_between_configured_statuses() {
+    let (mut store, _tmp) = open_store(
+        r#"
+[workflow]
+claim = "working"
+release = "parked"
+
+[[workflow.status]]
+name = "todo"
+next = ["parked", "blocked"]
+
+[[workflow.status]]
+name = "parked"
+next = ["working"]
+
+[[workflow.status]]
+name = "working"
+next = ["done", "parked"]
+"#,
+    );
+    let working = Status::Custom("working".into());
+    let parked = Status::Custom("parked".into());
+    let a = create(&store, "A");
+    let b = create(&store, "B");
+    store
+        .update(a, &TicketPatch::new().status(parked.clone()))
+        .unwrap();
+
+    // Only the release status is ready, so next skips the todo ticket.
+    let ready = store.list(&ListFilter::new().ready()).unwrap().tickets;
+    assert_eq!(ready.iter().map(|t| t.id).collect::<Vec<_>>(), [a]);
+    let t = store.claim_next(DEFAULT_LEASE_SECS).unwrap().unwrap();
+    assert_eq!((t.id, &t.status), (a, &working));
+    store.release(a, false).unwrap();
+    assert_eq!(store.get(a).unwrap().status, parked);
+
+    store.claim(b, 0, false).unwrap();
+    assert_eq!(store.get(b).unwrap().status, working);
+    let reaped = store.reap().unwrap();
+    assert_eq!(reaped[0].previous_status, working);
+    assert_eq!(reaped[0].restored_status, Status::Todo);
+
+    let err = workflow("[workflow]\nrelease = \"shipped\"\n").unwrap_err();
+    assert_eq!(err.code(), "invalid_config");
+    assert!(err
+        .to_string()
+        .contains("release status 'shipped' is not in the workflow"));
+}
+
+#[test]
+fn tree_rollup_counts_configured_done_statuses() {
+    let (mut store, _tmp) = open_store(REVIEW_FLOW);
+    let epic = create(&store, "Epic");
+    let child = store
+        .create(&NewTicket {
+            name: "Child",
+            parent: Some(epic),
+            ..Default::default()
+        })
+        .unwrap()
+        .get();
+    store.claim(child, DEFAULT_LEASE_SECS, false).unwrap();
+    for status in [review(), qa()] {
+        store
+            .update(child, &TicketPatch::new().status(status))
+            .unwrap();
+    }
+    assert_eq!(store.tree(epic).unwrap().rollup(), (1, 1));
+}
+
+#[test]
+fn invalid_workflow_configs_are_rejected() {
+    let err = workflow(
+        r#"
+[[workflow.status]]
+name = "review"
+next = ["shipped"]
+"#,
+    )
+    .unwrap_err();
+    assert_eq!(err.code(), "invalid_config");
+    assert!(err.to_string().contains("unknown next status 'shipped'"));
+
+    let twice = "[[workflow.status]]\nname = \"qa\"\n[[workflow.status]]\nname = \"qa\"\n";
+    assert!(workflow(twice)
+        .unwrap_err()
+        .to_string()
+        .contains("listed twice"));
+
+    let typo = "[[workflow.status]]\nname = \"qa\"\nclear_claim = true\n";
+    assert_eq!(workflow(typo).unwrap_err().code(), "invalid_config");
+
+    let bad_name = "[[workflow.status]]\nname = \"needs review\"\n";
+    assert_eq!(workflow(bad_name).unwrap_err().code(), "invalid_config");
+}
+
+#[test]
+fn cli_reads_workflow_from_config_beside_database() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(".rtik.toml"), REVIEW_FLOW).unwrap();
+    let rtik = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rtik"))
+            .args(args)
+            .env("RTIK_DB", dir.path().join("tickets.db"))
+            .env("RTIK_AGENT", "dev")
+            .env_remove("RTIK_OUTPUT")
+            .output()
+            .unwrap()
+    };
+    assert!(rtik(&["create", "--name", "Ship it"]).status.success());
+    assert!(rtik(&["claim", "1"]).status.success());
+    assert!(rtik(&["update", "1", "--status", "review"])
+        .status
+        .success());
+
+    let out = rtik(&["list", "--status", "review", "--json"]);
+    let tickets: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(tickets[0]["status"], "review");
+    assert_eq!(tickets[0]["claimed_by"], serde_json::Value::Null);
+
+    let out = rtik(&["update", "1", "--status", "staging", "--json"]);
+    assert!(!out.status.success());
+    let err: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    assert_eq!(err["code"], "invalid_status");
+
+    std::fs::write(dir.path().join(".rtik.toml"), "[workflow]\nstatuses = 1\n").unwrap();
+    let out = rtik(&["list", "--json"]);
+    assert!(!out.status.success());
+    let err: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    assert_eq!(err["code"], "invalid_config");
+}